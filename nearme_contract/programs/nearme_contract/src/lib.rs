@@ -1,5 +1,11 @@
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{
+    close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+    TransferChecked,
+};
+use solana_sha256_hasher::hash;
 
 declare_id!("CzvToWP9ryYfPkdJ8wxahvJwQKQ9aWLpAvdhYszHYTNd");
 
@@ -9,13 +15,786 @@ const MAX_MERCHANT_ID_LEN: usize = 32;
 /// Maximum length for business name (64 bytes)
 const MAX_BUSINESS_NAME_LEN: usize = 64;
 
-/// Registration fee in lamports (0.01 SOL = 10,000,000 lamports)
-const REGISTRATION_FEE: u64 = 10_000_000;
+/// Basis points denominator (100% = 10,000 bps)
+const MAX_BASIS_POINTS: u16 = 10_000;
+
+/// Maximum length for a payment request memo (100 bytes)
+const MAX_MEMO_LEN: usize = 100;
+/// Max evidence hashes each side can attach to a dispute
+const MAX_DISPUTE_EVIDENCE: usize = 4;
+/// Max staff legs a tip can be split into (also the max tip pool size)
+const MAX_TIP_LEGS: usize = 8;
+/// Max length of a review content URI in bytes
+const MAX_REVIEW_URI_LEN: usize = 200;
+/// Loyalty earn rates are expressed in points per this many base units paid
+const LOYALTY_EARN_RATE_UNIT: u64 = 1_000_000;
+
+/// Delegate permission: create and cancel payment requests
+const PERM_ISSUE_INVOICES: u8 = 1 << 0;
+/// Delegate permission: refund payments up to the delegate's refund limit
+const PERM_REFUND: u8 = 1 << 1;
+/// Delegate permission: update the business profile (name, location), open or close
+/// the business and reply to reviews
+const PERM_UPDATE_PROFILE: u8 = 1 << 2;
+/// Delegate permission: manage loyalty programs
+const PERM_MANAGE_LOYALTY: u8 = 1 << 3;
+/// Delegate permission: manage staff and the tip pool
+const PERM_MANAGE_STAFF: u8 = 1 << 4;
+/// Delegate permission: create and close coupons
+const PERM_MANAGE_PROMOTIONS: u8 = 1 << 5;
+/// Delegate permission: edit the merchant's accepted-mint list
+const PERM_MANAGE_MINTS: u8 = 1 << 6;
+/// Delegate permission: claim escrows and open or answer disputes on them
+const PERM_MANAGE_ESCROW: u8 = 1 << 7;
+
+/// Mint recorded on receipts for native SOL payments
+pub const NATIVE_SOL_MINT: Pubkey = Pubkey::new_from_array([0; 32]);
+
+/// Extra merchant PDA seed for a wallet's `generation`-th registration.
+///
+/// The first registration uses no extra seed, so accounts created before generations
+/// existed keep their address; later ones get a fresh PDA and never see the stale
+/// stats, reviews or loyalty accounts of a closed predecessor.
+fn generation_seed(generation: u32) -> Vec<u8> {
+    if generation == 0 {
+        Vec::new()
+    } else {
+        generation.to_le_bytes().to_vec()
+    }
+}
+
+/// Validate a business name (1 to 64 bytes)
+fn validate_business_name(business_name: &str) -> Result<()> {
+    require!(
+        business_name.len() <= MAX_BUSINESS_NAME_LEN && !business_name.is_empty(),
+        ErrorCode::InvalidBusinessName
+    );
+    Ok(())
+}
+
+/// Validate coordinates multiplied by 1,000,000
+fn validate_coordinates(lat: i64, lng: i64) -> Result<()> {
+    // Validate latitude (-90 to +90 degrees * 1,000,000)
+    require!(
+        (-90_000_000..=90_000_000).contains(&lat),
+        ErrorCode::InvalidLatitude
+    );
+
+    // Validate longitude (-180 to +180 degrees * 1,000,000)
+    require!(
+        (-180_000_000..=180_000_000).contains(&lng),
+        ErrorCode::InvalidLongitude
+    );
+    Ok(())
+}
+
+/// Metres per degree of latitude (and of longitude at the equator)
+const METERS_PER_DEGREE: i128 = 111_320;
+
+/// Fixed-point scale of the integer cosine (1.0 = 1,000,000)
+const COS_SCALE: i128 = 1_000_000;
+
+/// Integer cosine of a latitude given in degrees * 1,000,000, scaled by COS_SCALE
+///
+/// Uses Bhaskara I's sine approximation on sin(90° - |lat|); max error is about 0.2%.
+fn cos_microdegrees(lat: i64) -> i128 {
+    let y = 90_000_000 - (lat as i128).abs();
+    let p = y * (180_000_000 - y);
+    4 * p * COS_SCALE / (40_500_000_000_000_000 - p)
+}
+
+/// Integer square root (floor)
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Approximate distance in metres between two coordinates (degrees * 1,000,000)
+///
+/// Equirectangular projection around the mean latitude, integer arithmetic only.
+/// Accurate to well under 1% at the short ranges used for location proofs.
+fn approx_distance_m(lat1: i64, lng1: i64, lat2: i64, lng2: i64) -> u64 {
+    let dlat = (lat2 as i128 - lat1 as i128).abs();
+    let mut dlng = (lng2 as i128 - lng1 as i128).abs();
+    // Take the short way around the antimeridian
+    if dlng > 180_000_000 {
+        dlng = 360_000_000 - dlng;
+    }
+
+    let cos_lat = cos_microdegrees((lat1 + lat2) / 2);
+    // Both legs in metres * 1,000,000
+    let dy = dlat * METERS_PER_DEGREE;
+    let dx = dlng * METERS_PER_DEGREE * cos_lat / COS_SCALE;
+
+    (isqrt((dy * dy + dx * dx) as u128) / 1_000_000) as u64
+}
+
+/// Validate a review rating (1-5 stars) and content URI
+fn validate_review(rating: u8, uri: &str) -> Result<()> {
+    require!((1..=5).contains(&rating), ErrorCode::InvalidRating);
+    require!(uri.len() <= MAX_REVIEW_URI_LEN, ErrorCode::ReviewUriTooLong);
+    Ok(())
+}
+
+/// Protocol fee owed on a payment: `amount * fee_bps / 10,000`, rounded down
+/// and capped at `cap` (0 = no cap)
+fn compute_protocol_fee(amount: u64, fee_bps: u16, cap: u64) -> u64 {
+    // Cannot exceed `amount` because fee_bps <= 10,000
+    let fee = (amount as u128 * fee_bps as u128 / MAX_BASIS_POINTS as u128) as u64;
+    if cap > 0 {
+        fee.min(cap)
+    } else {
+        fee
+    }
+}
+
+/// Protocol fee cap for a token payment: the cap on the mint's global accepted-mint
+/// entry, whichever entry accepted the payment. Mints that are only merchant-listed
+/// have no cap.
+fn token_fee_cap(global_accepted_mint: &AccountInfo) -> Result<u64> {
+    if global_accepted_mint.owner != &crate::ID || global_accepted_mint.data_is_empty() {
+        return Ok(0);
+    }
+    let entry = AcceptedMint::try_deserialize(&mut &global_accepted_mint.try_borrow_data()?[..])?;
+    Ok(entry.protocol_fee_cap)
+}
+
+/// Pay escrowed funds out to the merchant, treasury and payer, close the token
+/// vault and clear the escrow from the receipt and the merchant's obligations.
+/// The escrow account itself is closed to the payer by the `SettleEscrow` context.
+fn settle_escrow(
+    accounts: &mut SettleEscrow,
+    to_merchant: u64,
+    to_treasury: u64,
+    to_payer: u64,
+) -> Result<()> {
+    let escrow = &accounts.escrow;
+    require!(
+        escrow.status == EscrowStatus::Held || accounts.dispute.is_some(),
+        ErrorCode::EscrowDisputed
+    );
+
+    if escrow.mint == NATIVE_SOL_MINT {
+        require!(accounts.mint.is_none(), ErrorCode::WrongPaymentMint);
+
+        // The escrow PDA is program-owned, so lamports can be moved directly
+        let escrow_info = escrow.to_account_info();
+        for (recipient, amount) in [
+            (accounts.merchant.to_account_info(), to_merchant),
+            (accounts.treasury.to_account_info(), to_treasury),
+            (accounts.payer.to_account_info(), to_payer),
+        ] {
+            if amount > 0 {
+                escrow_info.sub_lamports(amount)?;
+                recipient.add_lamports(amount)?;
+            }
+        }
+    } else {
+        let mint = accounts.mint.as_ref().ok_or(ErrorCode::WrongPaymentMint)?;
+        require_keys_eq!(mint.key(), escrow.mint, ErrorCode::WrongPaymentMint);
+        let (Some(vault), Some(token_program)) =
+            (accounts.vault.as_ref(), accounts.token_program.as_ref())
+        else {
+            return err!(ErrorCode::MissingTokenAccounts);
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[b"escrow", escrow.receipt.as_ref(), &[escrow.bump]]];
+        for (recipient, amount) in [
+            (accounts.merchant_token_account.as_ref(), to_merchant),
+            (accounts.treasury_token_account.as_ref(), to_treasury),
+            (accounts.payer_token_account.as_ref(), to_payer),
+        ] {
+            if amount > 0 {
+                let recipient = recipient.ok_or(ErrorCode::MissingTokenAccounts)?;
+                let cpi_context = CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: recipient.to_account_info(),
+                        authority: escrow.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                transfer_checked(cpi_context, amount, mint.decimals)?;
+            }
+        }
+
+        // Return the vault rent to the payer
+        let cpi_context = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            CloseAccount {
+                account: vault.to_account_info(),
+                destination: accounts.payer.to_account_info(),
+                authority: escrow.to_account_info(),
+            },
+            signer_seeds,
+        );
+        close_account(cpi_context)?;
+    }
+
+    let receipt = &mut accounts.receipt;
+    receipt.escrowed = false;
+    if to_payer > 0 {
+        receipt.refunded_amount = receipt
+            .refunded_amount
+            .checked_add(to_payer)
+            .ok_or(ErrorCode::RefundExceedsPayment)?;
+        accounts.merchant_stats.record_refund()?;
+        accounts.mint_stats.record_refund(to_payer)?;
+    }
+
+    let merchant_account = &mut accounts.merchant_account;
+    merchant_account.open_obligations = merchant_account.open_obligations.saturating_sub(1);
+
+    msg!(
+        "Escrow {} settled by {}: merchant={}, treasury={}, payer={}",
+        accounts.escrow.key(),
+        accounts.authority.key(),
+        to_merchant,
+        to_treasury,
+        to_payer
+    );
+
+    emit!(EscrowSettledEvent {
+        escrow: accounts.escrow.key(),
+        receipt: accounts.escrow.receipt,
+        settled_by: accounts.authority.key(),
+        to_merchant,
+        to_treasury,
+        to_payer,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Check that taking `amount` lamports out of a refund vault leaves it either empty
+/// or rent-exempt, since a system account cannot hold less.
+fn check_vault_withdrawal(refund_vault: &AccountInfo, amount: u64) -> Result<()> {
+    let remaining = refund_vault
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(ErrorCode::RefundVaultInsufficient)?;
+    require!(
+        remaining == 0 || remaining >= Rent::get()?.minimum_balance(0),
+        ErrorCode::RefundVaultInsufficient
+    );
+    Ok(())
+}
+
+/// Pay a tip to one staff member or split it across the merchant's tip pool by weight.
+///
+/// `staff_accounts` holds (Staff PDA, staff wallet) pairs. A pool split must list
+/// every pool member exactly once; rounding dust goes to the last leg.
+fn pay_tips<'info>(
+    merchant_account: &Account<'info, MerchantAccount>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    staff_accounts: &'info [AccountInfo<'info>],
+    tip: u64,
+    split_tip: bool,
+) -> Result<Vec<TipLeg>> {
+    if tip == 0 {
+        return Ok(Vec::new());
+    }
+    let pairs = staff_accounts.chunks_exact(2);
+    require!(
+        !staff_accounts.is_empty() && pairs.remainder().is_empty(),
+        ErrorCode::InvalidTipAccounts
+    );
+
+    let mut members: Vec<(Account<'info, Staff>, &'info AccountInfo<'info>)> = Vec::new();
+    for pair in pairs {
+        let staff = Account::<Staff>::try_from(&pair[0])?;
+        require_keys_eq!(
+            staff.merchant_account,
+            merchant_account.key(),
+            ErrorCode::InvalidTipAccounts
+        );
+        require_keys_eq!(pair[1].key(), staff.wallet, ErrorCode::InvalidTipAccounts);
+        require!(
+            !members
+                .iter()
+                .any(|(member, _)| member.wallet == staff.wallet),
+            ErrorCode::InvalidTipAccounts
+        );
+        members.push((staff, &pair[1]));
+    }
+
+    let total_weight: u64 = if split_tip {
+        require!(
+            members.len() == merchant_account.tip_pool_size as usize
+                && members.iter().all(|(staff, _)| staff.weight > 0),
+            ErrorCode::IncompleteTipPool
+        );
+        members.iter().map(|(staff, _)| staff.weight as u64).sum()
+    } else {
+        require!(members.len() == 1, ErrorCode::InvalidTipAccounts);
+        1
+    };
+
+    let mut legs = Vec::with_capacity(members.len());
+    let mut distributed = 0u64;
+    for (i, (staff, wallet)) in members.iter().enumerate() {
+        let share = if i + 1 == members.len() {
+            tip - distributed
+        } else if split_tip {
+            (tip as u128 * staff.weight as u128 / total_weight as u128) as u64
+        } else {
+            tip
+        };
+        distributed += share;
+
+        if share > 0 {
+            let cpi_context = CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: payer.to_account_info(),
+                    to: wallet.to_account_info(),
+                },
+            );
+            transfer(cpi_context, share)?;
+        }
+
+        legs.push(TipLeg {
+            staff: staff.wallet,
+            amount: share,
+        });
+    }
+
+    Ok(legs)
+}
+
+/// Apply a coupon to a payment of `amount` in `mint` and count the use.
+///
+/// Does nothing unless a coupon is passed, in which case the payer's clipped usage
+/// entry is required. Returns the discount taken.
+fn apply_coupon(
+    coupon: Option<&mut Account<Coupon>>,
+    usage: Option<&mut Account<CouponUsage>>,
+    mint: Pubkey,
+    amount: u64,
+) -> Result<u64> {
+    let Some(coupon) = coupon else {
+        return Ok(0);
+    };
+    let usage = usage.ok_or(ErrorCode::CouponNotClipped)?;
+    require_keys_eq!(usage.coupon, coupon.key(), ErrorCode::CouponNotClipped);
+    require_keys_eq!(coupon.mint, mint, ErrorCode::WrongPaymentMint);
+
+    let now = Clock::get()?.unix_timestamp;
+    coupon.check_window(now)?;
+    require!(
+        coupon.max_uses == 0 || coupon.uses < coupon.max_uses,
+        ErrorCode::CouponExhausted
+    );
+    require!(
+        coupon.per_wallet_limit == 0 || usage.uses < coupon.per_wallet_limit,
+        ErrorCode::CouponWalletLimitReached
+    );
+
+    coupon.uses = coupon.uses.checked_add(1).ok_or(ErrorCode::StatsOverflow)?;
+    usage.uses = usage.uses.checked_add(1).ok_or(ErrorCode::StatsOverflow)?;
+    let discount = coupon.discount.apply(amount);
+
+    emit!(CouponRedeemedEvent {
+        coupon: coupon.key(),
+        merchant_account: coupon.merchant_account,
+        wallet: usage.wallet,
+        discount,
+        uses: coupon.uses,
+        timestamp: now,
+    });
+
+    Ok(discount)
+}
+
+/// Take a cancelled or closed payment request out of the merchant's count.
+/// Requests created before the count existed are not in it.
+fn end_payment_request(merchant_account: &mut MerchantAccount) {
+    merchant_account.payment_request_count =
+        merchant_account.payment_request_count.saturating_sub(1);
+}
+
+/// Apply a customer's loyalty balance to a payment of `amount` in `mint`.
+///
+/// A passed balance is always initialized, even when it ends up untouched, so a balance
+/// created by this payment is usable afterwards; a new balance is counted on the program,
+/// which must then be passed too. Nothing else happens unless the program
+/// is passed and in `mint`. When `allow_discount` is set, any pending discount is taken
+/// off `amount`, even while the program is paused, so a discount redeemed before a pause
+/// is never stranded. Only an active program then forfeits points past its expiry and
+/// credits points on what is actually paid. Returns the discount taken.
+#[allow(clippy::too_many_arguments)]
+fn apply_loyalty(
+    mut program: Option<&mut Account<LoyaltyProgram>>,
+    balance: Option<&mut Account<LoyaltyBalance>>,
+    bump: Option<u8>,
+    merchant_account: Pubkey,
+    customer: Pubkey,
+    mint: Pubkey,
+    amount: u64,
+    allow_discount: bool,
+) -> Result<u64> {
+    let (Some(balance), Some(bump)) = (balance, bump) else {
+        return Ok(0);
+    };
+    if balance.merchant_account == Pubkey::default() {
+        let program = program.as_mut().ok_or(ErrorCode::MissingLoyaltyProgram)?;
+        program.balance_count = program
+            .balance_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+    }
+    balance.merchant_account = merchant_account;
+    balance.customer = customer;
+    balance.bump = bump;
+
+    let Some(program) = program else {
+        return Ok(0);
+    };
+    if mint != program.mint {
+        return Ok(0);
+    }
+
+    let discount = if allow_discount {
+        balance.pending_discount.min(amount)
+    } else {
+        0
+    };
+    balance.pending_discount -= discount;
+    if !program.is_active {
+        return Ok(discount);
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    balance.expire(program, now);
+
+    let points = program.points_for(amount - discount);
+    if points > 0 {
+        balance.points = balance
+            .points
+            .checked_add(points)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        balance.lifetime_points = balance.lifetime_points.saturating_add(points);
+        balance.last_activity_at = now;
+
+        emit!(LoyaltyPointsEarnedEvent {
+            merchant_account: balance.merchant_account,
+            customer,
+            points,
+            balance: balance.points,
+            timestamp: now,
+        });
+    }
+
+    Ok(discount)
+}
+
+/// Settle a disputed escrow, refunding `payer_share_bps` of it to the customer and
+/// releasing the rest to the merchant with a proportional share of the protocol fee.
+/// The dispute account is closed to the payer by the `SettleEscrow` context.
+fn rule_dispute(accounts: &mut SettleEscrow, payer_share_bps: u16, by_default: bool) -> Result<()> {
+    require!(
+        payer_share_bps <= MAX_BASIS_POINTS,
+        ErrorCode::InvalidFeeBps
+    );
+    let dispute = accounts
+        .dispute
+        .as_ref()
+        .ok_or(ErrorCode::EscrowNotDisputed)?;
+    let dispute_key = dispute.key();
+
+    let (to_merchant, to_treasury, to_payer) = accounts.escrow.split(payer_share_bps);
+
+    msg!(
+        "Dispute {} resolved by {}: {} bps to the customer{}",
+        dispute_key,
+        accounts.authority.key(),
+        payer_share_bps,
+        if by_default { " (default outcome)" } else { "" }
+    );
+
+    emit!(DisputeResolvedEvent {
+        dispute: dispute_key,
+        escrow: accounts.escrow.key(),
+        resolved_by: accounts.authority.key(),
+        payer_share_bps,
+        to_merchant,
+        to_treasury,
+        to_payer,
+        by_default,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    settle_escrow(accounts, to_merchant, to_treasury, to_payer)
+}
 
 #[program]
 pub mod nearme_contract {
     use super::*;
 
+    /// Initialize the global program config (one-time setup)
+    ///
+    /// # Arguments
+    /// * `treasury` - Wallet that receives registration and protocol fees
+    /// * `registration_fee` - Registration fee in lamports
+    /// * `protocol_fee_bps` - Protocol fee on payments in basis points (max 10,000)
+    /// * `protocol_fee_cap` - Max protocol fee per native SOL payment in lamports (0 = no cap);
+    ///   token payments use the cap on the mint's global accepted-mint entry
+    /// * `max_proof_distance_m` - Max distance in metres between a merchant and its location proof
+    /// * `escrow_dispute_window` - Seconds after funding during which an escrow can be disputed
+    /// * `dispute_ruling_window` - Seconds arbiters have to rule before the default outcome applies
+    /// * `dispute_default_refund_bps` - Share refunded to the customer when nobody rules
+    ///
+    /// # Security
+    /// - Only the program's upgrade authority can initialize the config
+    /// - The signer becomes the config admin
+    /// - PDA seeds: [b"config"] (singleton)
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        treasury: Pubkey,
+        registration_fee: u64,
+        protocol_fee_bps: u16,
+        protocol_fee_cap: u64,
+        max_proof_distance_m: u32,
+        escrow_dispute_window: i64,
+        dispute_ruling_window: i64,
+        dispute_default_refund_bps: u16,
+    ) -> Result<()> {
+        require!(
+            protocol_fee_bps <= MAX_BASIS_POINTS && dispute_default_refund_bps <= MAX_BASIS_POINTS,
+            ErrorCode::InvalidFeeBps
+        );
+        require!(
+            escrow_dispute_window >= 0 && dispute_ruling_window >= 0,
+            ErrorCode::InvalidDisputeWindow
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.treasury = treasury;
+        config.registration_fee = registration_fee;
+        config.protocol_fee_bps = protocol_fee_bps;
+        config.protocol_fee_cap = protocol_fee_cap;
+        config.max_proof_distance_m = max_proof_distance_m;
+        config.escrow_dispute_window = escrow_dispute_window;
+        config.dispute_ruling_window = dispute_ruling_window;
+        config.dispute_default_refund_bps = dispute_default_refund_bps;
+        config.bump = ctx.bumps.config;
+
+        msg!(
+            "Config initialized: admin={}, treasury={}, registration_fee={} lamports, protocol_fee={} bps (cap {} lamports), max_proof_distance={} m, escrow_dispute_window={} s, dispute_ruling_window={} s, dispute_default_refund={} bps",
+            config.admin,
+            treasury,
+            registration_fee,
+            protocol_fee_bps,
+            protocol_fee_cap,
+            max_proof_distance_m,
+            escrow_dispute_window,
+            dispute_ruling_window,
+            dispute_default_refund_bps
+        );
+
+        emit!(ConfigUpdatedEvent {
+            admin: config.admin,
+            treasury,
+            registration_fee,
+            protocol_fee_bps,
+            protocol_fee_cap,
+            max_proof_distance_m,
+            escrow_dispute_window,
+            dispute_ruling_window,
+            dispute_default_refund_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Update the global program config (admin only)
+    ///
+    /// Every argument is optional; `None` leaves the current value unchanged.
+    ///
+    /// # Arguments
+    /// * `new_admin` - Hand the admin role over to another wallet
+    /// * `treasury` - New treasury wallet
+    /// * `registration_fee` - New registration fee in lamports
+    /// * `protocol_fee_bps` - New protocol fee in basis points (max 10,000)
+    /// * `protocol_fee_cap` - New max protocol fee per native SOL payment in lamports
+    ///   (0 = no cap); token caps are set per mint with `add_accepted_mint`
+    /// * `max_proof_distance_m` - New max merchant-to-proof distance in metres
+    /// * `escrow_dispute_window` - New escrow dispute window in seconds
+    /// * `dispute_ruling_window` - New dispute ruling window in seconds
+    /// * `dispute_default_refund_bps` - New customer share when a dispute times out
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_admin: Option<Pubkey>,
+        treasury: Option<Pubkey>,
+        registration_fee: Option<u64>,
+        protocol_fee_bps: Option<u16>,
+        protocol_fee_cap: Option<u64>,
+        max_proof_distance_m: Option<u32>,
+        escrow_dispute_window: Option<i64>,
+        dispute_ruling_window: Option<i64>,
+        dispute_default_refund_bps: Option<u16>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        if let Some(new_admin) = new_admin {
+            config.admin = new_admin;
+        }
+
+        if let Some(treasury) = treasury {
+            config.treasury = treasury;
+        }
+
+        if let Some(registration_fee) = registration_fee {
+            config.registration_fee = registration_fee;
+        }
+
+        if let Some(protocol_fee_bps) = protocol_fee_bps {
+            require!(
+                protocol_fee_bps <= MAX_BASIS_POINTS,
+                ErrorCode::InvalidFeeBps
+            );
+            config.protocol_fee_bps = protocol_fee_bps;
+        }
+
+        if let Some(protocol_fee_cap) = protocol_fee_cap {
+            config.protocol_fee_cap = protocol_fee_cap;
+        }
+
+        if let Some(max_proof_distance_m) = max_proof_distance_m {
+            config.max_proof_distance_m = max_proof_distance_m;
+        }
+
+        if let Some(escrow_dispute_window) = escrow_dispute_window {
+            require!(escrow_dispute_window >= 0, ErrorCode::InvalidDisputeWindow);
+            config.escrow_dispute_window = escrow_dispute_window;
+        }
+
+        if let Some(dispute_ruling_window) = dispute_ruling_window {
+            require!(dispute_ruling_window >= 0, ErrorCode::InvalidDisputeWindow);
+            config.dispute_ruling_window = dispute_ruling_window;
+        }
+
+        if let Some(dispute_default_refund_bps) = dispute_default_refund_bps {
+            require!(
+                dispute_default_refund_bps <= MAX_BASIS_POINTS,
+                ErrorCode::InvalidFeeBps
+            );
+            config.dispute_default_refund_bps = dispute_default_refund_bps;
+        }
+
+        msg!(
+            "Config updated: admin={}, treasury={}, registration_fee={} lamports, protocol_fee={} bps (cap {} lamports), max_proof_distance={} m, escrow_dispute_window={} s, dispute_ruling_window={} s, dispute_default_refund={} bps",
+            config.admin,
+            config.treasury,
+            config.registration_fee,
+            config.protocol_fee_bps,
+            config.protocol_fee_cap,
+            config.max_proof_distance_m,
+            config.escrow_dispute_window,
+            config.dispute_ruling_window,
+            config.dispute_default_refund_bps
+        );
+
+        emit!(ConfigUpdatedEvent {
+            admin: config.admin,
+            treasury: config.treasury,
+            registration_fee: config.registration_fee,
+            protocol_fee_bps: config.protocol_fee_bps,
+            protocol_fee_cap: config.protocol_fee_cap,
+            max_proof_distance_m: config.max_proof_distance_m,
+            escrow_dispute_window: config.escrow_dispute_window,
+            dispute_ruling_window: config.dispute_ruling_window,
+            dispute_default_refund_bps: config.dispute_default_refund_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Register a wallet as a location verifier (admin only)
+    ///
+    /// # Arguments
+    /// * `verifier` - Wallet of the verification server allowed to create location proofs
+    ///
+    /// # Security
+    /// - Only the config admin can add verifiers
+    /// - PDA seeds: [b"verifier", verifier]
+    pub fn add_verifier(ctx: Context<AddVerifier>, verifier: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+
+        let verifier_account = &mut ctx.accounts.verifier_account;
+        verifier_account.authority = verifier;
+        verifier_account.is_active = true;
+        verifier_account.added_at = clock.unix_timestamp;
+        verifier_account.bump = ctx.bumps.verifier_account;
+
+        msg!("Verifier added: {}", verifier);
+
+        emit!(VerifierAddedEvent {
+            verifier,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Remove a verifier from the registry (admin only)
+    ///
+    /// Closes the verifier PDA and returns its rent to the admin.
+    pub fn remove_verifier(ctx: Context<RemoveVerifier>) -> Result<()> {
+        let verifier = ctx.accounts.verifier_account.authority;
+
+        msg!("Verifier removed: {}", verifier);
+
+        emit!(VerifierRemovedEvent {
+            verifier,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Suspend or reinstate a verifier (admin only)
+    ///
+    /// # Arguments
+    /// * `suspended` - `true` to suspend the verifier, `false` to reinstate it
+    ///
+    /// A suspended verifier stays in the registry but cannot create location proofs.
+    pub fn suspend_verifier(ctx: Context<SuspendVerifier>, suspended: bool) -> Result<()> {
+        let verifier_account = &mut ctx.accounts.verifier_account;
+        verifier_account.is_active = !suspended;
+
+        msg!(
+            "Verifier {} {}",
+            verifier_account.authority,
+            if suspended { "suspended" } else { "reinstated" }
+        );
+
+        emit!(VerifierSuspendedEvent {
+            verifier: verifier_account.authority,
+            suspended,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     /// Register a new merchant with registration fee
     ///
     /// # Arguments
@@ -25,12 +804,21 @@ pub mod nearme_contract {
     /// * `lng` - Longitude multiplied by 1,000,000 (6 decimal precision)
     ///
     /// # Fee
-    /// - Merchant must pay REGISTRATION_FEE (0.01 SOL) to register
-    /// - Fee goes to the treasury account
+    /// - Merchant must pay the configured registration fee to register
+    /// - Fee goes to the treasury account stored in the config
+    /// - Pass `fee_mint` and its token accounts to pay instead in a globally accepted
+    ///   mint at the entry's `registration_fee` price
+    /// - Pass a fee waiver issued for the merchant wallet to register for free; the
+    ///   waiver is consumed (closed, rent to the merchant)
     ///
     /// # Security
     /// - Only the merchant (signer) can register themselves
-    /// - Each merchant can only register once (PDA ensures uniqueness)
+    /// - `merchant_id` must be non-empty and is reserved for this merchant account until
+    ///   `close_merchant`, so no other wallet can register (or get a proof for) the same ID
+    /// - A wallet holds one live merchant account at a time; re-registering after
+    ///   `close_merchant` creates a fresh PDA (see `generation_seed`)
+    /// - PDA seeds: [b"merchant", registration_key, generation_seed]; the seeds stay fixed
+    ///   across ownership transfers
     pub fn register_merchant(
         ctx: Context<RegisterMerchant>,
         merchant_id: String,
@@ -39,54 +827,123 @@ pub mod nearme_contract {
         lng: i64,
     ) -> Result<()> {
         // Validate inputs
+        require!(!merchant_id.is_empty(), ErrorCode::EmptyMerchantId);
         require!(
             merchant_id.len() <= MAX_MERCHANT_ID_LEN,
             ErrorCode::MerchantIdTooLong
         );
 
-        require!(
-            business_name.len() <= MAX_BUSINESS_NAME_LEN && !business_name.is_empty(),
-            ErrorCode::InvalidBusinessName
-        );
-
-        require!(
-            lat >= -90_000_000 && lat <= 90_000_000,
-            ErrorCode::InvalidLatitude
-        );
-
-        require!(
-            lng >= -180_000_000 && lng <= 180_000_000,
-            ErrorCode::InvalidLongitude
-        );
+        validate_business_name(&business_name)?;
+        validate_coordinates(lat, lng)?;
 
         let clock = Clock::get()?;
+        let fee_waived = ctx.accounts.fee_waiver.is_some();
+        let (fee_mint, registration_fee) = if fee_waived {
+            (NATIVE_SOL_MINT, 0)
+        } else if let Some(fee_mint) = ctx.accounts.fee_mint.as_ref() {
+            let (
+                Some(fee_accepted_mint),
+                Some(merchant_fee_token_account),
+                Some(treasury_fee_token_account),
+                Some(token_program),
+            ) = (
+                ctx.accounts.fee_accepted_mint.as_ref(),
+                ctx.accounts.merchant_fee_token_account.as_ref(),
+                ctx.accounts.treasury_fee_token_account.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            )
+            else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+            require_keys_eq!(
+                fee_accepted_mint.mint,
+                fee_mint.key(),
+                ErrorCode::MintNotAccepted
+            );
+            let registration_fee = fee_accepted_mint.registration_fee;
+            require!(registration_fee > 0, ErrorCode::MintNotAccepted);
 
-        // Transfer registration fee from merchant to treasury
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.merchant.to_account_info(),
-                to: ctx.accounts.treasury.to_account_info(),
-            },
-        );
-        transfer(cpi_context, REGISTRATION_FEE)?;
+            // Transfer the token registration fee from merchant to treasury
+            let cpi_context = CpiContext::new(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: merchant_fee_token_account.to_account_info(),
+                    mint: fee_mint.to_account_info(),
+                    to: treasury_fee_token_account.to_account_info(),
+                    authority: ctx.accounts.merchant.to_account_info(),
+                },
+            );
+            transfer_checked(cpi_context, registration_fee, fee_mint.decimals)?;
+            (fee_mint.key(), registration_fee)
+        } else {
+            let registration_fee = ctx.accounts.config.registration_fee;
+
+            // Transfer registration fee from merchant to treasury
+            if registration_fee > 0 {
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.merchant.to_account_info(),
+                        to: ctx.accounts.treasury.to_account_info(),
+                    },
+                );
+                transfer(cpi_context, registration_fee)?;
+            }
+            (NATIVE_SOL_MINT, registration_fee)
+        };
 
         // Initialize merchant account
         let merchant_account = &mut ctx.accounts.merchant_account;
         merchant_account.merchant = ctx.accounts.merchant.key();
+        merchant_account.registration_key = ctx.accounts.merchant.key();
+        merchant_account.pending_merchant = None;
+        merchant_account.merchant_id = merchant_id;
+        merchant_account.location_proof = None;
+        merchant_account.location_verified = false;
         merchant_account.business_name = business_name.clone();
         merchant_account.lat = lat;
         merchant_account.lng = lng;
         merchant_account.registered_at = clock.unix_timestamp;
         merchant_account.is_active = true;
+        merchant_account.is_suspended = false;
+        merchant_account.suspension_reason = 0;
+        merchant_account.suspension_expires_at = None;
+        merchant_account.open_obligations = 0;
+        merchant_account.fee_bps_override = None;
+        merchant_account.staff_count = 0;
+        merchant_account.tip_pool_size = 0;
+        merchant_account.delegate_count = 0;
+        merchant_account.rating_sum = 0;
+        merchant_account.rating_count = 0;
+        merchant_account.generation = ctx.accounts.registrations.count;
+        merchant_account.coupon_count = 0;
+        merchant_account.payment_request_count = 0;
+        merchant_account.accepted_mint_count = 0;
+        merchant_account.has_loyalty_program = false;
+        merchant_account.has_stamp_card = false;
         merchant_account.bump = ctx.bumps.merchant_account;
+        ctx.accounts.registrations.bump = ctx.bumps.registrations;
+
+        let merchant_id_reservation = &mut ctx.accounts.merchant_id_reservation;
+        merchant_id_reservation.merchant_account = merchant_account.key();
+        merchant_id_reservation.bump = ctx.bumps.merchant_id_reservation;
+
+        let merchant_stats = &mut ctx.accounts.merchant_stats;
+        merchant_stats.merchant_account = merchant_account.key();
+        merchant_stats.payment_count = 0;
+        merchant_stats.unique_payers = 0;
+        merchant_stats.last_payment_at = 0;
+        merchant_stats.refund_count = 0;
+        merchant_stats.bump = ctx.bumps.merchant_stats;
 
         msg!(
-            "Merchant registered: {} at ({}, {}), fee paid: {} lamports",
+            "Merchant registered: {} at ({}, {}), fee paid: {} of mint {}{}",
             business_name,
             lat,
             lng,
-            REGISTRATION_FEE
+            registration_fee,
+            fee_mint,
+            if fee_waived { " (waived)" } else { "" }
         );
 
         // Emit event
@@ -96,208 +953,8103 @@ pub mod nearme_contract {
             lat,
             lng,
             timestamp: clock.unix_timestamp,
-            fee_paid: REGISTRATION_FEE,
+            fee_paid: registration_fee,
+            fee_mint,
+            fee_waived,
         });
 
         Ok(())
     }
 
-    /// Create an immutable on-chain proof of a merchant's verified GPS location
+    /// Update a merchant's profile
     ///
     /// # Arguments
-    /// * `lat` - Latitude multiplied by 1,000,000 (6 decimal precision)
-    /// * `lng` - Longitude multiplied by 1,000,000 (6 decimal precision)
-    /// * `merchant_id` - Firebase merchant document ID (used as PDA seed)
+    /// * `business_name` - New business name, or `None` to keep the current one
+    /// * `lat` - New latitude * 1,000,000, or `None` to keep the current one
+    /// * `lng` - New longitude * 1,000,000, or `None` to keep the current one
     ///
     /// # Security
-    /// - Only the server keypair (payer) can call this
-    /// - PDA seeds: [b"proof", merchant_id_bytes]
-    /// - Each merchant can only have ONE proof (PDA ensures uniqueness)
-    /// - Location coordinates are validated for reasonable bounds
-    pub fn create_location_proof(
-        ctx: Context<CreateLocationProof>,
-        lat: i64,
-        lng: i64,
+    /// - Only the merchant or a delegate with `PERM_UPDATE_PROFILE` (signer) can update the profile
+    /// - New values go through the same validation as registration
+    /// - Changing the location clears `location_verified` until a new proof is created
+    /// - Accounts written under an older layout must be upgraded with `migrate_merchant_account` first
+    pub fn update_merchant_profile(
+        ctx: Context<UpdateMerchantProfile>,
+        business_name: Option<String>,
+        lat: Option<i64>,
+        lng: Option<i64>,
+    ) -> Result<()> {
+        require!(
+            business_name.is_some() || lat.is_some() || lng.is_some(),
+            ErrorCode::NothingToUpdate
+        );
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_UPDATE_PROFILE,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let new_lat = lat.unwrap_or(merchant_account.lat);
+        let new_lng = lng.unwrap_or(merchant_account.lng);
+        validate_coordinates(new_lat, new_lng)?;
+
+        let mut event = MerchantUpdatedEvent {
+            merchant: merchant_account.merchant,
+            old_business_name: None,
+            new_business_name: None,
+            old_lat: None,
+            new_lat: None,
+            old_lng: None,
+            new_lng: None,
+            timestamp: Clock::get()?.unix_timestamp,
+        };
+
+        // Only fields that actually change are recorded in the event diff
+        if let Some(business_name) = business_name {
+            validate_business_name(&business_name)?;
+            if business_name != merchant_account.business_name {
+                event.old_business_name = Some(merchant_account.business_name.clone());
+                event.new_business_name = Some(business_name.clone());
+                merchant_account.business_name = business_name;
+            }
+        }
+
+        if new_lat != merchant_account.lat {
+            event.old_lat = Some(merchant_account.lat);
+            event.new_lat = Some(new_lat);
+            merchant_account.lat = new_lat;
+        }
+
+        if new_lng != merchant_account.lng {
+            event.old_lng = Some(merchant_account.lng);
+            event.new_lng = Some(new_lng);
+            merchant_account.lng = new_lng;
+        }
+
+        // A relocated merchant must be verified again at the new address
+        if event.new_lat.is_some() || event.new_lng.is_some() {
+            merchant_account.location_verified = false;
+        }
+
+        msg!(
+            "Merchant profile updated: {} at ({}, {})",
+            merchant_account.business_name,
+            merchant_account.lat,
+            merchant_account.lng
+        );
+
+        emit!(event);
+
+        Ok(())
+    }
+
+    /// Upgrade a merchant account written under an older layout to the current one
+    ///
+    /// `MerchantAccount` fields are only ever appended, so an older account decodes as a
+    /// prefix of the current layout; the missing fields take their zero defaults.
+    /// Accounts from before merchant IDs and payment statistics existed also get their
+    /// `merchant_id` reservation and stats PDA, so they can be paid and verified.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Firebase merchant document ID; must match the stored ID if the
+    ///   account already has one
+    ///
+    /// # Security
+    /// - Only the merchant (signer) can migrate their own account and pays the extra rent
+    /// - The account must carry the `MerchantAccount` discriminator and sit at its PDA
+    /// - The merchant ID must not be reserved by another merchant
+    pub fn migrate_merchant_account(
+        ctx: Context<MigrateMerchantAccount>,
         merchant_id: String,
     ) -> Result<()> {
-        // Validate merchant_id length
+        require!(!merchant_id.is_empty(), ErrorCode::EmptyMerchantId);
         require!(
             merchant_id.len() <= MAX_MERCHANT_ID_LEN,
             ErrorCode::MerchantIdTooLong
         );
 
-        // Validate latitude (-90 to +90 degrees * 1,000,000)
+        let info = ctx.accounts.merchant_account.to_account_info();
+        let old_len = info.data_len();
         require!(
-            lat >= -90_000_000 && lat <= 90_000_000,
-            ErrorCode::InvalidLatitude
+            old_len < MerchantAccount::LEN,
+            ErrorCode::MerchantAccountUpToDate
         );
 
-        // Validate longitude (-180 to +180 degrees * 1,000,000)
-        require!(
-            lng >= -180_000_000 && lng <= 180_000_000,
-            ErrorCode::InvalidLongitude
+        let mut data = info.try_borrow_data()?.to_vec();
+        data.resize(MerchantAccount::LEN, 0);
+        let mut merchant_account = MerchantAccount::try_deserialize(&mut data.as_slice())?;
+        require_keys_eq!(
+            merchant_account.merchant,
+            ctx.accounts.merchant.key(),
+            ErrorCode::Unauthorized
         );
 
-        let proof = &mut ctx.accounts.proof;
-        let clock = Clock::get()?;
+        // Accounts from before ownership transfers were seeded by the merchant wallet
+        if merchant_account.registration_key == Pubkey::default() {
+            merchant_account.registration_key = merchant_account.merchant;
+        }
+        let expected = Pubkey::create_program_address(
+            &[
+                b"merchant",
+                merchant_account.registration_key.as_ref(),
+                generation_seed(merchant_account.generation).as_ref(),
+                &[merchant_account.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::Unauthorized)?;
+        require_keys_eq!(expected, info.key(), ErrorCode::Unauthorized);
 
-        proof.lat = lat;
-        proof.lng = lng;
-        proof.verified_at = clock.unix_timestamp;
-        proof.bump = ctx.bumps.proof;
+        // Backfill the merchant ID and its reservation
+        if merchant_account.merchant_id.is_empty() {
+            merchant_account.merchant_id = merchant_id;
+        } else {
+            require!(
+                merchant_account.merchant_id == merchant_id,
+                ErrorCode::MerchantIdMismatch
+            );
+        }
+        let merchant_id_reservation = &mut ctx.accounts.merchant_id_reservation;
+        if merchant_id_reservation.merchant_account == Pubkey::default() {
+            merchant_id_reservation.merchant_account = info.key();
+            merchant_id_reservation.bump = ctx.bumps.merchant_id_reservation;
+        } else {
+            require_keys_eq!(
+                merchant_id_reservation.merchant_account,
+                info.key(),
+                ErrorCode::MerchantIdMismatch
+            );
+        }
+
+        // Backfill the payment statistics PDA
+        let merchant_stats = &mut ctx.accounts.merchant_stats;
+        if merchant_stats.merchant_account == Pubkey::default() {
+            merchant_stats.merchant_account = info.key();
+            merchant_stats.bump = ctx.bumps.merchant_stats;
+        }
+
+        // Top up rent for the larger account, then grow and rewrite it
+        let rent_due = Rent::get()?
+            .minimum_balance(MerchantAccount::LEN)
+            .saturating_sub(info.lamports());
+        if rent_due > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.merchant.to_account_info(),
+                    to: info.clone(),
+                },
+            );
+            transfer(cpi_context, rent_due)?;
+        }
+        info.resize(MerchantAccount::LEN)?;
+        merchant_account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
 
         msg!(
-            "Location proof created: lat={}, lng={}, timestamp={}",
-            lat,
-            lng,
-            clock.unix_timestamp
+            "Merchant account {} migrated: {} -> {} bytes",
+            info.key(),
+            old_len,
+            MerchantAccount::LEN
         );
 
-        // Emit event for indexing/monitoring
-        emit!(LocationVerifiedEvent {
-            lat,
-            lng,
-            timestamp: clock.unix_timestamp,
-        });
-
         Ok(())
     }
 
-    /// Close a location proof account (admin only)
-    ///
-    /// This is a safety mechanism to handle edge cases like:
-    /// - Fraudulent merchant detection
-    /// - Account cleanup if needed
+    /// Mark the merchant as closed for business
     ///
-    /// The authority must be the server keypair that created the proof
-    pub fn close_location_proof(ctx: Context<CloseLocationProof>) -> Result<()> {
-        msg!("Location proof closed for merchant");
-        Ok(())
-    }
-}
+    /// # Security
+    /// - Only the merchant owner or a delegate with `PERM_UPDATE_PROFILE` (signer) can
+    ///   deactivate the account
+    pub fn deactivate_merchant(ctx: Context<SetMerchantStatus>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_UPDATE_PROFILE,
+        )?;
 
-/// Account struct for storing merchant registration data
-#[account]
-pub struct MerchantAccount {
-    /// The merchant's wallet public key
-    pub merchant: Pubkey, // 32 bytes
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.is_active = false;
 
-    /// Business name (variable length, max 64 bytes)
-    pub business_name: String, // 4 + 64 = 68 bytes
+        msg!("Merchant deactivated: {}", merchant_account.merchant);
 
-    /// Latitude * 1,000,000 (6 decimal places)
-    pub lat: i64, // 8 bytes
+        emit!(MerchantDeactivatedEvent {
+            merchant: merchant_account.merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Mark the merchant as open for business again
+    ///
+    /// # Security
+    /// - Only the merchant owner or a delegate with `PERM_UPDATE_PROFILE` (signer) can
+    ///   reactivate the account
+    /// - Fails while an admin suspension is in force; an expired suspension is cleared
+    pub fn reactivate_merchant(ctx: Context<SetMerchantStatus>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_UPDATE_PROFILE,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let clock = Clock::get()?;
+
+        require!(
+            !merchant_account.is_suspended_at(clock.unix_timestamp),
+            ErrorCode::MerchantSuspended
+        );
+
+        merchant_account.is_active = true;
+        merchant_account.is_suspended = false;
+        merchant_account.suspension_reason = 0;
+        merchant_account.suspension_expires_at = None;
+
+        msg!("Merchant reactivated: {}", merchant_account.merchant);
+
+        emit!(MerchantReactivatedEvent {
+            merchant: merchant_account.merchant,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Suspend a merchant (admin only)
+    ///
+    /// # Arguments
+    /// * `reason_code` - Admin-defined suspension reason (must be non-zero)
+    /// * `expires_at` - Unix timestamp when the suspension ends, or `None` for indefinite
+    ///
+    /// The merchant is deactivated and cannot reactivate until the suspension
+    /// expires or is lifted by the admin.
+    pub fn suspend_merchant(
+        ctx: Context<SuspendMerchant>,
+        reason_code: u8,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+
+        require!(reason_code != 0, ErrorCode::InvalidReasonCode);
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > clock.unix_timestamp,
+                ErrorCode::InvalidSuspensionExpiry
+            );
+        }
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.is_active = false;
+        merchant_account.is_suspended = true;
+        merchant_account.suspension_reason = reason_code;
+        merchant_account.suspension_expires_at = expires_at;
+
+        msg!(
+            "Merchant suspended: {}, reason={}, expires_at={:?}",
+            merchant_account.merchant,
+            reason_code,
+            expires_at
+        );
+
+        emit!(MerchantSuspendedEvent {
+            merchant: merchant_account.merchant,
+            reason_code,
+            expires_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Lift a merchant suspension early (admin only)
+    ///
+    /// The merchant stays inactive until they call `reactivate_merchant`.
+    pub fn lift_merchant_suspension(ctx: Context<SuspendMerchant>) -> Result<()> {
+        let merchant_account = &mut ctx.accounts.merchant_account;
+
+        require!(
+            merchant_account.is_suspended,
+            ErrorCode::MerchantNotSuspended
+        );
+
+        merchant_account.is_suspended = false;
+        merchant_account.suspension_reason = 0;
+        merchant_account.suspension_expires_at = None;
+
+        msg!("Merchant suspension lifted: {}", merchant_account.merchant);
+
+        emit!(MerchantSuspensionLiftedEvent {
+            merchant: merchant_account.merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Close a merchant account and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the merchant (signer) can close their own account
+    /// - Refuses to close while the merchant has open obligations, staff or delegates
+    /// - Refuses to close while coupons, payment requests, merchant accepted-mint entries,
+    ///   the loyalty program or the stamp card remain; close those first
+    /// - Refuses to close while an admin suspension is in force
+    /// - The linked location proof, if any, must be passed and is closed too
+    /// - The `merchant_id` reservation must be passed and is closed too, releasing the ID
+    /// - Lamports left in the refund vault go back to the merchant; withdraw vault tokens
+    ///   with `withdraw_refund_vault` first
+    /// - Rent is returned to the merchant, who may register again afterwards; the new
+    ///   registration lives at a new PDA, so accounts keyed by this one (mint stats,
+    ///   customer records, receipts, reviews, loyalty, stamp cards, coupons, payment
+    ///   requests) are never inherited
+    pub fn close_merchant(ctx: Context<CloseMerchant>) -> Result<()> {
+        let merchant_account = &ctx.accounts.merchant_account;
+        let clock = Clock::get()?;
+
+        require!(
+            merchant_account.open_obligations == 0,
+            ErrorCode::MerchantHasOpenObligations
+        );
+        require!(
+            merchant_account.staff_count == 0,
+            ErrorCode::MerchantHasStaff
+        );
+        require!(
+            merchant_account.delegate_count == 0,
+            ErrorCode::MerchantHasDelegates
+        );
+        require!(
+            merchant_account.coupon_count == 0,
+            ErrorCode::MerchantHasCoupons
+        );
+        require!(
+            merchant_account.payment_request_count == 0,
+            ErrorCode::MerchantHasPaymentRequests
+        );
+        require!(
+            merchant_account.accepted_mint_count == 0,
+            ErrorCode::MerchantHasAcceptedMints
+        );
+        require!(
+            !merchant_account.has_loyalty_program,
+            ErrorCode::MerchantHasLoyaltyProgram
+        );
+        require!(
+            !merchant_account.has_stamp_card,
+            ErrorCode::MerchantHasStampCard
+        );
+        require!(
+            !merchant_account.is_suspended_at(clock.unix_timestamp),
+            ErrorCode::MerchantSuspended
+        );
+
+        // Close the linked location proof along with the merchant
+        if let Some(linked_proof) = merchant_account.location_proof {
+            let proof = ctx
+                .accounts
+                .location_proof
+                .as_ref()
+                .ok_or(ErrorCode::LocationProofMismatch)?;
+            require_keys_eq!(proof.key(), linked_proof, ErrorCode::LocationProofMismatch);
+            proof.close(ctx.accounts.merchant.to_account_info())?;
+        }
+
+        // Release the merchant ID reservation
+        if !merchant_account.merchant_id.is_empty() {
+            let reservation = ctx
+                .accounts
+                .merchant_id_reservation
+                .as_ref()
+                .ok_or(ErrorCode::MerchantIdMismatch)?;
+            reservation.close(ctx.accounts.merchant.to_account_info())?;
+        }
+
+        // Sweep the refund vault
+        let refund_vault = &ctx.accounts.refund_vault;
+        let vault_balance = refund_vault.lamports();
+        if vault_balance > 0 {
+            let merchant_account_key = merchant_account.key();
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"refund_vault",
+                merchant_account_key.as_ref(),
+                &[ctx.bumps.refund_vault],
+            ]];
+            let cpi_context = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: refund_vault.to_account_info(),
+                    to: ctx.accounts.merchant.to_account_info(),
+                },
+                signer_seeds,
+            );
+            transfer(cpi_context, vault_balance)?;
+        }
+
+        // The registration wallet's next merchant account gets the next generation
+        let registrations = &mut ctx.accounts.registrations;
+        registrations.count = merchant_account
+            .generation
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        registrations.bump = ctx.bumps.registrations;
+
+        msg!("Merchant closed: {}", merchant_account.merchant);
+
+        emit!(MerchantClosedEvent {
+            merchant: merchant_account.merchant,
+            merchant_id: merchant_account.merchant_id.clone(),
+            business_name: merchant_account.business_name.clone(),
+            location_proof: merchant_account.location_proof,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Propose transferring the merchant registration to a new wallet (step 1 of 2)
+    ///
+    /// # Arguments
+    /// * `new_merchant` - Wallet that must accept the transfer
+    ///
+    /// # Security
+    /// - Only the current merchant (signer) can propose a transfer
+    /// - Replaces any previously pending proposal
+    /// - All delegates must be removed first so none outlive the current owner
+    /// - The PDA address does not change, so proofs, stats and reviews stay linked
+    pub fn propose_merchant_transfer(
+        ctx: Context<ProposeMerchantTransfer>,
+        new_merchant: Pubkey,
+    ) -> Result<()> {
+        let merchant_account = &mut ctx.accounts.merchant_account;
+
+        require!(
+            new_merchant != merchant_account.merchant,
+            ErrorCode::InvalidTransferTarget
+        );
+        require!(
+            merchant_account.delegate_count == 0,
+            ErrorCode::MerchantHasDelegates
+        );
+
+        merchant_account.pending_merchant = Some(new_merchant);
+
+        msg!(
+            "Merchant transfer proposed: {} -> {}",
+            merchant_account.merchant,
+            new_merchant
+        );
+
+        emit!(MerchantTransferProposedEvent {
+            merchant_account: merchant_account.key(),
+            current_merchant: merchant_account.merchant,
+            proposed_merchant: new_merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Accept a pending merchant transfer (step 2 of 2)
+    ///
+    /// # Security
+    /// - Only the proposed wallet (signer) can accept
+    /// - Fails if the current owner added delegates after proposing
+    pub fn accept_merchant_transfer(ctx: Context<AcceptMerchantTransfer>) -> Result<()> {
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        require!(
+            merchant_account.delegate_count == 0,
+            ErrorCode::MerchantHasDelegates
+        );
+        let previous_merchant = merchant_account.merchant;
+
+        merchant_account.merchant = ctx.accounts.new_merchant.key();
+        merchant_account.pending_merchant = None;
+
+        msg!(
+            "Merchant transfer accepted: {} -> {}",
+            previous_merchant,
+            merchant_account.merchant
+        );
+
+        emit!(MerchantTransferAcceptedEvent {
+            merchant_account: merchant_account.key(),
+            previous_merchant,
+            new_merchant: merchant_account.merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a pending merchant transfer
+    ///
+    /// # Security
+    /// - The current merchant can withdraw the proposal
+    /// - The proposed wallet can decline it
+    pub fn cancel_merchant_transfer(ctx: Context<CancelMerchantTransfer>) -> Result<()> {
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let authority = ctx.accounts.authority.key();
+
+        let proposed_merchant = merchant_account
+            .pending_merchant
+            .ok_or(ErrorCode::NoPendingTransfer)?;
+        require!(
+            authority == merchant_account.merchant || authority == proposed_merchant,
+            ErrorCode::Unauthorized
+        );
+
+        merchant_account.pending_merchant = None;
+
+        msg!(
+            "Merchant transfer to {} cancelled by {}",
+            proposed_merchant,
+            authority
+        );
+
+        emit!(MerchantTransferCancelledEvent {
+            merchant_account: merchant_account.key(),
+            proposed_merchant,
+            cancelled_by: authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create an immutable on-chain proof of a merchant's verified GPS location
+    ///
+    /// # Arguments
+    /// * `lat` - Latitude multiplied by 1,000,000 (6 decimal precision)
+    /// * `lng` - Longitude multiplied by 1,000,000 (6 decimal precision)
+    /// * `merchant_id` - Firebase merchant document ID (used as PDA seed)
+    ///
+    /// # Security
+    /// - Only an active registered verifier (payer) can call this
+    /// - A registered merchant with the same `merchant_id` must exist and hold its
+    ///   reservation
+    /// - The proof must be within `max_proof_distance_m` of the merchant's registered location
+    /// - PDA seeds: [b"proof", merchant_id_bytes]
+    /// - Each merchant can only have ONE proof (PDA ensures uniqueness)
+    /// - Location coordinates are validated for reasonable bounds
+    pub fn create_location_proof(
+        ctx: Context<CreateLocationProof>,
+        lat: i64,
+        lng: i64,
+        merchant_id: String,
+    ) -> Result<()> {
+        // Validate merchant_id length
+        require!(
+            merchant_id.len() <= MAX_MERCHANT_ID_LEN,
+            ErrorCode::MerchantIdTooLong
+        );
+
+        // Validate coordinates are within reasonable bounds
+        validate_coordinates(lat, lng)?;
+
+        let proof = &mut ctx.accounts.proof;
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let clock = Clock::get()?;
+
+        // The verified location must match the registered one
+        let distance_m = approx_distance_m(merchant_account.lat, merchant_account.lng, lat, lng);
+        require!(
+            distance_m <= ctx.accounts.config.max_proof_distance_m as u64,
+            ErrorCode::LocationTooFar
+        );
+
+        proof.lat = lat;
+        proof.lng = lng;
+        proof.verified_at = clock.unix_timestamp;
+        proof.verifier = ctx.accounts.payer.key();
+        proof.merchant_account = merchant_account.key();
+        proof.bump = ctx.bumps.proof;
+
+        // Link the proof back from the merchant account
+        merchant_account.location_proof = Some(proof.key());
+        merchant_account.location_verified = true;
+
+        msg!(
+            "Location proof created for merchant {}: lat={}, lng={}, timestamp={}",
+            merchant_id,
+            lat,
+            lng,
+            clock.unix_timestamp
+        );
+
+        // Emit event for indexing/monitoring
+        emit!(LocationVerifiedEvent {
+            merchant_account: merchant_account.key(),
+            verifier: proof.verifier,
+            lat,
+            lng,
+            distance_m,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke and close a merchant's location proof
+    ///
+    /// This is a safety mechanism to handle edge cases like:
+    /// - Fraudulent merchant detection
+    /// - Account cleanup if needed
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Firebase merchant document ID (used as PDA seed)
+    /// * `reason_code` - Application-defined revocation reason, recorded in the event
+    ///
+    /// # Security
+    /// - The authority must be the verifier that created the proof or the config admin
+    /// - Rent is returned to the authority
+    pub fn close_location_proof(
+        ctx: Context<CloseLocationProof>,
+        merchant_id: String,
+        reason_code: u8,
+    ) -> Result<()> {
+        let proof = &ctx.accounts.proof;
+        let clock = Clock::get()?;
+
+        // Unlink the proof from the merchant account
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.location_proof = None;
+        merchant_account.location_verified = false;
+
+        msg!(
+            "Location proof revoked for merchant {}: reason={}, revoked_by={}",
+            merchant_id,
+            reason_code,
+            ctx.accounts.authority.key()
+        );
+
+        emit!(LocationProofRevokedEvent {
+            merchant_id,
+            proof: proof.key(),
+            verifier: proof.verifier,
+            revoked_by: ctx.accounts.authority.key(),
+            reason_code,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay a merchant in SOL and record an on-chain receipt
+    ///
+    /// # Arguments
+    /// * `amount` - Amount in lamports
+    /// * `reference` - Client-generated payment reference (used as PDA seed)
+    /// * `tip` - Optional tip in lamports on top of `amount`, paid to staff without protocol fee
+    /// * `split_tip` - `false` to tip one staff member, `true` to split across the tip pool
+    ///
+    /// Tip recipients are passed as remaining accounts in (Staff PDA, staff wallet) pairs.
+    ///
+    /// # Security
+    /// - The merchant must be active
+    /// - Lamports go to the merchant's current wallet, minus the protocol fee
+    ///   (merchant override or config bps, capped by config) which goes to the treasury
+    /// - Tips only go to wallets registered as the merchant's staff
+    /// - With a coupon passed, its discount is taken off `amount` and the use is counted
+    ///   (the payer must have clipped it; see `clip_coupon`)
+    /// - With the loyalty accounts passed, a pending loyalty discount is taken off `amount`
+    ///   and points are credited on the rest (payer pays rent for a new balance)
+    /// - PDA seeds: [b"receipt", merchant_account, reference]; a reference can only be paid once
+    /// - Updates the merchant's stats, per-mint stats and customer record (payer pays any new rent)
+    pub fn pay_merchant_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PayMerchantSol<'info>>,
+        amount: u64,
+        reference: [u8; 32],
+        tip: u64,
+        split_tip: bool,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+
+        // Take any coupon, then any redeemed loyalty discount, and credit points on what is paid
+        let coupon_discount = apply_coupon(
+            ctx.accounts.coupon.as_deref_mut(),
+            ctx.accounts.coupon_usage.as_deref_mut(),
+            NATIVE_SOL_MINT,
+            amount,
+        )?;
+        let discount = coupon_discount
+            + apply_loyalty(
+                ctx.accounts.loyalty_program.as_deref_mut(),
+                ctx.accounts.loyalty_balance.as_deref_mut(),
+                ctx.bumps.loyalty_balance,
+                ctx.accounts.merchant_account.key(),
+                ctx.accounts.payer.key(),
+                NATIVE_SOL_MINT,
+                amount - coupon_discount,
+                true,
+            )?;
+        let amount = amount - discount;
+
+        let config = &ctx.accounts.config;
+        let protocol_fee = compute_protocol_fee(
+            amount,
+            ctx.accounts.merchant_account.fee_bps(config),
+            config.protocol_fee_cap,
+        );
+        let merchant_amount = amount - protocol_fee;
+
+        // Transfer lamports from payer to merchant
+        if merchant_amount > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.merchant.to_account_info(),
+                },
+            );
+            transfer(cpi_context, merchant_amount)?;
+        }
+
+        // Transfer the protocol fee from payer to treasury
+        if protocol_fee > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
+                },
+            );
+            transfer(cpi_context, protocol_fee)?;
+        }
+
+        // Route any tip to staff
+        let tips = pay_tips(
+            &ctx.accounts.merchant_account,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ctx.remaining_accounts,
+            tip,
+            split_tip,
+        )?;
+
+        // Update on-chain payment statistics
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let first_payment = ctx.accounts.customer_record.record(
+            merchant_account_key,
+            ctx.accounts.payer.key(),
+            ctx.bumps.customer_record,
+            clock.unix_timestamp,
+        )?;
+        ctx.accounts.mint_stats.record(
+            merchant_account_key,
+            NATIVE_SOL_MINT,
+            ctx.bumps.mint_stats,
+            amount,
+        )?;
+        ctx.accounts
+            .merchant_stats
+            .record(first_payment, clock.unix_timestamp)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.payer = ctx.accounts.payer.key();
+        receipt.merchant_account = ctx.accounts.merchant_account.key();
+        receipt.merchant = ctx.accounts.merchant.key();
+        receipt.mint = NATIVE_SOL_MINT;
+        receipt.amount = amount;
+        receipt.discount = discount;
+        receipt.protocol_fee = protocol_fee;
+        receipt.merchant_amount = merchant_amount;
+        receipt.refunded_amount = 0;
+        receipt.tip_amount = tip;
+        receipt.tips = tips;
+        receipt.escrowed = false;
+        receipt.stamped = false;
+        receipt.reference = reference;
+        receipt.paid_at = clock.unix_timestamp;
+        receipt.bump = ctx.bumps.receipt;
+
+        msg!(
+            "SOL payment: {} lamports from {} to {} (protocol fee: {})",
+            amount,
+            receipt.payer,
+            receipt.merchant,
+            protocol_fee
+        );
+
+        emit!(PaymentMadeEvent {
+            receipt: receipt.key(),
+            payer: receipt.payer,
+            merchant_account: receipt.merchant_account,
+            merchant: receipt.merchant,
+            mint: NATIVE_SOL_MINT,
+            amount,
+            protocol_fee,
+            merchant_amount,
+            reference,
+            timestamp: clock.unix_timestamp,
+        });
+
+        if tip > 0 {
+            msg!(
+                "Tip of {} lamports split into {} leg(s)",
+                tip,
+                receipt.tips.len()
+            );
+
+            emit!(TipPaidEvent {
+                receipt: receipt.key(),
+                merchant_account: receipt.merchant_account,
+                tip,
+                legs: receipt.tips.clone(),
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Accept an SPL mint for payments to every merchant (admin only)
+    ///
+    /// # Arguments
+    /// * `protocol_fee_cap` - Max protocol fee per payment in the mint's base units (0 = no cap);
+    ///   also applies when a merchant entry accepted the payment
+    /// * `registration_fee` - Merchant registration price in the mint's base units
+    ///   (0 = the mint cannot be used to pay the registration fee)
+    ///
+    /// # Security
+    /// - Only the config admin can edit the global accepted-mint list
+    /// - PDA seeds: [b"accepted_mint", config, mint]
+    pub fn add_accepted_mint(
+        ctx: Context<AddAcceptedMint>,
+        protocol_fee_cap: u64,
+        registration_fee: u64,
+    ) -> Result<()> {
+        let accepted_mint = &mut ctx.accounts.accepted_mint;
+        accepted_mint.scope = ctx.accounts.config.key();
+        accepted_mint.mint = ctx.accounts.mint.key();
+        accepted_mint.protocol_fee_cap = protocol_fee_cap;
+        accepted_mint.registration_fee = registration_fee;
+        accepted_mint.added_at = Clock::get()?.unix_timestamp;
+        accepted_mint.bump = ctx.bumps.accepted_mint;
+
+        msg!("Mint accepted globally: {}", accepted_mint.mint);
+
+        emit!(AcceptedMintAddedEvent {
+            scope: accepted_mint.scope,
+            mint: accepted_mint.mint,
+            timestamp: accepted_mint.added_at,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an SPL mint from the global accepted-mint list (admin only)
+    pub fn remove_accepted_mint(ctx: Context<RemoveAcceptedMint>) -> Result<()> {
+        let accepted_mint = &ctx.accounts.accepted_mint;
+
+        msg!("Mint no longer accepted globally: {}", accepted_mint.mint);
+
+        emit!(AcceptedMintRemovedEvent {
+            scope: accepted_mint.scope,
+            mint: accepted_mint.mint,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Accept an SPL mint for payments to this merchant only
+    ///
+    /// # Security
+    /// - Only the merchant owner or a delegate with `PERM_MANAGE_MINTS` (signer) can edit
+    ///   the merchant's accepted-mint list
+    /// - Merchant entries cannot pay registration fees; payments in the mint are capped
+    ///   by the mint's global entry, if there is one
+    /// - PDA seeds: [b"accepted_mint", merchant_account, mint]
+    pub fn add_merchant_accepted_mint(ctx: Context<AddMerchantAcceptedMint>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_MINTS,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.accepted_mint_count = merchant_account
+            .accepted_mint_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let accepted_mint = &mut ctx.accounts.accepted_mint;
+        accepted_mint.scope = ctx.accounts.merchant_account.key();
+        accepted_mint.mint = ctx.accounts.mint.key();
+        accepted_mint.protocol_fee_cap = 0;
+        accepted_mint.registration_fee = 0;
+        accepted_mint.added_at = Clock::get()?.unix_timestamp;
+        accepted_mint.bump = ctx.bumps.accepted_mint;
+
+        msg!(
+            "Mint {} accepted by merchant {}",
+            accepted_mint.mint,
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(AcceptedMintAddedEvent {
+            scope: accepted_mint.scope,
+            mint: accepted_mint.mint,
+            timestamp: accepted_mint.added_at,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an SPL mint from this merchant's accepted-mint list
+    ///
+    /// # Security
+    /// - Only the merchant owner or a delegate with `PERM_MANAGE_MINTS` (signer) can edit
+    ///   the merchant's accepted-mint list
+    pub fn remove_merchant_accepted_mint(ctx: Context<RemoveMerchantAcceptedMint>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_MINTS,
+        )?;
+
+        // Entries added before the count existed are not in it
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.accepted_mint_count =
+            merchant_account.accepted_mint_count.saturating_sub(1);
+
+        let accepted_mint = &ctx.accounts.accepted_mint;
+
+        msg!(
+            "Mint {} no longer accepted by merchant {}",
+            accepted_mint.mint,
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(AcceptedMintRemovedEvent {
+            scope: accepted_mint.scope,
+            mint: accepted_mint.mint,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay a merchant in an SPL token (Token or Token-2022) and record an on-chain receipt
+    ///
+    /// # Arguments
+    /// * `amount` - Amount in the mint's base units
+    /// * `reference` - Client-generated payment reference (used as PDA seed)
+    ///
+    /// # Security
+    /// - The merchant must be active
+    /// - The protocol fee (merchant override or config bps, capped per mint) goes to the treasury ATA
+    /// - The mint must be on the global or the merchant's accepted-mint list
+    /// - The merchant's associated token account is created if missing (payer pays rent)
+    /// - Coupons, loyalty discounts and points work as in `pay_merchant_sol`
+    /// - PDA seeds: [b"receipt", merchant_account, reference]; a reference can only be paid once
+    /// - Updates the merchant's stats, per-mint stats and customer record (payer pays any new rent)
+    pub fn pay_merchant_token(
+        ctx: Context<PayMerchantToken>,
+        amount: u64,
+        reference: [u8; 32],
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+
+        // Take any coupon, then any redeemed loyalty discount, and credit points on what is paid
+        let coupon_discount = apply_coupon(
+            ctx.accounts.coupon.as_deref_mut(),
+            ctx.accounts.coupon_usage.as_deref_mut(),
+            ctx.accounts.mint.key(),
+            amount,
+        )?;
+        let discount = coupon_discount
+            + apply_loyalty(
+                ctx.accounts.loyalty_program.as_deref_mut(),
+                ctx.accounts.loyalty_balance.as_deref_mut(),
+                ctx.bumps.loyalty_balance,
+                ctx.accounts.merchant_account.key(),
+                ctx.accounts.payer.key(),
+                ctx.accounts.mint.key(),
+                amount - coupon_discount,
+                true,
+            )?;
+        let amount = amount - discount;
+
+        let protocol_fee = compute_protocol_fee(
+            amount,
+            ctx.accounts.merchant_account.fee_bps(&ctx.accounts.config),
+            token_fee_cap(&ctx.accounts.global_accepted_mint)?,
+        );
+        let merchant_amount = amount - protocol_fee;
+
+        // Transfer tokens from payer to merchant
+        if merchant_amount > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.payer_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.merchant_token_account.to_account_info(),
+                    authority: ctx.accounts.payer.to_account_info(),
+                },
+            );
+            transfer_checked(cpi_context, merchant_amount, ctx.accounts.mint.decimals)?;
+        }
+
+        // Transfer the protocol fee from payer to treasury
+        if protocol_fee > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.payer_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.treasury_token_account.to_account_info(),
+                    authority: ctx.accounts.payer.to_account_info(),
+                },
+            );
+            transfer_checked(cpi_context, protocol_fee, ctx.accounts.mint.decimals)?;
+        }
+
+        // Update on-chain payment statistics
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let first_payment = ctx.accounts.customer_record.record(
+            merchant_account_key,
+            ctx.accounts.payer.key(),
+            ctx.bumps.customer_record,
+            clock.unix_timestamp,
+        )?;
+        ctx.accounts.mint_stats.record(
+            merchant_account_key,
+            ctx.accounts.mint.key(),
+            ctx.bumps.mint_stats,
+            amount,
+        )?;
+        ctx.accounts
+            .merchant_stats
+            .record(first_payment, clock.unix_timestamp)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.payer = ctx.accounts.payer.key();
+        receipt.merchant_account = ctx.accounts.merchant_account.key();
+        receipt.merchant = ctx.accounts.merchant.key();
+        receipt.mint = ctx.accounts.mint.key();
+        receipt.amount = amount;
+        receipt.discount = discount;
+        receipt.protocol_fee = protocol_fee;
+        receipt.merchant_amount = merchant_amount;
+        receipt.refunded_amount = 0;
+        receipt.tip_amount = 0;
+        receipt.tips = Vec::new();
+        receipt.escrowed = false;
+        receipt.stamped = false;
+        receipt.reference = reference;
+        receipt.paid_at = clock.unix_timestamp;
+        receipt.bump = ctx.bumps.receipt;
+
+        msg!(
+            "Token payment: {} of mint {} from {} to {} (protocol fee: {})",
+            amount,
+            receipt.mint,
+            receipt.payer,
+            receipt.merchant,
+            protocol_fee
+        );
+
+        emit!(PaymentMadeEvent {
+            receipt: receipt.key(),
+            payer: receipt.payer,
+            merchant_account: receipt.merchant_account,
+            merchant: receipt.merchant,
+            mint: receipt.mint,
+            amount,
+            protocol_fee,
+            merchant_amount,
+            reference,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set or clear a merchant's protocol fee override (admin only)
+    ///
+    /// # Arguments
+    /// * `fee_bps` - Fee in basis points for this merchant (e.g. 0 for promotional
+    ///   partners), or `None` to fall back to the config fee
+    pub fn set_merchant_fee_override(
+        ctx: Context<SetMerchantFeeOverride>,
+        fee_bps: Option<u16>,
+    ) -> Result<()> {
+        if let Some(fee_bps) = fee_bps {
+            require!(fee_bps <= MAX_BASIS_POINTS, ErrorCode::InvalidFeeBps);
+        }
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.fee_bps_override = fee_bps;
+
+        msg!(
+            "Fee override for merchant {}: {:?} bps",
+            merchant_account.merchant,
+            fee_bps
+        );
+
+        emit!(MerchantFeeOverrideSetEvent {
+            merchant_account: merchant_account.key(),
+            fee_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create a payment request (invoice) for an exact amount
+    ///
+    /// # Arguments
+    /// * `request_id` - Merchant-chosen request ID (used as PDA seed)
+    /// * `mint` - Mint to be paid in (NATIVE_SOL_MINT for SOL)
+    /// * `amount` - Exact amount in the mint's base units
+    /// * `memo` - Free-form memo shown to the customer (max 100 bytes)
+    /// * `expires_at` - Optional Unix timestamp after which the request can no longer be paid
+    /// * `reusable` - If false the request is settled by its first payment
+    ///
+    /// # Security
+    /// - Only an active merchant or a delegate with `PERM_ISSUE_INVOICES` (signer) can create requests
+    /// - Token mints are checked against the accepted-mint lists when the request is paid
+    /// - PDA seeds: [b"payment_request", merchant_account, request_id]
+    pub fn create_payment_request(
+        ctx: Context<CreatePaymentRequest>,
+        request_id: u64,
+        mint: Pubkey,
+        amount: u64,
+        memo: String,
+        expires_at: Option<i64>,
+        reusable: bool,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(memo.len() <= MAX_MEMO_LEN, ErrorCode::MemoTooLong);
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_ISSUE_INVOICES,
+        )?;
+
+        let clock = Clock::get()?;
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > clock.unix_timestamp,
+                ErrorCode::InvalidRequestExpiry
+            );
+        }
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.payment_request_count = merchant_account
+            .payment_request_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let payment_request = &mut ctx.accounts.payment_request;
+        payment_request.merchant_account = ctx.accounts.merchant_account.key();
+        payment_request.request_id = request_id;
+        payment_request.mint = mint;
+        payment_request.amount = amount;
+        payment_request.memo = memo;
+        payment_request.expires_at = expires_at;
+        payment_request.reusable = reusable;
+        payment_request.status = PaymentRequestStatus::Open;
+        payment_request.payment_count = 0;
+        payment_request.last_receipt = None;
+        payment_request.created_at = clock.unix_timestamp;
+        payment_request.bump = ctx.bumps.payment_request;
+
+        msg!(
+            "Payment request {} created: {} of mint {}",
+            request_id,
+            amount,
+            mint
+        );
+
+        emit!(PaymentRequestCreatedEvent {
+            payment_request: payment_request.key(),
+            merchant_account: payment_request.merchant_account,
+            request_id,
+            mint,
+            amount,
+            expires_at,
+            reusable,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay an open payment request in full
+    ///
+    /// SOL requests take no token accounts; token requests need `mint`, `accepted_mint`,
+    /// the payer's token account, both ATAs and the token programs.
+    ///
+    /// # Arguments
+    /// * `reference` - Client-generated payment reference (used as receipt PDA seed)
+    ///
+    /// # Security
+    /// - The request must be open and not past its expiry
+    /// - The paid mint must match the request mint (and still be accepted for tokens)
+    /// - The protocol fee is split off exactly as in `pay_merchant_sol` / `pay_merchant_token`
+    /// - Single-use requests move to `Paid`; reusable requests stay `Open`
+    /// - With the loyalty accounts passed, points are credited on the full amount
+    /// - The request amount is final: coupons and loyalty discounts do not apply
+    pub fn pay_payment_request(ctx: Context<PayPaymentRequest>, reference: [u8; 32]) -> Result<()> {
+        let clock = Clock::get()?;
+
+        let payment_request = &ctx.accounts.payment_request;
+        match payment_request.status {
+            PaymentRequestStatus::Open => {
+                if let Some(expires_at) = payment_request.expires_at {
+                    require!(
+                        clock.unix_timestamp < expires_at,
+                        ErrorCode::PaymentRequestExpired
+                    );
+                }
+            }
+            PaymentRequestStatus::Expired => return err!(ErrorCode::PaymentRequestExpired),
+            _ => return err!(ErrorCode::PaymentRequestNotOpen),
+        }
+
+        let amount = payment_request.amount;
+        let mint_key = payment_request.mint;
+        let fee_bps = ctx.accounts.merchant_account.fee_bps(&ctx.accounts.config);
+
+        let (protocol_fee, merchant_amount) = if mint_key == NATIVE_SOL_MINT {
+            require!(ctx.accounts.mint.is_none(), ErrorCode::WrongPaymentMint);
+
+            let protocol_fee =
+                compute_protocol_fee(amount, fee_bps, ctx.accounts.config.protocol_fee_cap);
+            let merchant_amount = amount - protocol_fee;
+
+            // Transfer lamports from payer to merchant
+            if merchant_amount > 0 {
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: ctx.accounts.merchant.to_account_info(),
+                    },
+                );
+                transfer(cpi_context, merchant_amount)?;
+            }
+
+            // Transfer the protocol fee from payer to treasury
+            if protocol_fee > 0 {
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: ctx.accounts.treasury.to_account_info(),
+                    },
+                );
+                transfer(cpi_context, protocol_fee)?;
+            }
+
+            (protocol_fee, merchant_amount)
+        } else {
+            let mint = ctx
+                .accounts
+                .mint
+                .as_ref()
+                .ok_or(ErrorCode::WrongPaymentMint)?;
+            require_keys_eq!(mint.key(), mint_key, ErrorCode::WrongPaymentMint);
+            require!(
+                ctx.accounts.accepted_mint.is_some(),
+                ErrorCode::MintNotAccepted
+            );
+            let global_accepted_mint = ctx
+                .accounts
+                .global_accepted_mint
+                .as_ref()
+                .ok_or(ErrorCode::MissingTokenAccounts)?;
+            let (
+                Some(payer_token_account),
+                Some(merchant_token_account),
+                Some(treasury_token_account),
+                Some(token_program),
+            ) = (
+                ctx.accounts.payer_token_account.as_ref(),
+                ctx.accounts.merchant_token_account.as_ref(),
+                ctx.accounts.treasury_token_account.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            )
+            else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+
+            let protocol_fee =
+                compute_protocol_fee(amount, fee_bps, token_fee_cap(global_accepted_mint)?);
+            let merchant_amount = amount - protocol_fee;
+
+            // Transfer tokens from payer to merchant
+            if merchant_amount > 0 {
+                let cpi_context = CpiContext::new(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: payer_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: merchant_token_account.to_account_info(),
+                        authority: ctx.accounts.payer.to_account_info(),
+                    },
+                );
+                transfer_checked(cpi_context, merchant_amount, mint.decimals)?;
+            }
+
+            // Transfer the protocol fee from payer to treasury
+            if protocol_fee > 0 {
+                let cpi_context = CpiContext::new(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: payer_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: treasury_token_account.to_account_info(),
+                        authority: ctx.accounts.payer.to_account_info(),
+                    },
+                );
+                transfer_checked(cpi_context, protocol_fee, mint.decimals)?;
+            }
+
+            (protocol_fee, merchant_amount)
+        };
+
+        // Invoices are paid in full, so loyalty points are earned but no discount is taken
+        apply_loyalty(
+            ctx.accounts.loyalty_program.as_deref_mut(),
+            ctx.accounts.loyalty_balance.as_deref_mut(),
+            ctx.bumps.loyalty_balance,
+            ctx.accounts.merchant_account.key(),
+            ctx.accounts.payer.key(),
+            mint_key,
+            amount,
+            false,
+        )?;
+
+        // Update on-chain payment statistics
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let first_payment = ctx.accounts.customer_record.record(
+            merchant_account_key,
+            ctx.accounts.payer.key(),
+            ctx.bumps.customer_record,
+            clock.unix_timestamp,
+        )?;
+        ctx.accounts.mint_stats.record(
+            merchant_account_key,
+            mint_key,
+            ctx.bumps.mint_stats,
+            amount,
+        )?;
+        ctx.accounts
+            .merchant_stats
+            .record(first_payment, clock.unix_timestamp)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.payer = ctx.accounts.payer.key();
+        receipt.merchant_account = merchant_account_key;
+        receipt.merchant = ctx.accounts.merchant.key();
+        receipt.mint = mint_key;
+        receipt.amount = amount;
+        receipt.discount = 0;
+        receipt.protocol_fee = protocol_fee;
+        receipt.merchant_amount = merchant_amount;
+        receipt.refunded_amount = 0;
+        receipt.tip_amount = 0;
+        receipt.tips = Vec::new();
+        receipt.escrowed = false;
+        receipt.stamped = false;
+        receipt.reference = reference;
+        receipt.paid_at = clock.unix_timestamp;
+        receipt.bump = ctx.bumps.receipt;
+
+        let payment_request = &mut ctx.accounts.payment_request;
+        payment_request.payment_count = payment_request
+            .payment_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        payment_request.last_receipt = Some(receipt.key());
+        if !payment_request.reusable {
+            payment_request.status = PaymentRequestStatus::Paid;
+        }
+
+        msg!(
+            "Payment request {} paid: {} of mint {} by {}",
+            payment_request.request_id,
+            amount,
+            mint_key,
+            receipt.payer
+        );
+
+        emit!(PaymentMadeEvent {
+            receipt: receipt.key(),
+            payer: receipt.payer,
+            merchant_account: merchant_account_key,
+            merchant: receipt.merchant,
+            mint: mint_key,
+            amount,
+            protocol_fee,
+            merchant_amount,
+            reference,
+            timestamp: clock.unix_timestamp,
+        });
+
+        emit!(PaymentRequestPaidEvent {
+            payment_request: payment_request.key(),
+            receipt: receipt.key(),
+            payer: receipt.payer,
+            status: payment_request.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Mark an open payment request as expired (permissionless)
+    ///
+    /// # Security
+    /// - Only succeeds once the request's expiry has passed
+    pub fn expire_payment_request(ctx: Context<ExpirePaymentRequest>) -> Result<()> {
+        let clock = Clock::get()?;
+        let payment_request = &mut ctx.accounts.payment_request;
+
+        require!(
+            payment_request.status == PaymentRequestStatus::Open,
+            ErrorCode::PaymentRequestNotOpen
+        );
+        match payment_request.expires_at {
+            Some(expires_at) if clock.unix_timestamp >= expires_at => {}
+            _ => return err!(ErrorCode::PaymentRequestNotExpired),
+        }
+
+        payment_request.status = PaymentRequestStatus::Expired;
+
+        msg!("Payment request {} expired", payment_request.request_id);
+
+        emit!(PaymentRequestEndedEvent {
+            payment_request: payment_request.key(),
+            merchant_account: payment_request.merchant_account,
+            status: PaymentRequestStatus::Expired,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel an open payment request and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_ISSUE_INVOICES` (signer) can cancel requests
+    /// - Only open requests can be cancelled; use `close_payment_request` for settled ones
+    pub fn cancel_payment_request(ctx: Context<EndPaymentRequest>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_ISSUE_INVOICES,
+        )?;
+
+        let payment_request = &ctx.accounts.payment_request;
+        require!(
+            payment_request.status == PaymentRequestStatus::Open,
+            ErrorCode::PaymentRequestNotOpen
+        );
+        end_payment_request(&mut ctx.accounts.merchant_account);
+
+        msg!("Payment request {} cancelled", payment_request.request_id);
+
+        emit!(PaymentRequestEndedEvent {
+            payment_request: payment_request.key(),
+            merchant_account: payment_request.merchant_account,
+            status: PaymentRequestStatus::Cancelled,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Close a paid or expired payment request and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_ISSUE_INVOICES` (signer) can close requests
+    /// - Open requests must be cancelled instead; the receipts of settled ones remain
+    /// - The event carries the request's final status
+    pub fn close_payment_request(ctx: Context<EndPaymentRequest>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_ISSUE_INVOICES,
+        )?;
+
+        let payment_request = &ctx.accounts.payment_request;
+        require!(
+            payment_request.status != PaymentRequestStatus::Open,
+            ErrorCode::PaymentRequestStillOpen
+        );
+        end_payment_request(&mut ctx.accounts.merchant_account);
+
+        msg!(
+            "Payment request {} closed ({:?})",
+            payment_request.request_id,
+            payment_request.status
+        );
+
+        emit!(PaymentRequestEndedEvent {
+            payment_request: payment_request.key(),
+            merchant_account: payment_request.merchant_account,
+            status: payment_request.status,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Refund all or part of a payment to the original payer
+    ///
+    /// SOL receipts take no token accounts; token receipts need `mint`, the funding
+    /// token account, the payer's ATA and the token programs.
+    ///
+    /// The merchant refunds from their own wallet or token account. Delegates refund
+    /// from the merchant's refund vault ([b"refund_vault", merchant_account] for SOL,
+    /// its ATA for tokens), which the merchant funds by transferring to it.
+    ///
+    /// # Arguments
+    /// * `amount` - Amount to refund in the mint's base units
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_REFUND` (signer) can refund
+    /// - A delegate can only refund receipts in its `refund_mint`, and never more than
+    ///   its `refund_limit` in total across calls or more than the refund vault holds
+    /// - Funds can only go back to the payer recorded on the receipt
+    /// - Total refunds can never exceed the original payment amount
+    /// - Escrowed payments are refunded through the escrow instead
+    /// - Refunding a receipt that earned a stamp-card stamp takes the stamp back off the
+    ///   customer's card (`customer_stamps`); a stamp already spent on a claimed reward
+    ///   is not recovered
+    pub fn refund_payment(ctx: Context<RefundPayment>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref().map(|delegate| &**delegate),
+            PERM_REFUND,
+        )?;
+        if let Some(delegate) = ctx.accounts.delegate.as_deref_mut() {
+            require_keys_eq!(
+                delegate.refund_mint,
+                ctx.accounts.receipt.mint,
+                ErrorCode::WrongRefundMint
+            );
+            delegate.refunded_amount = delegate
+                .refunded_amount
+                .checked_add(amount)
+                .filter(|refunded| *refunded <= delegate.refund_limit)
+                .ok_or(ErrorCode::RefundLimitExceeded)?;
+        }
+
+        let receipt = &ctx.accounts.receipt;
+        require!(!receipt.escrowed, ErrorCode::EscrowPending);
+        let total_refunded = receipt
+            .refunded_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::RefundExceedsPayment)?;
+        require!(
+            total_refunded <= receipt.amount,
+            ErrorCode::RefundExceedsPayment
+        );
+
+        // Delegates refund out of the merchant's refund vault
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let vault = if ctx.accounts.delegate.is_some() {
+            let (Some(refund_vault), Some(vault_bump)) =
+                (ctx.accounts.refund_vault.as_ref(), ctx.bumps.refund_vault)
+            else {
+                return err!(ErrorCode::MissingRefundVault);
+            };
+            Some((refund_vault, vault_bump))
+        } else {
+            None
+        };
+
+        if receipt.mint == NATIVE_SOL_MINT {
+            require!(ctx.accounts.mint.is_none(), ErrorCode::WrongPaymentMint);
+
+            if let Some((refund_vault, vault_bump)) = vault {
+                check_vault_withdrawal(refund_vault, amount)?;
+                let signer_seeds: &[&[&[u8]]] = &[&[
+                    b"refund_vault",
+                    merchant_account_key.as_ref(),
+                    &[vault_bump],
+                ]];
+                let cpi_context = CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: refund_vault.to_account_info(),
+                        to: ctx.accounts.payer.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                transfer(cpi_context, amount)?;
+            } else {
+                // Transfer lamports from the merchant back to payer
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: ctx.accounts.payer.to_account_info(),
+                    },
+                );
+                transfer(cpi_context, amount)?;
+            }
+        } else {
+            let mint = ctx
+                .accounts
+                .mint
+                .as_ref()
+                .ok_or(ErrorCode::WrongPaymentMint)?;
+            require_keys_eq!(mint.key(), receipt.mint, ErrorCode::WrongPaymentMint);
+            let (Some(payer_token_account), Some(token_program)) = (
+                ctx.accounts.payer_token_account.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+
+            if let Some((refund_vault, vault_bump)) = vault {
+                let vault_token_account = ctx
+                    .accounts
+                    .vault_token_account
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingTokenAccounts)?;
+                let signer_seeds: &[&[&[u8]]] = &[&[
+                    b"refund_vault",
+                    merchant_account_key.as_ref(),
+                    &[vault_bump],
+                ]];
+                let cpi_context = CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: payer_token_account.to_account_info(),
+                        authority: refund_vault.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                transfer_checked(cpi_context, amount, mint.decimals)?;
+            } else {
+                let merchant_token_account = ctx
+                    .accounts
+                    .merchant_token_account
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingTokenAccounts)?;
+
+                // Transfer tokens from the merchant back to payer
+                let cpi_context = CpiContext::new(
+                    token_program.to_account_info(),
+                    TransferChecked {
+                        from: merchant_token_account.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: payer_token_account.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                    },
+                );
+                transfer_checked(cpi_context, amount, mint.decimals)?;
+            }
+        }
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.refunded_amount = total_refunded;
+
+        // Claw back the stamp the receipt earned
+        if receipt.stamped {
+            let customer_stamps = ctx
+                .accounts
+                .customer_stamps
+                .as_deref_mut()
+                .ok_or(ErrorCode::MissingCustomerStamps)?;
+            customer_stamps.stamps = customer_stamps.stamps.saturating_sub(1);
+            receipt.stamped = false;
+
+            msg!(
+                "Stamp clawed back from {} ({} left)",
+                customer_stamps.customer,
+                customer_stamps.stamps
+            );
+        }
+
+        ctx.accounts.merchant_stats.record_refund()?;
+        ctx.accounts.mint_stats.record_refund(amount)?;
+
+        msg!(
+            "Refunded {} of mint {} to {} ({} of {} refunded)",
+            amount,
+            receipt.mint,
+            receipt.payer,
+            total_refunded,
+            receipt.amount
+        );
+
+        emit!(PaymentRefundedEvent {
+            receipt: receipt.key(),
+            merchant_account: receipt.merchant_account,
+            payer: receipt.payer,
+            mint: receipt.mint,
+            amount,
+            total_refunded,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw SOL or tokens from the merchant's refund vault
+    ///
+    /// Pass `mint` and the token accounts to withdraw tokens; without them lamports
+    /// are withdrawn.
+    ///
+    /// # Arguments
+    /// * `amount` - Amount to withdraw in the mint's base units
+    ///
+    /// # Security
+    /// - Only the merchant owner (signer) can withdraw
+    /// - A SOL withdrawal must empty the vault or leave it rent-exempt
+    pub fn withdraw_refund_vault(ctx: Context<WithdrawRefundVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"refund_vault",
+            merchant_account_key.as_ref(),
+            &[ctx.bumps.refund_vault],
+        ]];
+
+        let mint_key = if let Some(mint) = ctx.accounts.mint.as_ref() {
+            let (Some(vault_token_account), Some(merchant_token_account), Some(token_program)) = (
+                ctx.accounts.vault_token_account.as_ref(),
+                ctx.accounts.merchant_token_account.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+
+            let cpi_context = CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: vault_token_account.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: merchant_token_account.to_account_info(),
+                    authority: ctx.accounts.refund_vault.to_account_info(),
+                },
+                signer_seeds,
+            );
+            transfer_checked(cpi_context, amount, mint.decimals)?;
+            mint.key()
+        } else {
+            check_vault_withdrawal(&ctx.accounts.refund_vault, amount)?;
+            let cpi_context = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.refund_vault.to_account_info(),
+                    to: ctx.accounts.merchant.to_account_info(),
+                },
+                signer_seeds,
+            );
+            transfer(cpi_context, amount)?;
+            NATIVE_SOL_MINT
+        };
+
+        msg!(
+            "Withdrew {} of mint {} from the refund vault of {}",
+            amount,
+            mint_key,
+            ctx.accounts.merchant.key()
+        );
+
+        emit!(RefundVaultWithdrawnEvent {
+            merchant_account: merchant_account_key,
+            mint: mint_key,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay a merchant in SOL through an escrow (buyer protection)
+    ///
+    /// The lamports are held in the escrow PDA until the customer confirms receipt,
+    /// the merchant claims after the dispute window, or a dispute is resolved.
+    ///
+    /// # Arguments
+    /// * `amount` - Amount in lamports
+    /// * `reference` - Client-generated payment reference (used as receipt PDA seed)
+    ///
+    /// # Security
+    /// - The merchant must be active; the held escrow counts as an open obligation
+    /// - The protocol fee is fixed at funding time and only charged on release
+    /// - Escrow payments earn no loyalty points and take no loyalty or coupon discount,
+    ///   since the funds can still go back to the customer through a dispute
+    /// - PDA seeds: [b"escrow", receipt]
+    pub fn escrow_payment_sol(
+        ctx: Context<EscrowPaymentSol>,
+        amount: u64,
+        reference: [u8; 32],
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+
+        let config = &ctx.accounts.config;
+        let protocol_fee = compute_protocol_fee(
+            amount,
+            ctx.accounts.merchant_account.fee_bps(config),
+            config.protocol_fee_cap,
+        );
+        let merchant_amount = amount - protocol_fee;
+        let release_after = clock
+            .unix_timestamp
+            .saturating_add(config.escrow_dispute_window);
+
+        // Lock lamports in the escrow PDA
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        );
+        transfer(cpi_context, amount)?;
+
+        // Update on-chain payment statistics
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let first_payment = ctx.accounts.customer_record.record(
+            merchant_account_key,
+            ctx.accounts.payer.key(),
+            ctx.bumps.customer_record,
+            clock.unix_timestamp,
+        )?;
+        ctx.accounts.mint_stats.record(
+            merchant_account_key,
+            NATIVE_SOL_MINT,
+            ctx.bumps.mint_stats,
+            amount,
+        )?;
+        ctx.accounts
+            .merchant_stats
+            .record(first_payment, clock.unix_timestamp)?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.open_obligations = merchant_account
+            .open_obligations
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.payer = ctx.accounts.payer.key();
+        receipt.merchant_account = merchant_account_key;
+        receipt.merchant = ctx.accounts.merchant.key();
+        receipt.mint = NATIVE_SOL_MINT;
+        receipt.amount = amount;
+        receipt.discount = 0;
+        receipt.protocol_fee = protocol_fee;
+        receipt.merchant_amount = merchant_amount;
+        receipt.refunded_amount = 0;
+        receipt.tip_amount = 0;
+        receipt.tips = Vec::new();
+        receipt.escrowed = true;
+        receipt.stamped = false;
+        receipt.reference = reference;
+        receipt.paid_at = clock.unix_timestamp;
+        receipt.bump = ctx.bumps.receipt;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.receipt = receipt.key();
+        escrow.payer = receipt.payer;
+        escrow.merchant_account = merchant_account_key;
+        escrow.mint = NATIVE_SOL_MINT;
+        escrow.amount = amount;
+        escrow.protocol_fee = protocol_fee;
+        escrow.merchant_amount = merchant_amount;
+        escrow.funded_at = clock.unix_timestamp;
+        escrow.release_after = release_after;
+        escrow.status = EscrowStatus::Held;
+        escrow.bump = ctx.bumps.escrow;
+
+        msg!(
+            "SOL escrow funded: {} lamports from {} for merchant {}, releasable after {}",
+            amount,
+            escrow.payer,
+            receipt.merchant,
+            release_after
+        );
+
+        emit!(EscrowFundedEvent {
+            escrow: escrow.key(),
+            receipt: escrow.receipt,
+            payer: escrow.payer,
+            merchant_account: merchant_account_key,
+            mint: NATIVE_SOL_MINT,
+            amount,
+            release_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay a merchant in an SPL token (Token or Token-2022) through an escrow vault
+    ///
+    /// # Arguments
+    /// * `amount` - Amount in the mint's base units
+    /// * `reference` - Client-generated payment reference (used as receipt PDA seed)
+    ///
+    /// # Security
+    /// - Same rules as `escrow_payment_sol`; the mint must be accepted as in `pay_merchant_token`
+    /// - Tokens are held in a vault owned by the escrow PDA: [b"escrow_vault", escrow]
+    pub fn escrow_payment_token(
+        ctx: Context<EscrowPaymentToken>,
+        amount: u64,
+        reference: [u8; 32],
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+
+        let protocol_fee = compute_protocol_fee(
+            amount,
+            ctx.accounts.merchant_account.fee_bps(&ctx.accounts.config),
+            token_fee_cap(&ctx.accounts.global_accepted_mint)?,
+        );
+        let merchant_amount = amount - protocol_fee;
+        let release_after = clock
+            .unix_timestamp
+            .saturating_add(ctx.accounts.config.escrow_dispute_window);
+
+        // Lock tokens in the escrow vault
+        let cpi_context = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.payer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.payer.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_context, amount, ctx.accounts.mint.decimals)?;
+
+        // Update on-chain payment statistics
+        let merchant_account_key = ctx.accounts.merchant_account.key();
+        let mint_key = ctx.accounts.mint.key();
+        let first_payment = ctx.accounts.customer_record.record(
+            merchant_account_key,
+            ctx.accounts.payer.key(),
+            ctx.bumps.customer_record,
+            clock.unix_timestamp,
+        )?;
+        ctx.accounts.mint_stats.record(
+            merchant_account_key,
+            mint_key,
+            ctx.bumps.mint_stats,
+            amount,
+        )?;
+        ctx.accounts
+            .merchant_stats
+            .record(first_payment, clock.unix_timestamp)?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.open_obligations = merchant_account
+            .open_obligations
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.payer = ctx.accounts.payer.key();
+        receipt.merchant_account = merchant_account_key;
+        receipt.merchant = ctx.accounts.merchant.key();
+        receipt.mint = mint_key;
+        receipt.amount = amount;
+        receipt.discount = 0;
+        receipt.protocol_fee = protocol_fee;
+        receipt.merchant_amount = merchant_amount;
+        receipt.refunded_amount = 0;
+        receipt.tip_amount = 0;
+        receipt.tips = Vec::new();
+        receipt.escrowed = true;
+        receipt.stamped = false;
+        receipt.reference = reference;
+        receipt.paid_at = clock.unix_timestamp;
+        receipt.bump = ctx.bumps.receipt;
+
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.receipt = receipt.key();
+        escrow.payer = receipt.payer;
+        escrow.merchant_account = merchant_account_key;
+        escrow.mint = mint_key;
+        escrow.amount = amount;
+        escrow.protocol_fee = protocol_fee;
+        escrow.merchant_amount = merchant_amount;
+        escrow.funded_at = clock.unix_timestamp;
+        escrow.release_after = release_after;
+        escrow.status = EscrowStatus::Held;
+        escrow.bump = ctx.bumps.escrow;
+
+        msg!(
+            "Token escrow funded: {} of mint {} from {} for merchant {}, releasable after {}",
+            amount,
+            mint_key,
+            escrow.payer,
+            receipt.merchant,
+            release_after
+        );
+
+        emit!(EscrowFundedEvent {
+            escrow: escrow.key(),
+            receipt: escrow.receipt,
+            payer: escrow.payer,
+            merchant_account: merchant_account_key,
+            mint: mint_key,
+            amount,
+            release_after,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Confirm receipt of an escrowed order and release the funds to the merchant
+    ///
+    /// # Security
+    /// - Only the customer who funded the escrow (signer) can confirm
+    /// - Also withdraws a dispute the customer no longer wants to pursue
+    ///   (pass the dispute account; recorded as a full release)
+    pub fn confirm_receipt(ctx: Context<SettleEscrow>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.authority.key(),
+            ctx.accounts.escrow.payer,
+            ErrorCode::Unauthorized
+        );
+
+        if ctx.accounts.escrow.status == EscrowStatus::Disputed {
+            return rule_dispute(ctx.accounts, 0, false);
+        }
+
+        let escrow = &ctx.accounts.escrow;
+        let (to_merchant, to_treasury) = (escrow.merchant_amount, escrow.protocol_fee);
+        settle_escrow(ctx.accounts, to_merchant, to_treasury, 0)
+    }
+
+    /// Claim an undisputed escrow once its dispute window has passed
+    ///
+    /// # Security
+    /// - Only the merchant's current wallet or a delegate with `PERM_MANAGE_ESCROW` (signer)
+    ///   can claim; funds always go to the merchant's wallet
+    /// - Fails while the dispute window is open or a dispute is pending
+    pub fn claim_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref().map(|delegate| &**delegate),
+            PERM_MANAGE_ESCROW,
+        )?;
+
+        let escrow = &ctx.accounts.escrow;
+        require!(
+            escrow.status == EscrowStatus::Held,
+            ErrorCode::EscrowDisputed
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= escrow.release_after,
+            ErrorCode::EscrowWindowOpen
+        );
+
+        let (to_merchant, to_treasury) = (escrow.merchant_amount, escrow.protocol_fee);
+        settle_escrow(ctx.accounts, to_merchant, to_treasury, 0)
+    }
+
+    /// Register a wallet as a dispute arbiter (admin only)
+    ///
+    /// # Arguments
+    /// * `arbiter` - Wallet allowed to rule on escrow disputes
+    ///
+    /// # Security
+    /// - Only the config admin can add arbiters
+    /// - PDA seeds: [b"arbiter", arbiter]
+    pub fn add_arbiter(ctx: Context<AddArbiter>, arbiter: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+
+        let arbiter_account = &mut ctx.accounts.arbiter_account;
+        arbiter_account.authority = arbiter;
+        arbiter_account.added_at = clock.unix_timestamp;
+        arbiter_account.bump = ctx.bumps.arbiter_account;
+
+        msg!("Arbiter added: {}", arbiter);
+
+        emit!(ArbiterAddedEvent {
+            arbiter,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an arbiter from the registry (admin only)
+    ///
+    /// Closes the arbiter PDA and returns its rent to the admin.
+    pub fn remove_arbiter(ctx: Context<RemoveArbiter>) -> Result<()> {
+        let arbiter = ctx.accounts.arbiter_account.authority;
+
+        msg!("Arbiter removed: {}", arbiter);
+
+        emit!(ArbiterRemovedEvent {
+            arbiter,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Open a dispute on a held escrow, freezing it until it is resolved
+    ///
+    /// # Arguments
+    /// * `reason_code` - Non-zero reason code defined by the client (e.g. not delivered)
+    /// * `evidence_hash` - Optional hash of the opener's first piece of evidence
+    ///
+    /// # Security
+    /// - The customer who funded the escrow, the merchant owner or a delegate with
+    ///   `PERM_MANAGE_ESCROW` (signer) can open a dispute; the opener is recorded
+    /// - The opener pays the dispute rent, which goes to the customer on settlement
+    /// - Must be opened before the escrow's dispute window ends
+    /// - Arbiters have `dispute_ruling_window` seconds to rule before the default applies
+    /// - PDA seeds: [b"dispute", escrow]
+    pub fn open_dispute(
+        ctx: Context<OpenDispute>,
+        reason_code: u8,
+        evidence_hash: Option<[u8; 32]>,
+    ) -> Result<()> {
+        require!(reason_code != 0, ErrorCode::InvalidReasonCode);
+
+        let opened_by = ctx.accounts.authority.key();
+        let by_customer = opened_by == ctx.accounts.escrow.payer;
+        if !by_customer {
+            ctx.accounts.merchant_account.authorize(
+                &opened_by,
+                ctx.accounts.delegate.as_deref(),
+                PERM_MANAGE_ESCROW,
+            )?;
+        }
+
+        let clock = Clock::get()?;
+        let escrow = &mut ctx.accounts.escrow;
+        require!(
+            escrow.status == EscrowStatus::Held,
+            ErrorCode::EscrowDisputed
+        );
+        require!(
+            clock.unix_timestamp < escrow.release_after,
+            ErrorCode::DisputeWindowClosed
+        );
+        escrow.status = EscrowStatus::Disputed;
+
+        let ruling_deadline = clock
+            .unix_timestamp
+            .saturating_add(ctx.accounts.config.dispute_ruling_window);
+
+        let dispute = &mut ctx.accounts.dispute;
+        dispute.escrow = escrow.key();
+        dispute.receipt = escrow.receipt;
+        dispute.merchant_account = escrow.merchant_account;
+        dispute.customer = escrow.payer;
+        dispute.opened_by = opened_by;
+        dispute.reason_code = reason_code;
+        let evidence: Vec<[u8; 32]> = evidence_hash.into_iter().collect();
+        if by_customer {
+            dispute.customer_evidence = evidence;
+            dispute.merchant_evidence = Vec::new();
+        } else {
+            dispute.customer_evidence = Vec::new();
+            dispute.merchant_evidence = evidence;
+        }
+        dispute.opened_at = clock.unix_timestamp;
+        dispute.ruling_deadline = ruling_deadline;
+        dispute.bump = ctx.bumps.dispute;
+
+        msg!(
+            "Dispute opened on escrow {} by {} (reason {}), ruling due by {}",
+            dispute.escrow,
+            opened_by,
+            reason_code,
+            ruling_deadline
+        );
+
+        emit!(DisputeOpenedEvent {
+            dispute: dispute.key(),
+            escrow: dispute.escrow,
+            customer: dispute.customer,
+            merchant_account: dispute.merchant_account,
+            opened_by,
+            reason_code,
+            ruling_deadline,
+            timestamp: clock.unix_timestamp,
+        });
+
+        if let Some(evidence_hash) = evidence_hash {
+            emit!(DisputeEvidenceSubmittedEvent {
+                dispute: dispute.key(),
+                submitted_by: opened_by,
+                evidence_hash,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Attach an evidence hash to an open dispute
+    ///
+    /// # Arguments
+    /// * `evidence_hash` - Hash of the off-chain evidence (photos, chat logs, tracking data)
+    ///
+    /// # Security
+    /// - Only the customer, the merchant's current wallet or a delegate with
+    ///   `PERM_MANAGE_ESCROW` (signer) can submit evidence
+    /// - Each side can attach at most 4 hashes, before the ruling deadline
+    pub fn submit_dispute_evidence(
+        ctx: Context<SubmitDisputeEvidence>,
+        evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let authority = ctx.accounts.authority.key();
+        let dispute = &mut ctx.accounts.dispute;
+        require!(
+            clock.unix_timestamp < dispute.ruling_deadline,
+            ErrorCode::RulingWindowClosed
+        );
+
+        let evidence = if authority == dispute.customer {
+            &mut dispute.customer_evidence
+        } else {
+            ctx.accounts.merchant_account.authorize(
+                &authority,
+                ctx.accounts.delegate.as_deref(),
+                PERM_MANAGE_ESCROW,
+            )?;
+            &mut dispute.merchant_evidence
+        };
+        require!(
+            evidence.len() < MAX_DISPUTE_EVIDENCE,
+            ErrorCode::TooMuchEvidence
+        );
+        evidence.push(evidence_hash);
+
+        msg!(
+            "Evidence submitted to dispute {} by {}",
+            dispute.key(),
+            authority
+        );
+
+        emit!(DisputeEvidenceSubmittedEvent {
+            dispute: dispute.key(),
+            submitted_by: authority,
+            evidence_hash,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Rule on a dispute: release to the merchant, refund the customer, or split
+    ///
+    /// # Arguments
+    /// * `payer_share_bps` - Share of the escrow refunded to the customer
+    ///   (0 = full release, 10,000 = full refund); the protocol fee is charged
+    ///   only on the released part
+    ///
+    /// # Security
+    /// - Only the config admin or a registered arbiter (signer) can rule
+    /// - Must be called before the dispute's ruling deadline
+    pub fn resolve_dispute(ctx: Context<SettleEscrow>, payer_share_bps: u16) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == ctx.accounts.config.admin || ctx.accounts.arbiter.is_some(),
+            ErrorCode::Unauthorized
+        );
+
+        let dispute = ctx
+            .accounts
+            .dispute
+            .as_ref()
+            .ok_or(ErrorCode::EscrowNotDisputed)?;
+        require!(
+            Clock::get()?.unix_timestamp < dispute.ruling_deadline,
+            ErrorCode::RulingWindowClosed
+        );
+
+        rule_dispute(ctx.accounts, payer_share_bps, false)
+    }
+
+    /// Apply the configured default outcome to a dispute nobody ruled on in time
+    ///
+    /// Permissionless so the funds never stay locked; the caller only pays for
+    /// any token accounts that need creating.
+    pub fn apply_dispute_default(ctx: Context<SettleEscrow>) -> Result<()> {
+        let dispute = ctx
+            .accounts
+            .dispute
+            .as_ref()
+            .ok_or(ErrorCode::EscrowNotDisputed)?;
+        require!(
+            Clock::get()?.unix_timestamp >= dispute.ruling_deadline,
+            ErrorCode::RulingWindowOpen
+        );
+
+        let payer_share_bps = ctx.accounts.config.dispute_default_refund_bps;
+        rule_dispute(ctx.accounts, payer_share_bps, true)
+    }
+
+    /// Register a staff member who can receive tips
+    ///
+    /// # Arguments
+    /// * `wallet` - Staff wallet receiving tips
+    /// * `weight` - Share weight in the tip pool (0 = direct tips only)
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_STAFF` (signer) can manage staff
+    /// - The tip pool holds at most 8 members
+    /// - PDA seeds: [b"staff", merchant_account, wallet]
+    pub fn add_staff(ctx: Context<AddStaff>, wallet: Pubkey, weight: u16) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_STAFF,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.staff_count = merchant_account
+            .staff_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        if weight > 0 {
+            require!(
+                (merchant_account.tip_pool_size as usize) < MAX_TIP_LEGS,
+                ErrorCode::TipPoolFull
+            );
+            merchant_account.tip_pool_size += 1;
+        }
+
+        let staff = &mut ctx.accounts.staff;
+        staff.merchant_account = merchant_account.key();
+        staff.wallet = wallet;
+        staff.weight = weight;
+        staff.added_at = Clock::get()?.unix_timestamp;
+        staff.bump = ctx.bumps.staff;
+
+        msg!(
+            "Staff {} added to merchant {} (weight {})",
+            wallet,
+            merchant_account.merchant,
+            weight
+        );
+
+        emit!(StaffUpdatedEvent {
+            merchant_account: staff.merchant_account,
+            wallet,
+            weight: Some(weight),
+            timestamp: staff.added_at,
+        });
+
+        Ok(())
+    }
+
+    /// Change a staff member's tip pool weight (0 removes them from the pool)
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_STAFF` (signer) can manage staff
+    pub fn set_staff_weight(ctx: Context<UpdateStaff>, weight: u16) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_STAFF,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let staff = &mut ctx.accounts.staff;
+
+        match (staff.weight > 0, weight > 0) {
+            (false, true) => {
+                require!(
+                    (merchant_account.tip_pool_size as usize) < MAX_TIP_LEGS,
+                    ErrorCode::TipPoolFull
+                );
+                merchant_account.tip_pool_size += 1;
+            }
+            (true, false) => merchant_account.tip_pool_size -= 1,
+            _ => {}
+        }
+        staff.weight = weight;
+
+        msg!("Staff {} weight set to {}", staff.wallet, weight);
+
+        emit!(StaffUpdatedEvent {
+            merchant_account: staff.merchant_account,
+            wallet: staff.wallet,
+            weight: Some(weight),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Remove a staff member and reclaim the rent
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_STAFF` (signer) can manage staff
+    pub fn remove_staff(ctx: Context<RemoveStaff>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_STAFF,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        let staff = &ctx.accounts.staff;
+
+        merchant_account.staff_count -= 1;
+        if staff.weight > 0 {
+            merchant_account.tip_pool_size -= 1;
+        }
+
+        msg!(
+            "Staff {} removed from merchant {}",
+            staff.wallet,
+            merchant_account.merchant
+        );
+
+        emit!(StaffUpdatedEvent {
+            merchant_account: staff.merchant_account,
+            wallet: staff.wallet,
+            weight: None,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Give a key delegated rights to act for the merchant
+    ///
+    /// # Arguments
+    /// * `delegate` - Wallet receiving the rights (e.g. a cashier's device key)
+    /// * `permissions` - Bitmask of PERM_* permissions (non-zero)
+    /// * `refund_mint` - Mint the delegate may refund in (NATIVE_SOL_MINT for SOL)
+    /// * `refund_limit` - Max total the delegate may refund, in `refund_mint` base units
+    ///
+    /// # Security
+    /// - Only the merchant owner (signer) can manage delegates; delegates cannot
+    ///   add other delegates
+    /// - Anything without a PERM_* bit stays owner-only: ownership transfer, refund
+    ///   vault withdrawals and closing the merchant
+    /// - PDA seeds: [b"delegate", merchant_account, delegate]
+    pub fn add_delegate(
+        ctx: Context<AddDelegate>,
+        delegate: Pubkey,
+        permissions: u8,
+        refund_mint: Pubkey,
+        refund_limit: u64,
+    ) -> Result<()> {
+        // Every bit of the mask is a defined PERM_* permission
+        require!(permissions != 0, ErrorCode::InvalidPermissions);
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.delegate_count = merchant_account
+            .delegate_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let delegate_account = &mut ctx.accounts.delegate_account;
+        delegate_account.merchant_account = merchant_account.key();
+        delegate_account.authority = delegate;
+        delegate_account.permissions = permissions;
+        delegate_account.refund_limit = refund_limit;
+        delegate_account.refund_mint = refund_mint;
+        delegate_account.refunded_amount = 0;
+        delegate_account.added_at = Clock::get()?.unix_timestamp;
+        delegate_account.bump = ctx.bumps.delegate_account;
+
+        msg!(
+            "Delegate {} added to merchant {} (permissions {:#07b}, refund limit {} of mint {})",
+            delegate,
+            merchant_account.merchant,
+            permissions,
+            refund_limit,
+            refund_mint
+        );
+
+        emit!(DelegateUpdatedEvent {
+            merchant_account: delegate_account.merchant_account,
+            delegate,
+            permissions,
+            refund_mint,
+            refund_limit,
+            timestamp: delegate_account.added_at,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a delegate and reclaim the rent
+    ///
+    /// # Security
+    /// - Only the merchant owner (signer) can manage delegates
+    pub fn remove_delegate(ctx: Context<RemoveDelegate>) -> Result<()> {
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.delegate_count -= 1;
+
+        let delegate = ctx.accounts.delegate_account.authority;
+
+        msg!(
+            "Delegate {} removed from merchant {}",
+            delegate,
+            merchant_account.merchant
+        );
+
+        emit!(DelegateUpdatedEvent {
+            merchant_account: merchant_account.key(),
+            delegate,
+            permissions: 0,
+            refund_mint: Pubkey::default(),
+            refund_limit: 0,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Issue a single-use registration fee waiver to a wallet (admin only)
+    ///
+    /// # Arguments
+    /// * `merchant` - Wallet of the onboarding partner that may register for free
+    ///
+    /// # Security
+    /// - Only the config admin can issue waivers
+    /// - PDA seeds: [b"fee_waiver", merchant]; consumed by `register_merchant`
+    pub fn issue_fee_waiver(ctx: Context<IssueFeeWaiver>, merchant: Pubkey) -> Result<()> {
+        let fee_waiver = &mut ctx.accounts.fee_waiver;
+        fee_waiver.merchant = merchant;
+        fee_waiver.issued_by = ctx.accounts.admin.key();
+        fee_waiver.issued_at = Clock::get()?.unix_timestamp;
+        fee_waiver.bump = ctx.bumps.fee_waiver;
+
+        msg!("Registration fee waiver issued to {}", merchant);
+
+        emit!(FeeWaiverIssuedEvent {
+            merchant,
+            issued_by: fee_waiver.issued_by,
+            timestamp: fee_waiver.issued_at,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke an unused fee waiver (admin only)
+    ///
+    /// Closes the waiver PDA and returns its rent to the admin.
+    pub fn revoke_fee_waiver(ctx: Context<RevokeFeeWaiver>) -> Result<()> {
+        let merchant = ctx.accounts.fee_waiver.merchant;
+
+        msg!("Registration fee waiver revoked for {}", merchant);
+
+        emit!(FeeWaiverRevokedEvent {
+            merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Review a merchant the reviewer has paid
+    ///
+    /// # Arguments
+    /// * `rating` - Rating from 1 to 5
+    /// * `content_hash` - Hash of the review text stored off-chain
+    /// * `uri` - Where the review text is stored (max 200 bytes)
+    ///
+    /// # Security
+    /// - The reviewer (signer) must be the payer on a receipt for this merchant
+    ///   that has not been fully refunded and is no longer held in escrow
+    /// - One review per reviewer and merchant; PDA seeds: [b"review", merchant_account, reviewer]
+    /// - Adds the rating to the merchant's on-chain rating sum and count
+    pub fn submit_review(
+        ctx: Context<SubmitReview>,
+        rating: u8,
+        content_hash: [u8; 32],
+        uri: String,
+    ) -> Result<()> {
+        validate_review(rating, &uri)?;
+
+        let receipt = &ctx.accounts.receipt;
+        require!(
+            receipt.refunded_amount < receipt.amount,
+            ErrorCode::ReviewNotEligible
+        );
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.rating_sum = merchant_account
+            .rating_sum
+            .checked_add(rating as u64)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        merchant_account.rating_count = merchant_account
+            .rating_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let clock = Clock::get()?;
+        let review = &mut ctx.accounts.review;
+        review.merchant_account = merchant_account.key();
+        review.reviewer = ctx.accounts.reviewer.key();
+        review.receipt = receipt.key();
+        review.rating = rating;
+        review.content_hash = content_hash;
+        review.uri = uri;
+        review.created_at = clock.unix_timestamp;
+        review.updated_at = clock.unix_timestamp;
+        review.reply_hash = None;
+        review.replied_at = 0;
+        review.is_hidden = false;
+        review.hide_reason = 0;
+        review.bump = ctx.bumps.review;
+
+        msg!(
+            "Review by {} for merchant {}: {} stars",
+            review.reviewer,
+            merchant_account.merchant,
+            rating
+        );
+
+        emit!(ReviewSubmittedEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            reviewer: review.reviewer,
+            rating,
+            content_hash,
+            uri: review.uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Edit an existing review's rating and content
+    ///
+    /// # Security
+    /// - Only the reviewer (signer) can edit their review
+    /// - The merchant's rating sum is adjusted by the rating change unless the review is hidden
+    pub fn edit_review(
+        ctx: Context<EditReview>,
+        rating: u8,
+        content_hash: [u8; 32],
+        uri: String,
+    ) -> Result<()> {
+        validate_review(rating, &uri)?;
+
+        let review = &mut ctx.accounts.review;
+        let old_rating = review.rating;
+
+        if !review.is_hidden {
+            let merchant_account = &mut ctx.accounts.merchant_account;
+            merchant_account.rating_sum = merchant_account
+                .rating_sum
+                .saturating_sub(old_rating as u64)
+                .checked_add(rating as u64)
+                .ok_or(ErrorCode::StatsOverflow)?;
+        }
+
+        let clock = Clock::get()?;
+        review.rating = rating;
+        review.content_hash = content_hash;
+        review.uri = uri;
+        review.updated_at = clock.unix_timestamp;
+
+        msg!(
+            "Review {} edited: {} -> {} stars",
+            review.key(),
+            old_rating,
+            rating
+        );
+
+        emit!(ReviewEditedEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            old_rating,
+            new_rating: rating,
+            content_hash,
+            uri: review.uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Delete a review and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the reviewer (signer) can delete their review
+    /// - Hidden reviews cannot be deleted, so a moderated review cannot be wiped and
+    ///   resubmitted into the aggregate
+    /// - The rating is removed from the merchant's rating sum and count
+    pub fn delete_review(ctx: Context<DeleteReview>) -> Result<()> {
+        let review = &ctx.accounts.review;
+        require!(!review.is_hidden, ErrorCode::ReviewHidden);
+
+        ctx.accounts.merchant_account.remove_rating(review.rating);
+
+        msg!("Review {} deleted", review.key());
+
+        emit!(ReviewDeletedEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            reviewer: review.reviewer,
+            rating: review.rating,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Reply to a review of the merchant
+    ///
+    /// # Arguments
+    /// * `reply_hash` - Hash of the reply text stored off-chain (replaces any earlier reply)
+    ///
+    /// # Security
+    /// - Only the merchant owner or a delegate with `PERM_UPDATE_PROFILE` (signer) can
+    ///   reply to reviews of the business
+    pub fn reply_to_review(ctx: Context<ReplyToReview>, reply_hash: [u8; 32]) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_UPDATE_PROFILE,
+        )?;
+
+        let clock = Clock::get()?;
+        let review = &mut ctx.accounts.review;
+        review.reply_hash = Some(reply_hash);
+        review.replied_at = clock.unix_timestamp;
+
+        msg!("Merchant replied to review {}", review.key());
+
+        emit!(ReviewRepliedEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            reply_hash,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Hide a review from the rating aggregate (admin only)
+    ///
+    /// # Arguments
+    /// * `reason_code` - Non-zero moderation reason code
+    ///
+    /// The review account and its history are kept; `unhide_review` restores it.
+    pub fn hide_review(ctx: Context<ModerateReview>, reason_code: u8) -> Result<()> {
+        require!(reason_code != 0, ErrorCode::InvalidReasonCode);
+
+        let review = &mut ctx.accounts.review;
+        require!(!review.is_hidden, ErrorCode::ReviewHidden);
+        review.is_hidden = true;
+        review.hide_reason = reason_code;
+
+        ctx.accounts.merchant_account.remove_rating(review.rating);
+
+        msg!("Review {} hidden (reason {})", review.key(), reason_code);
+
+        emit!(ReviewHiddenEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            hidden: true,
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Restore a hidden review to the rating aggregate (admin only)
+    pub fn unhide_review(ctx: Context<ModerateReview>) -> Result<()> {
+        let review = &mut ctx.accounts.review;
+        require!(review.is_hidden, ErrorCode::ReviewNotHidden);
+        review.is_hidden = false;
+        review.hide_reason = 0;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.rating_sum = merchant_account
+            .rating_sum
+            .checked_add(review.rating as u64)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        merchant_account.rating_count = merchant_account
+            .rating_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        msg!("Review {} restored", review.key());
+
+        emit!(ReviewHiddenEvent {
+            review: review.key(),
+            merchant_account: review.merchant_account,
+            hidden: false,
+            reason_code: 0,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Start a loyalty points program for a merchant
+    ///
+    /// # Arguments
+    /// * `mint` - Mint that earns and redeems points (NATIVE_SOL_MINT for SOL)
+    /// * `earn_rate` - Points credited per 1,000,000 base units paid (lamports for SOL)
+    /// * `redeem_value` - Discount in base units that one point is worth
+    /// * `points_ttl` - Seconds without activity after which a balance expires (0 = never)
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage loyalty
+    /// - One program per merchant; PDA seeds: [b"loyalty_program", merchant_account]
+    pub fn create_loyalty_program(
+        ctx: Context<CreateLoyaltyProgram>,
+        mint: Pubkey,
+        earn_rate: u64,
+        redeem_value: u64,
+        points_ttl: i64,
+    ) -> Result<()> {
+        require!(points_ttl >= 0, ErrorCode::InvalidPointsTtl);
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let loyalty_program = &mut ctx.accounts.loyalty_program;
+        loyalty_program.merchant_account = ctx.accounts.merchant_account.key();
+        loyalty_program.mint = mint;
+        loyalty_program.earn_rate = earn_rate;
+        loyalty_program.redeem_value = redeem_value;
+        loyalty_program.points_ttl = points_ttl;
+        loyalty_program.is_active = true;
+        loyalty_program.created_at = Clock::get()?.unix_timestamp;
+        loyalty_program.bump = ctx.bumps.loyalty_program;
+        loyalty_program.balance_count = 0;
+        ctx.accounts.merchant_account.has_loyalty_program = true;
+
+        msg!(
+            "Loyalty program created for merchant {}: {} points per {} units of {}",
+            ctx.accounts.merchant_account.merchant,
+            earn_rate,
+            LOYALTY_EARN_RATE_UNIT,
+            mint
+        );
+
+        emit!(LoyaltyProgramUpdatedEvent {
+            loyalty_program: loyalty_program.key(),
+            merchant_account: loyalty_program.merchant_account,
+            mint,
+            earn_rate,
+            redeem_value,
+            points_ttl,
+            is_active: true,
+            timestamp: loyalty_program.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Update a merchant's loyalty program (`None` keeps the current value)
+    ///
+    /// Pausing the program stops earning and redemption; existing balances are kept and
+    /// discounts already redeemed still apply to the customer's next payment. While paused,
+    /// the merchant can also close balances to wind the program down.
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage loyalty
+    pub fn update_loyalty_program(
+        ctx: Context<UpdateLoyaltyProgram>,
+        earn_rate: Option<u64>,
+        redeem_value: Option<u64>,
+        points_ttl: Option<i64>,
+        is_active: Option<bool>,
+    ) -> Result<()> {
+        require!(
+            earn_rate.is_some()
+                || redeem_value.is_some()
+                || points_ttl.is_some()
+                || is_active.is_some(),
+            ErrorCode::NothingToUpdate
+        );
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let loyalty_program = &mut ctx.accounts.loyalty_program;
+        if let Some(earn_rate) = earn_rate {
+            loyalty_program.earn_rate = earn_rate;
+        }
+        if let Some(redeem_value) = redeem_value {
+            loyalty_program.redeem_value = redeem_value;
+        }
+        if let Some(points_ttl) = points_ttl {
+            require!(points_ttl >= 0, ErrorCode::InvalidPointsTtl);
+            loyalty_program.points_ttl = points_ttl;
+        }
+        if let Some(is_active) = is_active {
+            loyalty_program.is_active = is_active;
+        }
+
+        msg!(
+            "Loyalty program updated for merchant {}",
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(LoyaltyProgramUpdatedEvent {
+            loyalty_program: loyalty_program.key(),
+            merchant_account: loyalty_program.merchant_account,
+            mint: loyalty_program.mint,
+            earn_rate: loyalty_program.earn_rate,
+            redeem_value: loyalty_program.redeem_value,
+            points_ttl: loyalty_program.points_ttl,
+            is_active: loyalty_program.is_active,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Convert loyalty points into a discount on the customer's next payment to the merchant
+    ///
+    /// The discount is taken off the next `pay_merchant_sol` / `pay_merchant_token` in the
+    /// program's mint, so redeeming and paying can share one transaction.
+    ///
+    /// # Arguments
+    /// * `points` - Points to redeem
+    ///
+    /// # Security
+    /// - Only the customer (signer) can redeem their own points
+    /// - The program must be active and expired points cannot be redeemed
+    pub fn redeem_points(ctx: Context<RedeemPoints>, points: u64) -> Result<()> {
+        require!(points > 0, ErrorCode::InvalidAmount);
+
+        let loyalty_program = &ctx.accounts.loyalty_program;
+        require!(loyalty_program.is_active, ErrorCode::LoyaltyProgramInactive);
+
+        let now = Clock::get()?.unix_timestamp;
+        let balance = &mut ctx.accounts.loyalty_balance;
+        balance.expire(loyalty_program, now);
+        require!(balance.points >= points, ErrorCode::InsufficientPoints);
+
+        let discount = points
+            .checked_mul(loyalty_program.redeem_value)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        balance.points -= points;
+        balance.pending_discount = balance
+            .pending_discount
+            .checked_add(discount)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        balance.last_activity_at = now;
+
+        msg!(
+            "Customer {} redeemed {} points for a discount of {}",
+            balance.customer,
+            points,
+            discount
+        );
+
+        emit!(LoyaltyPointsRedeemedEvent {
+            merchant_account: balance.merchant_account,
+            customer: balance.customer,
+            points,
+            discount,
+            pending_discount: balance.pending_discount,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Close a customer's loyalty balance, returning its rent to the customer
+    ///
+    /// Any points and pending discount left on the balance are forfeited.
+    ///
+    /// # Security
+    /// - The customer (signer) can close their own balance at any time
+    /// - The merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can close it only
+    ///   while the program is paused, so that a program can always be wound down
+    /// - Rent always goes back to the customer, who paid it
+    pub fn close_loyalty_balance(ctx: Context<CloseLoyaltyBalance>) -> Result<()> {
+        let balance = &ctx.accounts.loyalty_balance;
+        if ctx.accounts.authority.key() != balance.customer {
+            ctx.accounts.merchant_account.authorize(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.delegate.as_deref(),
+                PERM_MANAGE_LOYALTY,
+            )?;
+            require!(
+                !ctx.accounts.loyalty_program.is_active,
+                ErrorCode::LoyaltyProgramActive
+            );
+        }
+
+        let loyalty_program = &mut ctx.accounts.loyalty_program;
+        loyalty_program.balance_count = loyalty_program.balance_count.saturating_sub(1);
+
+        msg!(
+            "Loyalty balance of {} closed with {} points",
+            balance.customer,
+            balance.points
+        );
+
+        emit!(LoyaltyBalanceClosedEvent {
+            merchant_account: balance.merchant_account,
+            customer: balance.customer,
+            points: balance.points,
+            pending_discount: balance.pending_discount,
+            closed_by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Close a merchant's loyalty program and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage loyalty
+    /// - Every customer balance must be closed first (see `close_loyalty_balance`)
+    pub fn close_loyalty_program(ctx: Context<CloseLoyaltyProgram>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let loyalty_program = &ctx.accounts.loyalty_program;
+        require!(
+            loyalty_program.balance_count == 0,
+            ErrorCode::LoyaltyProgramHasBalances
+        );
+        ctx.accounts.merchant_account.has_loyalty_program = false;
+
+        msg!(
+            "Loyalty program closed for merchant {}",
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(LoyaltyProgramClosedEvent {
+            loyalty_program: loyalty_program.key(),
+            merchant_account: loyalty_program.merchant_account,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set up a stamp card for a merchant ("buy N, get one free")
+    ///
+    /// # Arguments
+    /// * `mint` - Mint a payment must be in to earn a stamp (NATIVE_SOL_MINT for SOL)
+    /// * `stamps_required` - Stamps needed to claim the reward (at least 1)
+    /// * `reward_hash` - Hash of the reward description stored off-chain
+    /// * `min_purchase` - Minimum net payment in base units that earns a stamp
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage stamp cards
+    /// - One stamp card per merchant; PDA seeds: [b"stamp_card", merchant_account]
+    pub fn create_stamp_card(
+        ctx: Context<CreateStampCard>,
+        mint: Pubkey,
+        stamps_required: u8,
+        reward_hash: [u8; 32],
+        min_purchase: u64,
+    ) -> Result<()> {
+        require!(stamps_required > 0, ErrorCode::InvalidStampsRequired);
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let stamp_card = &mut ctx.accounts.stamp_card;
+        stamp_card.merchant_account = ctx.accounts.merchant_account.key();
+        stamp_card.mint = mint;
+        stamp_card.stamps_required = stamps_required;
+        stamp_card.reward_hash = reward_hash;
+        stamp_card.min_purchase = min_purchase;
+        stamp_card.is_active = true;
+        stamp_card.created_at = Clock::get()?.unix_timestamp;
+        stamp_card.bump = ctx.bumps.stamp_card;
+        stamp_card.holder_count = 0;
+        ctx.accounts.merchant_account.has_stamp_card = true;
+
+        msg!(
+            "Stamp card created for merchant {}: {} stamps, min purchase {} of {}",
+            ctx.accounts.merchant_account.merchant,
+            stamps_required,
+            min_purchase,
+            mint
+        );
+
+        emit!(StampCardUpdatedEvent {
+            stamp_card: stamp_card.key(),
+            merchant_account: stamp_card.merchant_account,
+            mint,
+            stamps_required,
+            reward_hash,
+            min_purchase,
+            is_active: true,
+            timestamp: stamp_card.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Update a merchant's stamp card (`None` keeps the current value)
+    ///
+    /// Pausing the card stops stamp collection; rewards already earned can still be claimed.
+    /// While paused, the merchant can also close customer cards to wind the card down.
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage stamp cards
+    pub fn update_stamp_card(
+        ctx: Context<UpdateStampCard>,
+        stamps_required: Option<u8>,
+        reward_hash: Option<[u8; 32]>,
+        min_purchase: Option<u64>,
+        is_active: Option<bool>,
+    ) -> Result<()> {
+        require!(
+            stamps_required.is_some()
+                || reward_hash.is_some()
+                || min_purchase.is_some()
+                || is_active.is_some(),
+            ErrorCode::NothingToUpdate
+        );
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let stamp_card = &mut ctx.accounts.stamp_card;
+        if let Some(stamps_required) = stamps_required {
+            require!(stamps_required > 0, ErrorCode::InvalidStampsRequired);
+            stamp_card.stamps_required = stamps_required;
+        }
+        if let Some(reward_hash) = reward_hash {
+            stamp_card.reward_hash = reward_hash;
+        }
+        if let Some(min_purchase) = min_purchase {
+            stamp_card.min_purchase = min_purchase;
+        }
+        if let Some(is_active) = is_active {
+            stamp_card.is_active = is_active;
+        }
+
+        msg!(
+            "Stamp card updated for merchant {}",
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(StampCardUpdatedEvent {
+            stamp_card: stamp_card.key(),
+            merchant_account: stamp_card.merchant_account,
+            mint: stamp_card.mint,
+            stamps_required: stamp_card.stamps_required,
+            reward_hash: stamp_card.reward_hash,
+            min_purchase: stamp_card.min_purchase,
+            is_active: stamp_card.is_active,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Add a stamp to the customer's card for a qualifying payment receipt
+    ///
+    /// # Security
+    /// - Only the customer who paid (signer) can stamp with their receipt
+    /// - The receipt must be in the card's mint, made after the card was set up, settled
+    ///   (not escrowed), never refunded and at least `min_purchase`
+    /// - Refunding a stamped receipt takes its stamp back off the card (see `refund_payment`)
+    /// - Each receipt earns at most one stamp, and a full card must be claimed first
+    /// - PDA seeds: [b"stamps", merchant_account, customer]; created on first use (customer pays rent)
+    pub fn collect_stamp(ctx: Context<CollectStamp>) -> Result<()> {
+        let stamp_card = &mut ctx.accounts.stamp_card;
+        require!(stamp_card.is_active, ErrorCode::StampCardInactive);
+
+        let receipt = &mut ctx.accounts.receipt;
+        require!(!receipt.stamped, ErrorCode::ReceiptAlreadyStamped);
+        require!(
+            receipt.mint == stamp_card.mint
+                && !receipt.escrowed
+                && receipt.paid_at >= stamp_card.created_at
+                && receipt.refunded_amount == 0
+                && receipt.amount >= stamp_card.min_purchase,
+            ErrorCode::ReceiptNotQualifying
+        );
+
+        let customer_stamps = &mut ctx.accounts.customer_stamps;
+        require!(
+            customer_stamps.stamps < stamp_card.stamps_required,
+            ErrorCode::StampCardFull
+        );
+
+        if customer_stamps.merchant_account == Pubkey::default() {
+            stamp_card.holder_count = stamp_card
+                .holder_count
+                .checked_add(1)
+                .ok_or(ErrorCode::StatsOverflow)?;
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        receipt.stamped = true;
+        customer_stamps.merchant_account = stamp_card.merchant_account;
+        customer_stamps.customer = ctx.accounts.customer.key();
+        customer_stamps.stamps += 1;
+        customer_stamps.last_stamped_at = now;
+        customer_stamps.bump = ctx.bumps.customer_stamps;
+
+        msg!(
+            "Stamp {}/{} collected by {}",
+            customer_stamps.stamps,
+            stamp_card.stamps_required,
+            customer_stamps.customer
+        );
+
+        emit!(StampCollectedEvent {
+            merchant_account: customer_stamps.merchant_account,
+            customer: customer_stamps.customer,
+            receipt: receipt.key(),
+            stamps: customer_stamps.stamps,
+            stamps_required: stamp_card.stamps_required,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem a full stamp card for its reward, carrying any extra stamps over to the next card
+    ///
+    /// # Security
+    /// - The customer (signer) must present the card and the merchant or a delegate with
+    ///   `PERM_MANAGE_LOYALTY` (signer) must confirm the reward was handed over
+    /// - The card must hold at least the currently required number of stamps
+    pub fn claim_stamp_reward(ctx: Context<ClaimStampReward>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let stamp_card = &ctx.accounts.stamp_card;
+        let customer_stamps = &mut ctx.accounts.customer_stamps;
+        require!(
+            customer_stamps.stamps >= stamp_card.stamps_required,
+            ErrorCode::StampCardIncomplete
+        );
+
+        customer_stamps.stamps -= stamp_card.stamps_required;
+        customer_stamps.rewards_claimed = customer_stamps
+            .rewards_claimed
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        msg!(
+            "Stamp reward claimed by {} (confirmed by {})",
+            customer_stamps.customer,
+            ctx.accounts.authority.key()
+        );
+
+        emit!(StampRewardClaimedEvent {
+            merchant_account: customer_stamps.merchant_account,
+            customer: customer_stamps.customer,
+            reward_hash: stamp_card.reward_hash,
+            rewards_claimed: customer_stamps.rewards_claimed,
+            confirmed_by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Close a customer's stamp card, returning its rent to the customer
+    ///
+    /// Any stamps left on the card are forfeited.
+    ///
+    /// # Security
+    /// - The customer (signer) can close their own card at any time
+    /// - The merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can close it only
+    ///   while the stamp card is paused, so that a card can always be wound down
+    /// - Rent always goes back to the customer, who paid it
+    pub fn close_customer_stamps(ctx: Context<CloseCustomerStamps>) -> Result<()> {
+        let customer_stamps = &ctx.accounts.customer_stamps;
+        if ctx.accounts.authority.key() != customer_stamps.customer {
+            ctx.accounts.merchant_account.authorize(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.delegate.as_deref(),
+                PERM_MANAGE_LOYALTY,
+            )?;
+            require!(
+                !ctx.accounts.stamp_card.is_active,
+                ErrorCode::StampCardActive
+            );
+        }
+
+        let stamp_card = &mut ctx.accounts.stamp_card;
+        stamp_card.holder_count = stamp_card.holder_count.saturating_sub(1);
+
+        msg!(
+            "Stamp card of {} closed with {} stamps",
+            customer_stamps.customer,
+            customer_stamps.stamps
+        );
+
+        emit!(CustomerStampsClosedEvent {
+            merchant_account: customer_stamps.merchant_account,
+            customer: customer_stamps.customer,
+            stamps: customer_stamps.stamps,
+            closed_by: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Close a merchant's stamp card and reclaim its rent
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_LOYALTY` (signer) can manage stamp cards
+    /// - Every customer card must be closed first (see `close_customer_stamps`)
+    pub fn close_stamp_card(ctx: Context<CloseStampCard>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_LOYALTY,
+        )?;
+
+        let stamp_card = &ctx.accounts.stamp_card;
+        require!(stamp_card.holder_count == 0, ErrorCode::StampCardHasHolders);
+        ctx.accounts.merchant_account.has_stamp_card = false;
+
+        msg!(
+            "Stamp card closed for merchant {}",
+            ctx.accounts.merchant_account.merchant
+        );
+
+        emit!(StampCardClosedEvent {
+            stamp_card: stamp_card.key(),
+            merchant_account: stamp_card.merchant_account,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create a coupon for a merchant's promotions
+    ///
+    /// # Arguments
+    /// * `coupon_id` - Merchant-chosen coupon ID (used as PDA seed)
+    /// * `mint` - Mint the coupon applies to (NATIVE_SOL_MINT for SOL)
+    /// * `discount` - Percentage (in bps) or fixed amount off each payment
+    /// * `max_uses` - Total uses across all wallets (0 = unlimited)
+    /// * `per_wallet_limit` - Uses per wallet (0 = unlimited)
+    /// * `valid_from` - Unix timestamp from which the coupon can be used
+    /// * `valid_until` - Optional Unix timestamp after which the coupon is expired
+    /// * `code_hash` - Optional SHA-256 of a secret code customers must present to clip the coupon
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_PROMOTIONS` (signer) can manage coupons
+    /// - PDA seeds: [b"coupon", merchant_account, coupon_id]
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_coupon(
+        ctx: Context<CreateCoupon>,
+        coupon_id: u64,
+        mint: Pubkey,
+        discount: CouponDiscount,
+        max_uses: u32,
+        per_wallet_limit: u32,
+        valid_from: i64,
+        valid_until: Option<i64>,
+        code_hash: Option<[u8; 32]>,
+    ) -> Result<()> {
+        require!(discount.is_valid(), ErrorCode::InvalidCouponDiscount);
+        if let Some(valid_until) = valid_until {
+            require!(valid_until > valid_from, ErrorCode::InvalidCouponWindow);
+        }
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_PROMOTIONS,
+        )?;
+
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.coupon_count = merchant_account
+            .coupon_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+
+        let coupon = &mut ctx.accounts.coupon;
+        coupon.merchant_account = ctx.accounts.merchant_account.key();
+        coupon.coupon_id = coupon_id;
+        coupon.mint = mint;
+        coupon.discount = discount;
+        coupon.max_uses = max_uses;
+        coupon.per_wallet_limit = per_wallet_limit;
+        coupon.uses = 0;
+        coupon.valid_from = valid_from;
+        coupon.valid_until = valid_until;
+        coupon.code_hash = code_hash;
+        coupon.created_at = Clock::get()?.unix_timestamp;
+        coupon.bump = ctx.bumps.coupon;
+
+        msg!(
+            "Coupon {} created for merchant {}: {:?} on {}",
+            coupon_id,
+            ctx.accounts.merchant_account.merchant,
+            discount,
+            mint
+        );
+
+        emit!(CouponCreatedEvent {
+            coupon: coupon.key(),
+            merchant_account: coupon.merchant_account,
+            coupon_id,
+            mint,
+            discount,
+            max_uses,
+            per_wallet_limit,
+            valid_from,
+            valid_until,
+            has_code: code_hash.is_some(),
+            timestamp: coupon.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// End a promotion early and reclaim the coupon's rent
+    ///
+    /// Usage entries are keyed by the coupon address, so a coupon re-created with the same
+    /// ID keeps its per-wallet counts; use a fresh ID for a new promotion.
+    ///
+    /// # Security
+    /// - Only the merchant or a delegate with `PERM_MANAGE_PROMOTIONS` (signer) can manage coupons
+    pub fn close_coupon(ctx: Context<CloseCoupon>) -> Result<()> {
+        ctx.accounts.merchant_account.authorize(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.delegate.as_deref(),
+            PERM_MANAGE_PROMOTIONS,
+        )?;
+
+        // Coupons created before the count existed are not in it
+        let merchant_account = &mut ctx.accounts.merchant_account;
+        merchant_account.coupon_count = merchant_account.coupon_count.saturating_sub(1);
+
+        let coupon = &ctx.accounts.coupon;
+
+        msg!(
+            "Coupon {} closed after {} uses",
+            coupon.coupon_id,
+            coupon.uses
+        );
+
+        emit!(CouponClosedEvent {
+            coupon: coupon.key(),
+            merchant_account: coupon.merchant_account,
+            uses: coupon.uses,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Clip a coupon to the customer's wallet so payments can use it
+    ///
+    /// Can be bundled with the payment in one transaction. Coupons only apply to
+    /// `pay_merchant_sol` and `pay_merchant_token`, not to escrow payments or payment
+    /// requests.
+    ///
+    /// # Arguments
+    /// * `code` - The secret code, for coupons created with a `code_hash`
+    ///
+    /// # Security
+    /// - The coupon must be within its validity window
+    /// - The code must hash (SHA-256) to the coupon's `code_hash`
+    /// - PDA seeds: [b"coupon_usage", coupon, wallet]; the wallet pays rent on first clip
+    pub fn clip_coupon(ctx: Context<ClipCoupon>, code: Option<String>) -> Result<()> {
+        let coupon = &ctx.accounts.coupon;
+        let now = Clock::get()?.unix_timestamp;
+        coupon.check_window(now)?;
+        if let Some(code_hash) = coupon.code_hash {
+            let code = code.ok_or(ErrorCode::InvalidCouponCode)?;
+            require!(
+                hash(code.as_bytes()).to_bytes() == code_hash,
+                ErrorCode::InvalidCouponCode
+            );
+        }
+
+        let usage = &mut ctx.accounts.coupon_usage;
+        if usage.clipped_at == 0 {
+            usage.coupon = coupon.key();
+            usage.wallet = ctx.accounts.wallet.key();
+            usage.uses = 0;
+            usage.clipped_at = now;
+            usage.bump = ctx.bumps.coupon_usage;
+        }
+
+        msg!("Coupon {} clipped by {}", coupon.coupon_id, usage.wallet);
+
+        Ok(())
+    }
+}
+
+/// Global program configuration (singleton PDA)
+#[account]
+pub struct Config {
+    /// Wallet allowed to change the config
+    pub admin: Pubkey, // 32 bytes
+
+    /// Wallet that receives registration and protocol fees
+    pub treasury: Pubkey, // 32 bytes
+
+    /// Registration fee in lamports
+    pub registration_fee: u64, // 8 bytes
+
+    /// Protocol fee on payments in basis points
+    pub protocol_fee_bps: u16, // 2 bytes
+
+    /// Max protocol fee per native SOL payment in lamports (0 = no cap); token
+    /// payments are capped by the mint's global accepted-mint entry instead
+    pub protocol_fee_cap: u64, // 8 bytes
+
+    /// Max distance in metres between a merchant's registered location and its proof
+    pub max_proof_distance_m: u32, // 4 bytes
+
+    /// Seconds after funding during which an escrowed payment can be disputed
+    pub escrow_dispute_window: i64, // 8 bytes
+
+    /// Seconds arbiters have to rule on a dispute before the default outcome applies
+    pub dispute_ruling_window: i64, // 8 bytes
+
+    /// Share of a disputed escrow refunded to the customer when nobody rules (bps)
+    pub dispute_default_refund_bps: u16, // 2 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+impl Config {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // admin
+        + 32 // treasury
+        + 8 // registration_fee
+        + 2 // protocol_fee_bps
+        + 8 // protocol_fee_cap
+        + 4 // max_proof_distance_m
+        + 8 // escrow_dispute_window
+        + 8 // dispute_ruling_window
+        + 2 // dispute_default_refund_bps
+        + 1; // bump
+}
+
+/// Registry entry for a wallet allowed to create location proofs
+#[account]
+pub struct Verifier {
+    /// The verifier's wallet public key
+    pub authority: Pubkey, // 32 bytes
+
+    /// Whether the verifier may currently create proofs (false when suspended)
+    pub is_active: bool, // 1 byte
+
+    /// Unix timestamp when the verifier was added
+    pub added_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (authority) + 1 (is_active) + 8 (added_at) + 1 (bump) = 50 bytes
+
+/// Single-use voucher that makes a wallet's merchant registration free
+#[account]
+pub struct FeeWaiver {
+    /// Wallet the waiver was issued to
+    pub merchant: Pubkey, // 32 bytes
+
+    /// Admin that issued the waiver
+    pub issued_by: Pubkey, // 32 bytes
+
+    /// Unix timestamp when the waiver was issued
+    pub issued_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (merchant) + 32 (issued_by) + 8 (issued_at) + 1 (bump) = 81 bytes
+
+/// Registry entry for a wallet allowed to rule on escrow disputes
+#[account]
+pub struct Arbiter {
+    /// The arbiter's wallet public key
+    pub authority: Pubkey, // 32 bytes
+
+    /// Unix timestamp when the arbiter was added
+    pub added_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (authority) + 8 (added_at) + 1 (bump) = 49 bytes
+
+/// Account struct for storing merchant registration data
+#[account]
+pub struct MerchantAccount {
+    /// The merchant's current wallet public key (signs merchant actions, receives payments)
+    pub merchant: Pubkey, // 32 bytes
+
+    /// Business name (variable length, max 64 bytes)
+    pub business_name: String, // 4 + 64 = 68 bytes
+
+    /// Latitude * 1,000,000 (6 decimal places)
+    pub lat: i64, // 8 bytes
+
+    /// Longitude * 1,000,000 (6 decimal places)
+    pub lng: i64, // 8 bytes
+
+    /// Unix timestamp when registered
+    pub registered_at: i64, // 8 bytes
+
+    /// Whether merchant is active (open for business and not suspended)
+    pub is_active: bool, // 1 byte
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+
+    // Fields below were added after the initial release. New fields must be appended at the
+    // end so older accounts decode as a prefix; see `migrate_merchant_account`.
+    /// Whether the merchant is suspended by the admin
+    pub is_suspended: bool, // 1 byte
+
+    /// Admin-defined suspension reason code (0 when not suspended)
+    pub suspension_reason: u8, // 1 byte
+
+    /// Unix timestamp when the suspension ends (None = indefinite)
+    pub suspension_expires_at: Option<i64>, // 1 + 8 = 9 bytes
+
+    /// Number of unsettled obligations (e.g. escrowed payments) blocking account closure
+    pub open_obligations: u32, // 4 bytes
+
+    /// The wallet used as PDA seed at registration (never changes, even after a transfer)
+    pub registration_key: Pubkey, // 32 bytes
+
+    /// Wallet proposed as the new owner in a pending ownership transfer
+    pub pending_merchant: Option<Pubkey>, // 1 + 32 = 33 bytes
+
+    /// Firebase merchant document ID (max 32 bytes)
+    pub merchant_id: String, // 4 + 32 = 36 bytes
+
+    /// Location proof PDA linked to this merchant, if any
+    pub location_proof: Option<Pubkey>, // 1 + 32 = 33 bytes
+
+    /// Whether the linked proof is within range of the registered location ("verified" badge)
+    pub location_verified: bool, // 1 byte
+
+    /// Protocol fee override in basis points (None = use the config fee)
+    pub fee_bps_override: Option<u16>, // 1 + 2 bytes
+
+    /// Number of registered staff members
+    pub staff_count: u16, // 2 bytes
+
+    /// Number of staff in the tip pool (staff with a non-zero weight)
+    pub tip_pool_size: u8, // 1 byte
+
+    /// Number of delegate keys acting for the merchant
+    pub delegate_count: u16, // 2 bytes
+
+    /// Sum of all current review ratings (average = rating_sum / rating_count)
+    pub rating_sum: u64, // 8 bytes
+
+    /// Number of current reviews
+    pub rating_count: u32, // 4 bytes
+
+    /// How many merchant accounts the registration wallet closed before this one (extra PDA seed)
+    pub generation: u32, // 4 bytes
+
+    /// Number of coupons not yet closed
+    pub coupon_count: u16, // 2 bytes
+
+    /// Number of payment requests not yet cancelled or closed
+    pub payment_request_count: u32, // 4 bytes
+
+    /// Number of mints on the merchant's own accepted-mint list
+    pub accepted_mint_count: u16, // 2 bytes
+
+    /// Whether the merchant has a loyalty program
+    pub has_loyalty_program: bool, // 1 byte
+
+    /// Whether the merchant has a stamp card
+    pub has_stamp_card: bool, // 1 byte
+}
+
+impl MerchantAccount {
+    /// Take a review's rating out of the rating aggregate
+    pub fn remove_rating(&mut self, rating: u8) {
+        self.rating_sum = self.rating_sum.saturating_sub(rating as u64);
+        self.rating_count = self.rating_count.saturating_sub(1);
+    }
+
+    /// Check that `authority` may act for the merchant: the owner always can, a
+    /// delegate needs its entry (seeded by the signer) with `permission` set
+    pub fn authorize(
+        &self,
+        authority: &Pubkey,
+        delegate: Option<&Delegate>,
+        permission: u8,
+    ) -> Result<()> {
+        if *authority == self.merchant {
+            return Ok(());
+        }
+        let delegate = delegate.ok_or(ErrorCode::Unauthorized)?;
+        require!(
+            delegate.permissions & permission != 0,
+            ErrorCode::MissingPermission
+        );
+        Ok(())
+    }
+
+    /// Account size including the discriminator, with room for the longest business name
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant
+        + (4 + MAX_BUSINESS_NAME_LEN) // business_name
+        + 8 // lat
+        + 8 // lng
+        + 8 // registered_at
+        + 1 // is_active
+        + 1 // bump
+        + 1 // is_suspended
+        + 1 // suspension_reason
+        + (1 + 8) // suspension_expires_at
+        + 4 // open_obligations
+        + 32 // registration_key
+        + (1 + 32) // pending_merchant
+        + (4 + MAX_MERCHANT_ID_LEN) // merchant_id
+        + (1 + 32) // location_proof
+        + 1 // location_verified
+        + (1 + 2) // fee_bps_override
+        + 2 // staff_count
+        + 1 // tip_pool_size
+        + 2 // delegate_count
+        + 8 // rating_sum
+        + 4 // rating_count
+        + 4 // generation
+        + 2 // coupon_count
+        + 4 // payment_request_count
+        + 2 // accepted_mint_count
+        + 1 // has_loyalty_program
+        + 1; // has_stamp_card
+
+    /// Protocol fee in basis points that applies to payments to this merchant
+    pub fn fee_bps(&self, config: &Config) -> u16 {
+        self.fee_bps_override.unwrap_or(config.protocol_fee_bps)
+    }
+
+    /// Whether an admin suspension is in force at `now`
+    pub fn is_suspended_at(&self, now: i64) -> bool {
+        match self.suspension_expires_at {
+            Some(expires_at) => self.is_suspended && now < expires_at,
+            None => self.is_suspended,
+        }
+    }
+}
+
+/// Account struct for storing location proof (97 bytes total)
+#[account]
+pub struct LocationProof {
+    /// Latitude * 1,000,000 (6 decimal places)
+    pub lat: i64, // 8 bytes
 
     /// Longitude * 1,000,000 (6 decimal places)
     pub lng: i64, // 8 bytes
 
-    /// Unix timestamp when registered
-    pub registered_at: i64, // 8 bytes
+    /// Unix timestamp when location was verified
+    pub verified_at: i64, // 8 bytes
+
+    /// The verifier that created the proof
+    pub verifier: Pubkey, // 32 bytes
+
+    /// The merchant account PDA this proof belongs to
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 8 (lat) + 8 (lng) + 8 (timestamp) + 32 (verifier) + 32 (merchant_account) + 1 (bump) = 97 bytes
+
+/// On-chain receipt for a merchant payment
+#[account]
+pub struct PaymentReceipt {
+    /// Wallet that paid
+    pub payer: Pubkey, // 32 bytes
+
+    /// Merchant account PDA that was paid
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Merchant wallet that received the funds
+    pub merchant: Pubkey, // 32 bytes
+
+    /// Mint of the payment (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey, // 32 bytes
+
+    /// Amount paid in the mint's base units (lamports for SOL), after any loyalty discount
+    pub amount: u64, // 8 bytes
+
+    /// Coupon and loyalty discount taken off the price
+    pub discount: u64, // 8 bytes
+
+    /// Part of `amount` sent to the treasury as protocol fee
+    pub protocol_fee: u64, // 8 bytes
+
+    /// Part of `amount` sent to the merchant
+    pub merchant_amount: u64, // 8 bytes
+
+    /// Total refunded to the payer so far (never exceeds `amount`)
+    pub refunded_amount: u64, // 8 bytes
+
+    /// Tip paid to staff on top of `amount`
+    pub tip_amount: u64, // 8 bytes
+
+    /// Staff legs of the tip (max 8); the account is sized for the legs it holds
+    pub tips: Vec<TipLeg>, // 4 + 40 bytes per leg
+
+    /// Whether the funds are still held in an escrow
+    pub escrowed: bool, // 1 byte
+
+    /// Whether the receipt has earned a stamp-card stamp
+    pub stamped: bool, // 1 byte
+
+    /// Client-generated payment reference
+    pub reference: [u8; 32], // 32 bytes
+
+    /// Unix timestamp of the payment
+    pub paid_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+impl PaymentReceipt {
+    /// Account size including the discriminator, for a receipt without tip legs
+    pub const LEN: usize = 8 // discriminator
+        + 32 // payer
+        + 32 // merchant_account
+        + 32 // merchant
+        + 32 // mint
+        + 8 // amount
+        + 8 // discount
+        + 8 // protocol_fee
+        + 8 // merchant_amount
+        + 8 // refunded_amount
+        + 8 // tip_amount
+        + 4 // tips (empty)
+        + 1 // escrowed
+        + 1 // stamped
+        + 32 // reference
+        + 8 // paid_at
+        + 1; // bump
+
+    /// Account size for a receipt recording the legs `pay_tips` will produce: none
+    /// without a tip, one for a direct tip, one per pool member for a split tip
+    pub fn space(tip: u64, split_tip: bool, tip_pool_size: u8) -> usize {
+        let legs = if tip == 0 {
+            0
+        } else if split_tip {
+            tip_pool_size as usize
+        } else {
+            1
+        };
+        Self::LEN + legs * TipLeg::LEN
+    }
+}
+
+/// A key allowed to act for a merchant within a permission bitmask
+#[account]
+pub struct Delegate {
+    /// Merchant account PDA the delegate acts for
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Delegate wallet (e.g. a cashier's device key)
+    pub authority: Pubkey, // 32 bytes
+
+    /// Bitmask of PERM_* permissions
+    pub permissions: u8, // 1 byte
+
+    /// Max total a delegate may refund, in `refund_mint` base units
+    pub refund_limit: u64, // 8 bytes
+
+    /// The only mint the delegate may refund (NATIVE_SOL_MINT for SOL)
+    pub refund_mint: Pubkey, // 32 bytes
+
+    /// Running total refunded by the delegate in `refund_mint` base units
+    pub refunded_amount: u64, // 8 bytes
+
+    /// Unix timestamp when the delegate was added
+    pub added_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (merchant_account) + 32 (authority) + 1 (permissions) + 8 (refund_limit) + 32 (refund_mint) + 8 (refunded_amount) + 8 (added_at) + 1 (bump) = 130 bytes
+
+/// One staff member's share of a tip
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TipLeg {
+    /// Staff wallet that received the share
+    pub staff: Pubkey,
+
+    /// Lamports sent to the staff wallet
+    pub amount: u64,
+}
+
+impl TipLeg {
+    /// Serialized size
+    pub const LEN: usize = 32 + 8;
+}
+
+/// A staff member registered under a merchant, eligible for tips
+#[account]
+pub struct Staff {
+    /// Merchant account PDA the staff member works for
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Staff wallet receiving tips
+    pub wallet: Pubkey, // 32 bytes
+
+    /// Share weight in the tip pool (0 = not in the pool, direct tips only)
+    pub weight: u16, // 2 bytes
+
+    /// Unix timestamp when the staff member was added
+    pub added_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (merchant_account) + 32 (wallet) + 2 (weight) + 8 (added_at) + 1 (bump) = 83 bytes
+
+/// Entry in an accepted-mint list (global or per merchant)
+#[account]
+pub struct AcceptedMint {
+    /// List owner: the config PDA (global) or a merchant account PDA
+    pub scope: Pubkey, // 32 bytes
+
+    /// The accepted SPL mint
+    pub mint: Pubkey, // 32 bytes
+
+    /// Max protocol fee per payment in the mint's base units (0 = no cap); only read
+    /// from global entries, merchant entries store 0
+    pub protocol_fee_cap: u64, // 8 bytes
+
+    /// Merchant registration price in the mint's base units (0 = not accepted for registration)
+    pub registration_fee: u64, // 8 bytes
+
+    /// Unix timestamp when the mint was added
+    pub added_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (scope) + 32 (mint) + 8 (protocol_fee_cap) + 8 (registration_fee) + 8 (added_at) + 1 (bump) = 97 bytes
+
+/// Aggregate payment statistics for a merchant, updated only by payment instructions
+#[account]
+pub struct MerchantStats {
+    /// The merchant account these stats belong to
+    pub merchant_account: Pubkey,
+
+    /// Total number of payments received (all mints)
+    pub payment_count: u64,
+
+    /// Number of distinct wallets that have paid this merchant
+    pub unique_payers: u64,
+
+    /// Unix timestamp of the most recent payment (0 = never paid)
+    pub last_payment_at: i64,
+
+    /// Total number of refunds issued (all mints)
+    pub refund_count: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl MerchantStats {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 8 // payment_count
+        + 8 // unique_payers
+        + 8 // last_payment_at
+        + 8 // refund_count
+        + 1; // bump
+
+    /// Count a payment, optionally from a wallet that has never paid before
+    pub fn record(&mut self, new_payer: bool, now: i64) -> Result<()> {
+        self.payment_count = self
+            .payment_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        if new_payer {
+            self.unique_payers = self
+                .unique_payers
+                .checked_add(1)
+                .ok_or(ErrorCode::StatsOverflow)?;
+        }
+        self.last_payment_at = now;
+        Ok(())
+    }
+
+    /// Count a refund
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.refund_count = self
+            .refund_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        Ok(())
+    }
+}
+
+/// Payment volume received by a merchant in a single mint
+#[account]
+pub struct MerchantMintStats {
+    /// The merchant account these stats belong to
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// The payment mint (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey, // 32 bytes
+
+    /// Number of payments received in this mint
+    pub payment_count: u64, // 8 bytes
+
+    /// Total volume received in the mint's base units
+    pub volume: u64, // 8 bytes
+
+    /// Total volume refunded in the mint's base units
+    pub refunded_volume: u64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+impl MerchantMintStats {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 32 // mint
+        + 8 // payment_count
+        + 8 // volume
+        + 8 // refunded_volume
+        + 1; // bump
+
+    /// Add a payment to the volume, initializing the entry on first use
+    pub fn record(
+        &mut self,
+        merchant_account: Pubkey,
+        mint: Pubkey,
+        bump: u8,
+        amount: u64,
+    ) -> Result<()> {
+        if self.payment_count == 0 {
+            self.merchant_account = merchant_account;
+            self.mint = mint;
+            self.bump = bump;
+        }
+        self.payment_count = self
+            .payment_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        self.volume = self
+            .volume
+            .checked_add(amount)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        Ok(())
+    }
+
+    /// Add a refund to the refunded volume
+    pub fn record_refund(&mut self, amount: u64) -> Result<()> {
+        self.refunded_volume = self
+            .refunded_volume
+            .checked_add(amount)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        Ok(())
+    }
+}
+
+/// A customer's payment history with a single merchant
+#[account]
+pub struct CustomerRecord {
+    /// The merchant account that was paid
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// The paying wallet
+    pub payer: Pubkey, // 32 bytes
+
+    /// Number of payments made to this merchant
+    pub payment_count: u64, // 8 bytes
+
+    /// Unix timestamp of the first payment
+    pub first_paid_at: i64, // 8 bytes
+
+    /// Unix timestamp of the most recent payment
+    pub last_paid_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+impl CustomerRecord {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 32 // payer
+        + 8 // payment_count
+        + 8 // first_paid_at
+        + 8 // last_paid_at
+        + 1; // bump
+
+    /// Count a payment; returns true if this is the customer's first payment to the merchant
+    pub fn record(
+        &mut self,
+        merchant_account: Pubkey,
+        payer: Pubkey,
+        bump: u8,
+        now: i64,
+    ) -> Result<bool> {
+        let first_payment = self.payment_count == 0;
+        if first_payment {
+            self.merchant_account = merchant_account;
+            self.payer = payer;
+            self.first_paid_at = now;
+            self.bump = bump;
+        }
+        self.payment_count = self
+            .payment_count
+            .checked_add(1)
+            .ok_or(ErrorCode::StatsOverflow)?;
+        self.last_paid_at = now;
+        Ok(first_payment)
+    }
+}
+
+/// Lifecycle of a payment request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentRequestStatus {
+    /// Can be paid
+    Open,
+    /// Settled by a payment (single-use requests only)
+    Paid,
+    /// Past its expiry without being paid
+    Expired,
+    /// Cancelled by the merchant
+    Cancelled,
+}
+
+/// A merchant-issued request for an exact payment (invoice / QR bill)
+#[account]
+pub struct PaymentRequest {
+    /// Merchant account PDA being paid
+    pub merchant_account: Pubkey,
+
+    /// Merchant-chosen request ID
+    pub request_id: u64,
+
+    /// Mint to be paid in (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey,
+
+    /// Exact amount in the mint's base units
+    pub amount: u64,
+
+    /// Memo shown to the customer
+    pub memo: String,
+
+    /// Unix timestamp after which the request can no longer be paid
+    pub expires_at: Option<i64>,
+
+    /// Whether the request stays open after a payment
+    pub reusable: bool,
+
+    /// Current lifecycle state
+    pub status: PaymentRequestStatus,
+
+    /// Number of payments made against this request
+    pub payment_count: u32,
+
+    /// Receipt of the most recent payment
+    pub last_receipt: Option<Pubkey>,
+
+    /// Unix timestamp when the request was created
+    pub created_at: i64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PaymentRequest {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 8 // request_id
+        + 32 // mint
+        + 8 // amount
+        + (4 + MAX_MEMO_LEN) // memo
+        + (1 + 8) // expires_at
+        + 1 // reusable
+        + 1 // status
+        + 4 // payment_count
+        + (1 + 32) // last_receipt
+        + 8 // created_at
+        + 1; // bump
+}
+
+/// Lifecycle of an escrowed payment while its funds are held
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EscrowStatus {
+    /// Funds held; can be confirmed, claimed after the window, or disputed
+    Held,
+    /// Frozen until the dispute is ruled on, times out, or the customer confirms
+    Disputed,
+}
+
+/// An escrowed payment; holds the lamports itself or owns the token vault
+#[account]
+pub struct Escrow {
+    /// Receipt of the escrowed payment
+    pub receipt: Pubkey,
+
+    /// Customer who funded the escrow
+    pub payer: Pubkey,
+
+    /// Merchant account PDA being paid
+    pub merchant_account: Pubkey,
+
+    /// Mint of the escrowed funds (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey,
+
+    /// Escrowed amount in the mint's base units
+    pub amount: u64,
+
+    /// Part of `amount` owed to the treasury on release
+    pub protocol_fee: u64,
+
+    /// Part of `amount` owed to the merchant on release
+    pub merchant_amount: u64,
+
+    /// Unix timestamp when the escrow was funded
+    pub funded_at: i64,
+
+    /// Unix timestamp after which the merchant can claim (end of the dispute window)
+    pub release_after: i64,
+
+    /// Current escrow state
+    pub status: EscrowStatus,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Escrow {
+    /// Split the escrow into (to_merchant, to_treasury, to_payer) when `payer_share_bps`
+    /// is refunded; the protocol fee is charged pro rata on the released part
+    pub fn split(&self, payer_share_bps: u16) -> (u64, u64, u64) {
+        let to_payer =
+            (self.amount as u128 * payer_share_bps as u128 / MAX_BASIS_POINTS as u128) as u64;
+        let released = self.amount - to_payer;
+        let to_treasury = if self.amount > 0 {
+            (self.protocol_fee as u128 * released as u128 / self.amount as u128) as u64
+        } else {
+            0
+        };
+        (released - to_treasury, to_treasury, to_payer)
+    }
+
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // receipt
+        + 32 // payer
+        + 32 // merchant_account
+        + 32 // mint
+        + 8 // amount
+        + 8 // protocol_fee
+        + 8 // merchant_amount
+        + 8 // funded_at
+        + 8 // release_after
+        + 1 // status
+        + 1; // bump
+}
+
+/// A dispute over an escrowed payment, awaiting an arbiter's ruling
+#[account]
+pub struct Dispute {
+    /// Escrow under dispute
+    pub escrow: Pubkey,
+
+    /// Receipt of the disputed payment
+    pub receipt: Pubkey,
+
+    /// Merchant account PDA that was paid
+    pub merchant_account: Pubkey,
+
+    /// Customer who funded the escrow
+    pub customer: Pubkey,
+
+    /// Wallet that opened the dispute (the customer, merchant or a delegate)
+    pub opened_by: Pubkey,
+
+    /// Client-defined reason for the dispute (non-zero)
+    pub reason_code: u8,
+
+    /// Evidence hashes submitted by the customer (max 4)
+    pub customer_evidence: Vec<[u8; 32]>,
+
+    /// Evidence hashes submitted by the merchant (max 4)
+    pub merchant_evidence: Vec<[u8; 32]>,
+
+    /// Unix timestamp when the dispute was opened
+    pub opened_at: i64,
+
+    /// Unix timestamp after which the default outcome can be applied
+    pub ruling_deadline: i64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Dispute {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // escrow
+        + 32 // receipt
+        + 32 // merchant_account
+        + 32 // customer
+        + 32 // opened_by
+        + 1 // reason_code
+        + (4 + 32 * MAX_DISPUTE_EVIDENCE) // customer_evidence
+        + (4 + 32 * MAX_DISPUTE_EVIDENCE) // merchant_evidence
+        + 8 // opened_at
+        + 8 // ruling_deadline
+        + 1; // bump
+}
+
+/// A customer's review of a merchant, backed by a payment receipt
+#[account]
+pub struct Review {
+    /// Merchant account PDA being reviewed
+    pub merchant_account: Pubkey,
+
+    /// Wallet that wrote the review
+    pub reviewer: Pubkey,
+
+    /// Receipt proving the reviewer paid the merchant
+    pub receipt: Pubkey,
+
+    /// Rating from 1 to 5
+    pub rating: u8,
+
+    /// Hash of the review text stored off-chain
+    pub content_hash: [u8; 32],
+
+    /// Where the review text is stored (max 200 bytes)
+    pub uri: String,
+
+    /// Unix timestamp when the review was submitted
+    pub created_at: i64,
+
+    /// Unix timestamp of the last edit
+    pub updated_at: i64,
+
+    /// Hash of the merchant's reply stored off-chain, if any
+    pub reply_hash: Option<[u8; 32]>,
+
+    /// Unix timestamp of the merchant's latest reply (0 = no reply)
+    pub replied_at: i64,
+
+    /// Whether an admin hid the review (excluded from the rating aggregate)
+    pub is_hidden: bool,
+
+    /// Moderation reason code (0 when visible)
+    pub hide_reason: u8,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Review {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 32 // reviewer
+        + 32 // receipt
+        + 1 // rating
+        + 32 // content_hash
+        + (4 + MAX_REVIEW_URI_LEN) // uri
+        + 8 // created_at
+        + 8 // updated_at
+        + (1 + 32) // reply_hash
+        + 8 // replied_at
+        + 1 // is_hidden
+        + 1 // hide_reason
+        + 1; // bump
+}
+
+/// A merchant's loyalty points program
+#[account]
+pub struct LoyaltyProgram {
+    /// Merchant account PDA running the program
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Mint that earns and redeems points (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey, // 32 bytes
+
+    /// Points credited per 1,000,000 base units paid
+    pub earn_rate: u64, // 8 bytes
+
+    /// Discount in base units that one point is worth
+    pub redeem_value: u64, // 8 bytes
+
+    /// Seconds without activity after which a balance expires (0 = never)
+    pub points_ttl: i64, // 8 bytes
+
+    /// Whether payments earn points and points can be redeemed (pending discounts apply either way)
+    pub is_active: bool, // 1 byte
+
+    /// Unix timestamp when the program was created
+    pub created_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+
+    /// Number of customer balances not yet closed
+    pub balance_count: u32, // 4 bytes
+}
+
+impl LoyaltyProgram {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 32 // mint
+        + 8 // earn_rate
+        + 8 // redeem_value
+        + 8 // points_ttl
+        + 1 // is_active
+        + 8 // created_at
+        + 1 // bump
+        + 4; // balance_count
+
+    /// Points earned by paying `amount` base units
+    pub fn points_for(&self, amount: u64) -> u64 {
+        (amount as u128 * self.earn_rate as u128 / LOYALTY_EARN_RATE_UNIT as u128)
+            .min(u64::MAX as u128) as u64
+    }
+}
+
+/// A customer's loyalty points with a single merchant
+#[account]
+pub struct LoyaltyBalance {
+    /// Merchant account PDA whose program issued the points
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Customer wallet
+    pub customer: Pubkey, // 32 bytes
+
+    /// Spendable points
+    pub points: u64, // 8 bytes
+
+    /// Points earned over the balance's lifetime
+    pub lifetime_points: u64, // 8 bytes
+
+    /// Redeemed discount not yet applied to a payment, in base units
+    pub pending_discount: u64, // 8 bytes
+
+    /// Unix timestamp of the last earn or redemption
+    pub last_activity_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+impl LoyaltyBalance {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 32 // customer
+        + 8 // points
+        + 8 // lifetime_points
+        + 8 // pending_discount
+        + 8 // last_activity_at
+        + 1; // bump
+
+    /// Forfeit all points if the balance has been idle for the program's TTL
+    pub fn expire(&mut self, program: &LoyaltyProgram, now: i64) {
+        if program.points_ttl == 0 || self.points == 0 {
+            return;
+        }
+        if now.saturating_sub(self.last_activity_at) < program.points_ttl {
+            return;
+        }
+
+        emit!(LoyaltyPointsExpiredEvent {
+            merchant_account: self.merchant_account,
+            customer: self.customer,
+            points: self.points,
+            timestamp: now,
+        });
+        self.points = 0;
+    }
+}
+
+/// A merchant's stamp card template ("buy N, get one free")
+#[account]
+pub struct StampCard {
+    /// Merchant account PDA offering the card
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Mint a payment must be in to earn a stamp (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey, // 32 bytes
+
+    /// Stamps needed to claim the reward
+    pub stamps_required: u8, // 1 byte
+
+    /// Hash of the reward description stored off-chain
+    pub reward_hash: [u8; 32], // 32 bytes
+
+    /// Minimum net payment in base units that earns a stamp
+    pub min_purchase: u64, // 8 bytes
+
+    /// Whether payments can still earn stamps
+    pub is_active: bool, // 1 byte
+
+    /// Unix timestamp when the card was set up (older receipts don't qualify)
+    pub created_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+
+    /// Number of customer stamp cards not yet closed
+    pub holder_count: u32, // 4 bytes
+}
+
+// 8 (discriminator) + 32 (merchant_account) + 32 (mint) + 1 (stamps_required) + 32 (reward_hash) + 8 (min_purchase) + 1 (is_active) + 8 (created_at) + 1 (bump) + 4 (holder_count) = 127 bytes
+
+/// A customer's stamp card with a single merchant
+#[account]
+pub struct CustomerStamps {
+    /// Merchant account PDA whose card this is
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// Customer wallet
+    pub customer: Pubkey, // 32 bytes
+
+    /// Stamps collected on the current card
+    pub stamps: u8, // 1 byte
+
+    /// Rewards claimed so far
+    pub rewards_claimed: u32, // 4 bytes
+
+    /// Unix timestamp of the last stamp
+    pub last_stamped_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (merchant_account) + 32 (customer) + 1 (stamps) + 4 (rewards_claimed) + 8 (last_stamped_at) + 1 (bump) = 86 bytes
+
+/// How much a coupon takes off a payment
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CouponDiscount {
+    /// A share of the amount, in basis points
+    Percentage { bps: u16 },
+    /// A fixed amount in the mint's base units (capped at the payment amount)
+    Fixed { amount: u64 },
+}
+
+impl CouponDiscount {
+    /// Whether the discount takes something off without exceeding 100%
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            CouponDiscount::Percentage { bps } => bps > 0 && bps <= MAX_BASIS_POINTS,
+            CouponDiscount::Fixed { amount } => amount > 0,
+        }
+    }
+
+    /// Discount on a payment of `amount` base units
+    pub fn apply(&self, amount: u64) -> u64 {
+        match *self {
+            CouponDiscount::Percentage { bps } => {
+                (amount as u128 * bps as u128 / MAX_BASIS_POINTS as u128) as u64
+            }
+            CouponDiscount::Fixed { amount: fixed } => fixed.min(amount),
+        }
+    }
+}
+
+/// A merchant's promotional coupon
+#[account]
+pub struct Coupon {
+    /// Merchant account PDA running the promotion
+    pub merchant_account: Pubkey,
+
+    /// Merchant-chosen coupon ID
+    pub coupon_id: u64,
+
+    /// Mint the coupon applies to (NATIVE_SOL_MINT for SOL)
+    pub mint: Pubkey,
+
+    /// Percentage or fixed discount
+    pub discount: CouponDiscount,
+
+    /// Total uses across all wallets (0 = unlimited)
+    pub max_uses: u32,
+
+    /// Uses per wallet (0 = unlimited)
+    pub per_wallet_limit: u32,
+
+    /// Uses so far
+    pub uses: u32,
+
+    /// Unix timestamp from which the coupon can be used
+    pub valid_from: i64,
+
+    /// Unix timestamp after which the coupon is expired, if any
+    pub valid_until: Option<i64>,
+
+    /// SHA-256 of the secret code needed to clip the coupon, if any
+    pub code_hash: Option<[u8; 32]>,
+
+    /// Unix timestamp when the coupon was created
+    pub created_at: i64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Coupon {
+    /// Account size including the discriminator
+    pub const LEN: usize = 8 // discriminator
+        + 32 // merchant_account
+        + 8 // coupon_id
+        + 32 // mint
+        + (1 + 8) // discount
+        + 4 // max_uses
+        + 4 // per_wallet_limit
+        + 4 // uses
+        + 8 // valid_from
+        + (1 + 8) // valid_until
+        + (1 + 32) // code_hash
+        + 8 // created_at
+        + 1; // bump
+
+    /// Check that the coupon is within its validity window at `now`
+    pub fn check_window(&self, now: i64) -> Result<()> {
+        require!(now >= self.valid_from, ErrorCode::CouponNotStarted);
+        if let Some(valid_until) = self.valid_until {
+            require!(now < valid_until, ErrorCode::CouponExpired);
+        }
+        Ok(())
+    }
+}
+
+/// A wallet's clipped coupon and how often it has been used
+#[account]
+pub struct CouponUsage {
+    /// Coupon PDA
+    pub coupon: Pubkey, // 32 bytes
+
+    /// Wallet that clipped the coupon
+    pub wallet: Pubkey, // 32 bytes
+
+    /// Payments this wallet made with the coupon
+    pub uses: u32, // 4 bytes
+
+    /// Unix timestamp when the coupon was first clipped
+    pub clipped_at: i64, // 8 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (coupon) + 32 (wallet) + 4 (uses) + 8 (clipped_at) + 1 (bump) = 85 bytes
+
+/// Number of merchant accounts a registration wallet has closed (never closed itself)
+#[account]
+pub struct RegistrationCounter {
+    /// Closed registrations so far; the next merchant account uses this as its generation
+    pub count: u32, // 4 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 4 (count) + 1 (bump) = 13 bytes
+
+/// Claim on a `merchant_id`, held by one merchant account until it closes
+#[account]
+pub struct MerchantIdReservation {
+    /// The merchant account registered with this ID
+    pub merchant_account: Pubkey, // 32 bytes
+
+    /// PDA bump seed
+    pub bump: u8, // 1 byte
+}
+
+// 8 (discriminator) + 32 (merchant_account) + 1 (bump) = 41 bytes
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    /// The global config PDA
+    #[account(
+        init,
+        payer = admin,
+        space = Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The program upgrade authority, becomes the config admin
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// This program, used to look up its program data account
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::NearmeContract>,
+
+    /// The program data account holding the upgrade authority
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// The global config PDA
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The current config admin
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(verifier: Pubkey)]
+pub struct AddVerifier<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The verifier registry entry PDA
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 1 + 8 + 1, // discriminator + authority + is_active + added_at + bump
+        seeds = [b"verifier", verifier.as_ref()],
+        bump
+    )]
+    pub verifier_account: Account<'info, Verifier>,
+
+    /// The config admin (pays rent for the registry entry)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveVerifier<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The verifier registry entry PDA to close
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"verifier", verifier_account.authority.as_ref()],
+        bump = verifier_account.bump
+    )]
+    pub verifier_account: Account<'info, Verifier>,
+
+    /// The config admin (receives the reclaimed rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SuspendVerifier<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The verifier registry entry PDA
+    #[account(
+        mut,
+        seeds = [b"verifier", verifier_account.authority.as_ref()],
+        bump = verifier_account.bump
+    )]
+    pub verifier_account: Account<'info, Verifier>,
+
+    /// The config admin
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(merchant_id: String, business_name: String, lat: i64, lng: i64)]
+pub struct RegisterMerchant<'info> {
+    /// Registration counter for the merchant wallet, created on first registration.
+    /// It only advances on `close_merchant`, so a live merchant blocks re-registration.
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        space = 8 + 4 + 1, // discriminator + count + bump
+        seeds = [b"registrations", merchant.key().as_ref()],
+        bump
+    )]
+    pub registrations: Account<'info, RegistrationCounter>,
+
+    /// The merchant account PDA
+    #[account(
+        init,
+        payer = merchant,
+        space = MerchantAccount::LEN,
+        seeds = [
+            b"merchant",
+            merchant.key().as_ref(),
+            generation_seed(registrations.count).as_ref()
+        ],
+        bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// Reservation of `merchant_id` for this merchant account
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + 32 + 1, // discriminator + merchant_account + bump
+        seeds = [b"merchant_id", merchant_id.as_bytes()],
+        bump
+    )]
+    pub merchant_id_reservation: Account<'info, MerchantIdReservation>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        init,
+        payer = merchant,
+        space = MerchantStats::LEN,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump
+    )]
+    pub merchant_stats: Account<'info, MerchantStats>,
+
+    /// The merchant wallet (pays rent + registration fee)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    /// The global config PDA (fee and treasury settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Treasury account that receives registration fees
+    /// CHECK: This is safe because we only transfer SOL to it (or own its fee token account) and it must match the config treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// Fee waiver issued for this wallet, consumed on registration
+    #[account(
+        mut,
+        close = merchant,
+        seeds = [b"fee_waiver", merchant.key().as_ref()],
+        bump = fee_waiver.bump
+    )]
+    pub fee_waiver: Option<Box<Account<'info, FeeWaiver>>>,
+
+    /// Mint to pay the registration fee in (token fees only)
+    #[account(mint::token_program = token_program)]
+    pub fee_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Global accepted-mint entry holding the registration price (token fees only)
+    #[account(
+        seeds = [b"accepted_mint", config.key().as_ref(), fee_accepted_mint.mint.as_ref()],
+        bump = fee_accepted_mint.bump
+    )]
+    pub fee_accepted_mint: Option<Box<Account<'info, AcceptedMint>>>,
+
+    /// The merchant's token account paying the fee (token fees only)
+    #[account(
+        mut,
+        token::mint = fee_mint,
+        token::authority = merchant,
+        token::token_program = token_program
+    )]
+    pub merchant_fee_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The treasury's associated token account, created if missing (token fees only)
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        associated_token::mint = fee_mint,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_fee_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMerchantProfile<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant wallet or a delegate with the profile permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+#[instruction(merchant_id: String)]
+pub struct MigrateMerchantAccount<'info> {
+    /// The merchant account PDA in an older layout
+    /// CHECK: Decoded, validated and rewritten by the handler; must be owned by this program
+    #[account(mut, owner = crate::ID)]
+    pub merchant_account: UncheckedAccount<'info>,
+
+    /// Reservation of `merchant_id`, created if the account predates merchant IDs
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        space = 8 + 32 + 1, // discriminator + merchant_account + bump
+        seeds = [b"merchant_id", merchant_id.as_bytes()],
+        bump
+    )]
+    pub merchant_id_reservation: Account<'info, MerchantIdReservation>,
+
+    /// The merchant's payment statistics PDA, created if the account predates it
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        space = MerchantStats::LEN,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump
+    )]
+    pub merchant_stats: Account<'info, MerchantStats>,
+
+    /// The merchant wallet (pays the extra rent)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantStatus<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant wallet or a delegate with the profile permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMerchant<'info> {
+    /// The merchant account PDA to close
+    #[account(
+        mut,
+        close = merchant,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant's payment statistics PDA, closed with the merchant
+    #[account(
+        mut,
+        close = merchant,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Account<'info, MerchantStats>,
+
+    /// The linked location proof PDA, required if the merchant has one
+    #[account(mut)]
+    pub location_proof: Option<Account<'info, LocationProof>>,
+
+    /// The merchant ID reservation, required unless the merchant has no ID
+    #[account(
+        mut,
+        seeds = [b"merchant_id", merchant_account.merchant_id.as_bytes()],
+        bump = merchant_id_reservation.bump,
+        has_one = merchant_account @ ErrorCode::MerchantIdMismatch
+    )]
+    pub merchant_id_reservation: Option<Account<'info, MerchantIdReservation>>,
+
+    /// The merchant's refund vault, emptied into the merchant wallet
+    /// CHECK: System-owned PDA that only holds lamports and signs with its seeds
+    #[account(mut, seeds = [b"refund_vault", merchant_account.key().as_ref()], bump)]
+    pub refund_vault: UncheckedAccount<'info>,
+
+    /// Registration counter of the registration wallet, advanced past this generation.
+    /// Created here for merchants registered before counters existed.
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        space = 8 + 4 + 1, // discriminator + count + bump
+        seeds = [b"registrations", merchant_account.registration_key.as_ref()],
+        bump
+    )]
+    pub registrations: Account<'info, RegistrationCounter>,
+
+    /// The merchant wallet (receives the reclaimed rent)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeMerchantTransfer<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The current merchant wallet
+    pub merchant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptMerchantTransfer<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.pending_merchant == Some(new_merchant.key()) @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The proposed merchant wallet
+    pub new_merchant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMerchantTransfer<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The current merchant or the proposed wallet
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SuspendMerchant<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The config admin
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(lat: i64, lng: i64, merchant_id: String)]
+pub struct CreateLocationProof<'info> {
+    /// The global config PDA (proximity radius)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The location proof PDA account
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 8 + 8 + 8 + 32 + 32 + 1, // discriminator + lat + lng + timestamp + verifier + merchant_account + bump
+        seeds = [b"proof", merchant_id.as_bytes()],
+        bump
+    )]
+    pub proof: Account<'info, LocationProof>,
+
+    /// The registered merchant the proof is created for
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.merchant_id == merchant_id @ ErrorCode::MerchantIdMismatch
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The reservation proving `merchant_id` belongs to the merchant account
+    #[account(
+        seeds = [b"merchant_id", merchant_id.as_bytes()],
+        bump = merchant_id_reservation.bump,
+        has_one = merchant_account @ ErrorCode::MerchantIdMismatch
+    )]
+    pub merchant_id_reservation: Account<'info, MerchantIdReservation>,
+
+    /// The verifier registry entry of the signer
+    #[account(
+        seeds = [b"verifier", payer.key().as_ref()],
+        bump = verifier.bump,
+        constraint = verifier.is_active @ ErrorCode::VerifierSuspended
+    )]
+    pub verifier: Account<'info, Verifier>,
+
+    /// The verifier server keypair that pays for the account creation
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(merchant_id: String)]
+pub struct CloseLocationProof<'info> {
+    /// The global config PDA (admin lookup)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The location proof PDA account to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"proof", merchant_id.as_bytes()],
+        bump = proof.bump,
+        constraint = authority.key() == proof.verifier
+            || authority.key() == config.admin @ ErrorCode::Unauthorized
+    )]
+    pub proof: Account<'info, LocationProof>,
+
+    /// The merchant account the proof is linked to
+    #[account(
+        mut,
+        address = proof.merchant_account @ ErrorCode::LocationProofMismatch
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The authority closing the account (original verifier or config admin)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, reference: [u8; 32], tip: u64, split_tip: bool)]
+pub struct PayMerchantSol<'info> {
+    /// The global config PDA (protocol fee settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The merchant account PDA being paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant wallet receiving the lamports
+    /// CHECK: Must match the merchant account's current wallet; only receives SOL
+    #[account(mut, address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// Treasury account that receives the protocol fee
+    /// CHECK: This is safe because we only transfer SOL to it and it must match the config treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// The payment receipt PDA
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentReceipt::space(tip, split_tip, merchant_account.tip_pool_size),
+        seeds = [b"receipt", merchant_account.key().as_ref(), reference.as_ref()],
+        bump
+    )]
+    pub receipt: Account<'info, PaymentReceipt>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for this merchant
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MerchantMintStats::LEN,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), NATIVE_SOL_MINT.as_ref()],
+        bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// Per-customer payment record for this merchant (tracks unique payers)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CustomerRecord::LEN,
+        seeds = [b"customer", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub customer_record: Box<Account<'info, CustomerRecord>>,
+
+    /// The merchant's loyalty program, if the customer wants to earn or redeem points
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Option<Box<Account<'info, LoyaltyProgram>>>,
+
+    /// The customer's loyalty balance, created on first use (payer pays rent)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = LoyaltyBalance::LEN,
+        seeds = [b"loyalty", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub loyalty_balance: Option<Box<Account<'info, LoyaltyBalance>>>,
+
+    /// A coupon to apply to the payment
+    #[account(
+        mut,
+        seeds = [
+            b"coupon",
+            merchant_account.key().as_ref(),
+            coupon.coupon_id.to_le_bytes().as_ref()
+        ],
+        bump = coupon.bump
+    )]
+    pub coupon: Option<Box<Account<'info, Coupon>>>,
+
+    /// The payer's usage entry for the coupon (created by `clip_coupon`)
+    #[account(
+        mut,
+        seeds = [b"coupon_usage", coupon_usage.coupon.as_ref(), payer.key().as_ref()],
+        bump = coupon_usage.bump
+    )]
+    pub coupon_usage: Option<Box<Account<'info, CouponUsage>>>,
+
+    /// The customer wallet paying the merchant and the receipt rent
+    #[account(
+        mut,
+        constraint = payer.key() != merchant_account.merchant @ ErrorCode::SelfPayment
+    )]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddAcceptedMint<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The mint to accept
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The global accepted-mint entry PDA
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1, // discriminator + scope + mint + protocol_fee_cap + registration_fee + added_at + bump
+        seeds = [b"accepted_mint", config.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    /// The config admin (pays rent for the entry)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAcceptedMint<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The global accepted-mint entry PDA to close
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"accepted_mint", config.key().as_ref(), accepted_mint.mint.as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    /// The config admin (receives the reclaimed rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddMerchantAcceptedMint<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The mint to accept
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// The merchant's accepted-mint entry PDA
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1, // discriminator + scope + mint + protocol_fee_cap + registration_fee + added_at + bump
+        seeds = [b"accepted_mint", merchant_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    /// The merchant wallet or a delegate with the mints permission (pays rent for the entry)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveMerchantAcceptedMint<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant's accepted-mint entry PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"accepted_mint", merchant_account.key().as_ref(), accepted_mint.mint.as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    /// The merchant wallet or a delegate with the mints permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, reference: [u8; 32])]
+pub struct PayMerchantToken<'info> {
+    /// The global config PDA (global accepted-mint scope and protocol fee settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// The merchant account PDA being paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant wallet owning the receiving token account
+    /// CHECK: Must match the merchant account's current wallet; only used as token account authority
+    #[account(address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The payment mint (Token or Token-2022)
+    #[account(mint::token_program = token_program)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Global or merchant accepted-mint entry for this mint
+    #[account(
+        seeds = [b"accepted_mint", accepted_mint.scope.as_ref(), mint.key().as_ref()],
+        bump = accepted_mint.bump,
+        constraint = accepted_mint.scope == config.key()
+            || accepted_mint.scope == merchant_account.key() @ ErrorCode::MintNotAccepted
+    )]
+    pub accepted_mint: Box<Account<'info, AcceptedMint>>,
+
+    /// The mint's global accepted-mint entry PDA, whose cap limits the protocol fee
+    /// CHECK: Address derived from the config and mint; read only if the entry exists
+    #[account(seeds = [b"accepted_mint", config.key().as_ref(), mint.key().as_ref()], bump)]
+    pub global_accepted_mint: UncheckedAccount<'info>,
+
+    /// The payer's token account
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = payer,
+        token::token_program = token_program
+    )]
+    pub payer_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The merchant's associated token account (created if missing)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = merchant,
+        associated_token::token_program = token_program
+    )]
+    pub merchant_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Treasury wallet owning the fee token account
+    /// CHECK: Must match the config treasury; only used as token account authority
+    #[account(address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// The treasury's associated token account (created if missing)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The payment receipt PDA
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentReceipt::LEN,
+        seeds = [b"receipt", merchant_account.key().as_ref(), reference.as_ref()],
+        bump
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for this merchant
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MerchantMintStats::LEN,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// Per-customer payment record for this merchant (tracks unique payers)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CustomerRecord::LEN,
+        seeds = [b"customer", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub customer_record: Box<Account<'info, CustomerRecord>>,
+
+    /// The merchant's loyalty program, if the customer wants to earn or redeem points
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Option<Box<Account<'info, LoyaltyProgram>>>,
+
+    /// The customer's loyalty balance, created on first use (payer pays rent)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = LoyaltyBalance::LEN,
+        seeds = [b"loyalty", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub loyalty_balance: Option<Box<Account<'info, LoyaltyBalance>>>,
+
+    /// A coupon to apply to the payment
+    #[account(
+        mut,
+        seeds = [
+            b"coupon",
+            merchant_account.key().as_ref(),
+            coupon.coupon_id.to_le_bytes().as_ref()
+        ],
+        bump = coupon.bump
+    )]
+    pub coupon: Option<Box<Account<'info, Coupon>>>,
+
+    /// The payer's usage entry for the coupon (created by `clip_coupon`)
+    #[account(
+        mut,
+        seeds = [b"coupon_usage", coupon_usage.coupon.as_ref(), payer.key().as_ref()],
+        bump = coupon_usage.bump
+    )]
+    pub coupon_usage: Option<Box<Account<'info, CouponUsage>>>,
+
+    /// The customer wallet paying the merchant and any rent
+    #[account(
+        mut,
+        constraint = payer.key() != merchant_account.merchant @ ErrorCode::SelfPayment
+    )]
+    pub payer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantFeeOverride<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The config admin
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct CreatePaymentRequest<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The payment request PDA
+    #[account(
+        init,
+        payer = authority,
+        space = PaymentRequest::LEN,
+        seeds = [
+            b"payment_request",
+            merchant_account.key().as_ref(),
+            request_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+
+    /// The merchant wallet or a delegate with the invoice permission (pays rent for the request)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(reference: [u8; 32])]
+pub struct PayPaymentRequest<'info> {
+    /// The global config PDA (global accepted-mint scope and protocol fee settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// The merchant account PDA being paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant wallet receiving the funds
+    /// CHECK: Must match the merchant account's current wallet; receives SOL or owns the token account
+    #[account(mut, address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// Treasury wallet receiving the protocol fee
+    /// CHECK: Must match the config treasury; receives SOL or owns the fee token account
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// The payment request being paid
+    #[account(
+        mut,
+        seeds = [
+            b"payment_request",
+            merchant_account.key().as_ref(),
+            payment_request.request_id.to_le_bytes().as_ref()
+        ],
+        bump = payment_request.bump
+    )]
+    pub payment_request: Box<Account<'info, PaymentRequest>>,
+
+    /// The payment receipt PDA
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentReceipt::LEN,
+        seeds = [b"receipt", merchant_account.key().as_ref(), reference.as_ref()],
+        bump
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for this merchant
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MerchantMintStats::LEN,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), payment_request.mint.as_ref()],
+        bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// Per-customer payment record for this merchant (tracks unique payers)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CustomerRecord::LEN,
+        seeds = [b"customer", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub customer_record: Box<Account<'info, CustomerRecord>>,
+
+    /// The merchant's loyalty program, if the customer wants to earn or redeem points
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Option<Box<Account<'info, LoyaltyProgram>>>,
+
+    /// The customer's loyalty balance, created on first use (payer pays rent)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = LoyaltyBalance::LEN,
+        seeds = [b"loyalty", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub loyalty_balance: Option<Box<Account<'info, LoyaltyBalance>>>,
+
+    /// The payment mint (token requests only)
+    #[account(mint::token_program = token_program)]
+    pub mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// Global or merchant accepted-mint entry for the request mint (token requests only)
+    #[account(
+        seeds = [b"accepted_mint", accepted_mint.scope.as_ref(), payment_request.mint.as_ref()],
+        bump = accepted_mint.bump,
+        constraint = accepted_mint.scope == config.key()
+            || accepted_mint.scope == merchant_account.key() @ ErrorCode::MintNotAccepted
+    )]
+    pub accepted_mint: Option<Box<Account<'info, AcceptedMint>>>,
+
+    /// The mint's global accepted-mint entry PDA, whose cap limits the protocol fee
+    /// (token requests only)
+    /// CHECK: Address derived from the config and request mint; read only if the entry exists
+    #[account(
+        seeds = [b"accepted_mint", config.key().as_ref(), payment_request.mint.as_ref()],
+        bump
+    )]
+    pub global_accepted_mint: Option<UncheckedAccount<'info>>,
+
+    /// The payer's token account (token requests only)
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = payer,
+        token::token_program = token_program
+    )]
+    pub payer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant's associated token account, created if missing (token requests only)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = merchant,
+        associated_token::token_program = token_program
+    )]
+    pub merchant_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The treasury's associated token account, created if missing (token requests only)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The customer wallet paying the request and any rent
+    #[account(
+        mut,
+        constraint = payer.key() != merchant_account.merchant @ ErrorCode::SelfPayment
+    )]
+    pub payer: Signer<'info>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpirePaymentRequest<'info> {
+    /// The payment request to mark as expired
+    #[account(mut)]
+    pub payment_request: Account<'info, PaymentRequest>,
+}
+
+#[derive(Accounts)]
+pub struct EndPaymentRequest<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The payment request PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"payment_request",
+            merchant_account.key().as_ref(),
+            payment_request.request_id.to_le_bytes().as_ref()
+        ],
+        bump = payment_request.bump
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+
+    /// The merchant wallet or a delegate with the invoice permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct RefundPayment<'info> {
+    /// The merchant account PDA that was paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The receipt of the payment being refunded
+    #[account(
+        mut,
+        seeds = [b"receipt", merchant_account.key().as_ref(), receipt.reference.as_ref()],
+        bump = receipt.bump
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for the receipt mint
+    #[account(
+        mut,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), receipt.mint.as_ref()],
+        bump = mint_stats.bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// The original payer receiving the refund
+    /// CHECK: Must match the payer recorded on the receipt; receives SOL or owns the token account
+    #[account(mut, address = receipt.payer @ ErrorCode::InvalidRefundRecipient)]
+    pub payer: UncheckedAccount<'info>,
+
+    /// The payment mint (token receipts only)
+    #[account(mint::token_program = token_program)]
+    pub mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// The merchant's token account funding the refund (owner token refunds only)
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = authority,
+        token::token_program = token_program
+    )]
+    pub merchant_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant's refund vault (delegate refunds only)
+    /// CHECK: System-owned PDA that only holds lamports and signs with its seeds
+    #[account(mut, seeds = [b"refund_vault", merchant_account.key().as_ref()], bump)]
+    pub refund_vault: Option<UncheckedAccount<'info>>,
+
+    /// The refund vault's token account funding the refund (delegate token refunds only)
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = refund_vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The payer's associated token account, created if missing (token receipts only)
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant wallet (funding the refund) or a delegate with the refund permission
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only), tracking its refunded total
+    #[account(
+        mut,
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Box<Account<'info, Delegate>>>,
+
+    /// The payer's stamp card (required when refunding a stamped receipt)
+    #[account(
+        mut,
+        seeds = [b"stamps", merchant_account.key().as_ref(), receipt.payer.as_ref()],
+        bump = customer_stamps.bump
+    )]
+    pub customer_stamps: Option<Box<Account<'info, CustomerStamps>>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawRefundVault<'info> {
+    /// The merchant account PDA owning the vault
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant's refund vault
+    /// CHECK: System-owned PDA that only holds lamports and signs with its seeds
+    #[account(mut, seeds = [b"refund_vault", merchant_account.key().as_ref()], bump)]
+    pub refund_vault: UncheckedAccount<'info>,
+
+    /// The mint to withdraw (token withdrawals only)
+    #[account(mint::token_program = token_program)]
+    pub mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// The refund vault's token account (token withdrawals only)
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = refund_vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant's token account receiving the tokens (token withdrawals only)
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = merchant,
+        token::token_program = token_program
+    )]
+    pub merchant_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant wallet (receives withdrawn lamports)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, reference: [u8; 32])]
+pub struct EscrowPaymentSol<'info> {
+    /// The global config PDA (protocol fee and dispute window settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// The merchant account PDA being paid
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant wallet recorded on the receipt
+    /// CHECK: Must match the merchant account's current wallet; receives nothing here
+    #[account(address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The payment receipt PDA
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentReceipt::LEN,
+        seeds = [b"receipt", merchant_account.key().as_ref(), reference.as_ref()],
+        bump
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The escrow PDA, which also holds the lamports
+    #[account(
+        init,
+        payer = payer,
+        space = Escrow::LEN,
+        seeds = [b"escrow", receipt.key().as_ref()],
+        bump
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for this merchant
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MerchantMintStats::LEN,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), NATIVE_SOL_MINT.as_ref()],
+        bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// Per-customer payment record for this merchant (tracks unique payers)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CustomerRecord::LEN,
+        seeds = [b"customer", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub customer_record: Box<Account<'info, CustomerRecord>>,
+
+    /// The customer wallet funding the escrow and any rent
+    #[account(
+        mut,
+        constraint = payer.key() != merchant_account.merchant @ ErrorCode::SelfPayment
+    )]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, reference: [u8; 32])]
+pub struct EscrowPaymentToken<'info> {
+    /// The global config PDA (global accepted-mint scope, fee and dispute window settings)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// The merchant account PDA being paid
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant wallet recorded on the receipt
+    /// CHECK: Must match the merchant account's current wallet; receives nothing here
+    #[account(address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The payment mint (Token or Token-2022)
+    #[account(mint::token_program = token_program)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Global or merchant accepted-mint entry for this mint
+    #[account(
+        seeds = [b"accepted_mint", accepted_mint.scope.as_ref(), mint.key().as_ref()],
+        bump = accepted_mint.bump,
+        constraint = accepted_mint.scope == config.key()
+            || accepted_mint.scope == merchant_account.key() @ ErrorCode::MintNotAccepted
+    )]
+    pub accepted_mint: Box<Account<'info, AcceptedMint>>,
+
+    /// The mint's global accepted-mint entry PDA, whose cap limits the protocol fee
+    /// CHECK: Address derived from the config and mint; read only if the entry exists
+    #[account(seeds = [b"accepted_mint", config.key().as_ref(), mint.key().as_ref()], bump)]
+    pub global_accepted_mint: UncheckedAccount<'info>,
+
+    /// The payer's token account
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = payer,
+        token::token_program = token_program
+    )]
+    pub payer_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The payment receipt PDA
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentReceipt::LEN,
+        seeds = [b"receipt", merchant_account.key().as_ref(), reference.as_ref()],
+        bump
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The escrow PDA (vault authority)
+    #[account(
+        init,
+        payer = payer,
+        space = Escrow::LEN,
+        seeds = [b"escrow", receipt.key().as_ref()],
+        bump
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The escrow's token vault
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"escrow_vault", escrow.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for this merchant
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MerchantMintStats::LEN,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// Per-customer payment record for this merchant (tracks unique payers)
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = CustomerRecord::LEN,
+        seeds = [b"customer", merchant_account.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub customer_record: Box<Account<'info, CustomerRecord>>,
+
+    /// The customer wallet funding the escrow and any rent
+    #[account(
+        mut,
+        constraint = payer.key() != merchant_account.merchant @ ErrorCode::SelfPayment
+    )]
+    pub payer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleEscrow<'info> {
+    /// The global config PDA (treasury and admin)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// The merchant account PDA that was paid
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Box<Account<'info, MerchantAccount>>,
+
+    /// The merchant wallet receiving released funds
+    /// CHECK: Must match the merchant account's current wallet; receives SOL or owns the token account
+    #[account(mut, address = merchant_account.merchant @ ErrorCode::Unauthorized)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// Treasury wallet receiving the protocol fee
+    /// CHECK: Must match the config treasury; receives SOL or owns the fee token account
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// The customer who funded the escrow (receives refunds and the reclaimed rent)
+    /// CHECK: Must match the escrow payer; receives SOL or owns the token account
+    #[account(mut, address = escrow.payer @ ErrorCode::InvalidRefundRecipient)]
+    pub payer: UncheckedAccount<'info>,
+
+    /// The escrow PDA being settled
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"escrow", escrow.receipt.as_ref()],
+        bump = escrow.bump,
+        has_one = merchant_account,
+        has_one = receipt
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The receipt of the escrowed payment
+    #[account(mut)]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The dispute on the escrow (required while it is disputed)
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"dispute", escrow.key().as_ref()],
+        bump = dispute.bump
+    )]
+    pub dispute: Option<Box<Account<'info, Dispute>>>,
+
+    /// The signer's arbiter registry entry (arbiter rulings only)
+    #[account(
+        seeds = [b"arbiter", authority.key().as_ref()],
+        bump = arbiter.bump
+    )]
+    pub arbiter: Option<Box<Account<'info, Arbiter>>>,
+
+    /// The signer's delegate entry (delegate claims only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Box<Account<'info, Delegate>>>,
+
+    /// The merchant's payment statistics PDA
+    #[account(
+        mut,
+        seeds = [b"stats", merchant_account.key().as_ref()],
+        bump = merchant_stats.bump
+    )]
+    pub merchant_stats: Box<Account<'info, MerchantStats>>,
+
+    /// Per-mint payment statistics for the escrow mint
+    #[account(
+        mut,
+        seeds = [b"mint_stats", merchant_account.key().as_ref(), escrow.mint.as_ref()],
+        bump = mint_stats.bump
+    )]
+    pub mint_stats: Box<Account<'info, MerchantMintStats>>,
+
+    /// The escrow mint (token escrows only)
+    #[account(mint::token_program = token_program)]
+    pub mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+
+    /// The escrow's token vault (token escrows only)
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", escrow.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow,
+        token::token_program = token_program
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The merchant's associated token account, created if missing (token releases only)
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = merchant,
+        associated_token::token_program = token_program
+    )]
+    pub merchant_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The treasury's associated token account, created if missing (token releases only)
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The payer's associated token account, created if missing (token refunds only)
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// The customer, merchant, delegate, admin or arbiter settling the escrow (pays any new ATA rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(arbiter: Pubkey)]
+pub struct AddArbiter<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The arbiter registry entry PDA
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 8 + 1, // discriminator + authority + added_at + bump
+        seeds = [b"arbiter", arbiter.as_ref()],
+        bump
+    )]
+    pub arbiter_account: Account<'info, Arbiter>,
+
+    /// The config admin (pays rent for the registry entry)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveArbiter<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The arbiter registry entry PDA to close
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"arbiter", arbiter_account.authority.as_ref()],
+        bump = arbiter_account.bump
+    )]
+    pub arbiter_account: Account<'info, Arbiter>,
+
+    /// The config admin (receives the reclaimed rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    /// The global config PDA (ruling window)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// The merchant account PDA that was paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The escrow being disputed
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.receipt.as_ref()],
+        bump = escrow.bump,
+        has_one = merchant_account
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    /// The dispute PDA
+    #[account(
+        init,
+        payer = authority,
+        space = Dispute::LEN,
+        seeds = [b"dispute", escrow.key().as_ref()],
+        bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    /// The customer who funded the escrow, the merchant wallet or a delegate with the
+    /// escrow permission (pays rent, which goes to the customer on settlement)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitDisputeEvidence<'info> {
+    /// The merchant account PDA that was paid
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The dispute PDA
+    #[account(
+        mut,
+        seeds = [b"dispute", dispute.escrow.as_ref()],
+        bump = dispute.bump,
+        has_one = merchant_account
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    /// The customer, the merchant's current wallet or a delegate with the escrow permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddStaff<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The staff PDA
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 2 + 8 + 1, // discriminator + merchant_account + wallet + weight + added_at + bump
+        seeds = [b"staff", merchant_account.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub staff: Account<'info, Staff>,
+
+    /// The merchant wallet or a delegate with the staff permission (pays rent for the staff entry)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStaff<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The staff PDA
+    #[account(
+        mut,
+        seeds = [b"staff", merchant_account.key().as_ref(), staff.wallet.as_ref()],
+        bump = staff.bump,
+        has_one = merchant_account
+    )]
+    pub staff: Account<'info, Staff>,
+
+    /// The merchant wallet or a delegate with the staff permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveStaff<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The staff PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"staff", merchant_account.key().as_ref(), staff.wallet.as_ref()],
+        bump = staff.bump,
+        has_one = merchant_account
+    )]
+    pub staff: Account<'info, Staff>,
+
+    /// The merchant wallet or a delegate with the staff permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct AddDelegate<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The delegate PDA
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + 32 + 32 + 1 + 8 + 32 + 8 + 8 + 1, // discriminator + merchant_account + authority + permissions + refund_limit + refund_mint + refunded_amount + added_at + bump
+        seeds = [b"delegate", merchant_account.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub delegate_account: Account<'info, Delegate>,
+
+    /// The merchant wallet (pays rent for the delegate entry)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveDelegate<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The delegate PDA to close
+    #[account(
+        mut,
+        close = merchant,
+        seeds = [b"delegate", merchant_account.key().as_ref(), delegate_account.authority.as_ref()],
+        bump = delegate_account.bump,
+        has_one = merchant_account
+    )]
+    pub delegate_account: Account<'info, Delegate>,
+
+    /// The merchant wallet (receives the reclaimed rent)
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(merchant: Pubkey)]
+pub struct IssueFeeWaiver<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The fee waiver PDA
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 32 + 8 + 1, // discriminator + merchant + issued_by + issued_at + bump
+        seeds = [b"fee_waiver", merchant.as_ref()],
+        bump
+    )]
+    pub fee_waiver: Account<'info, FeeWaiver>,
+
+    /// The config admin (pays rent for the waiver)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeFeeWaiver<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The fee waiver PDA to close
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"fee_waiver", fee_waiver.merchant.as_ref()],
+        bump = fee_waiver.bump
+    )]
+    pub fee_waiver: Account<'info, FeeWaiver>,
+
+    /// The config admin (receives the reclaimed rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitReview<'info> {
+    /// The merchant account PDA being reviewed
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// A settled (not escrowed) receipt for a payment from the reviewer to this merchant
+    #[account(
+        seeds = [b"receipt", merchant_account.key().as_ref(), receipt.reference.as_ref()],
+        bump = receipt.bump,
+        constraint = receipt.payer == reviewer.key() @ ErrorCode::ReviewNotEligible,
+        constraint = !receipt.escrowed @ ErrorCode::ReviewNotEligible
+    )]
+    pub receipt: Account<'info, PaymentReceipt>,
+
+    /// The review PDA
+    #[account(
+        init,
+        payer = reviewer,
+        space = Review::LEN,
+        seeds = [b"review", merchant_account.key().as_ref(), reviewer.key().as_ref()],
+        bump
+    )]
+    pub review: Account<'info, Review>,
+
+    /// The reviewing customer (pays rent for the review)
+    #[account(mut)]
+    pub reviewer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EditReview<'info> {
+    /// The merchant account PDA being reviewed
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The review PDA
+    #[account(
+        mut,
+        seeds = [b"review", merchant_account.key().as_ref(), reviewer.key().as_ref()],
+        bump = review.bump,
+        has_one = reviewer @ ErrorCode::Unauthorized
+    )]
+    pub review: Account<'info, Review>,
+
+    /// The reviewing customer
+    pub reviewer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteReview<'info> {
+    /// The merchant account PDA being reviewed
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The review PDA to close
+    #[account(
+        mut,
+        close = reviewer,
+        seeds = [b"review", merchant_account.key().as_ref(), reviewer.key().as_ref()],
+        bump = review.bump,
+        has_one = reviewer @ ErrorCode::Unauthorized
+    )]
+    pub review: Account<'info, Review>,
+
+    /// The reviewing customer (receives the reclaimed rent)
+    #[account(mut)]
+    pub reviewer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReplyToReview<'info> {
+    /// The merchant account PDA that was reviewed
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The review PDA
+    #[account(
+        mut,
+        seeds = [b"review", merchant_account.key().as_ref(), review.reviewer.as_ref()],
+        bump = review.bump
+    )]
+    pub review: Account<'info, Review>,
+
+    /// The merchant wallet or a delegate with the profile permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct ModerateReview<'info> {
+    /// The global config PDA
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The merchant account PDA that was reviewed
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The review PDA
+    #[account(
+        mut,
+        seeds = [b"review", merchant_account.key().as_ref(), review.reviewer.as_ref()],
+        bump = review.bump
+    )]
+    pub review: Account<'info, Review>,
+
+    /// The config admin
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateLoyaltyProgram<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The loyalty program PDA
+    #[account(
+        init,
+        payer = authority,
+        space = LoyaltyProgram::LEN,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump
+    )]
+    pub loyalty_program: Account<'info, LoyaltyProgram>,
+
+    /// The merchant wallet or a delegate with the loyalty permission (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLoyaltyProgram<'info> {
+    /// The merchant account PDA
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The loyalty program PDA
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, LoyaltyProgram>,
+
+    /// The merchant wallet or a delegate with the loyalty permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemPoints<'info> {
+    /// The merchant's loyalty program PDA
+    #[account(
+        seeds = [b"loyalty_program", loyalty_program.merchant_account.as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, LoyaltyProgram>,
+
+    /// The customer's loyalty balance PDA
+    #[account(
+        mut,
+        seeds = [
+            b"loyalty",
+            loyalty_program.merchant_account.as_ref(),
+            customer.key().as_ref()
+        ],
+        bump = loyalty_balance.bump
+    )]
+    pub loyalty_balance: Account<'info, LoyaltyBalance>,
+
+    /// The customer redeeming points
+    pub customer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseLoyaltyBalance<'info> {
+    /// The merchant account PDA
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant's loyalty program PDA
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, LoyaltyProgram>,
+
+    /// The customer's loyalty balance PDA to close
+    #[account(
+        mut,
+        close = customer,
+        seeds = [
+            b"loyalty",
+            merchant_account.key().as_ref(),
+            customer.key().as_ref()
+        ],
+        bump = loyalty_balance.bump
+    )]
+    pub loyalty_balance: Account<'info, LoyaltyBalance>,
+
+    /// The customer wallet receiving the reclaimed rent
+    /// CHECK: Only receives SOL; the balance PDA is seeded by it
+    #[account(mut)]
+    pub customer: UncheckedAccount<'info>,
+
+    /// The customer, or the merchant wallet or a delegate with the loyalty permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseLoyaltyProgram<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The loyalty program PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"loyalty_program", merchant_account.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, LoyaltyProgram>,
+
+    /// The merchant wallet or a delegate with the loyalty permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateStampCard<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The stamp card PDA
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 1 + 32 + 8 + 1 + 8 + 1 + 4, // discriminator + merchant_account + mint + stamps_required + reward_hash + min_purchase + is_active + created_at + bump + holder_count
+        seeds = [b"stamp_card", merchant_account.key().as_ref()],
+        bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// The merchant wallet or a delegate with the loyalty permission (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStampCard<'info> {
+    /// The merchant account PDA
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The stamp card PDA
+    #[account(
+        mut,
+        seeds = [b"stamp_card", merchant_account.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// The merchant wallet or a delegate with the loyalty permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CollectStamp<'info> {
+    /// The merchant's stamp card PDA
+    #[account(
+        mut,
+        seeds = [b"stamp_card", stamp_card.merchant_account.as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// A receipt for a payment from the customer to this merchant
+    #[account(
+        mut,
+        seeds = [b"receipt", stamp_card.merchant_account.as_ref(), receipt.reference.as_ref()],
+        bump = receipt.bump,
+        constraint = receipt.payer == customer.key() @ ErrorCode::ReceiptNotQualifying
+    )]
+    pub receipt: Box<Account<'info, PaymentReceipt>>,
+
+    /// The customer's stamp card PDA
+    #[account(
+        init_if_needed,
+        payer = customer,
+        space = 8 + 32 + 32 + 1 + 4 + 8 + 1, // discriminator + merchant_account + customer + stamps + rewards_claimed + last_stamped_at + bump
+        seeds = [b"stamps", stamp_card.merchant_account.as_ref(), customer.key().as_ref()],
+        bump
+    )]
+    pub customer_stamps: Account<'info, CustomerStamps>,
+
+    /// The customer collecting the stamp (pays rent for a new card)
+    #[account(mut)]
+    pub customer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStampReward<'info> {
+    /// The merchant account PDA
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant's stamp card PDA
+    #[account(
+        seeds = [b"stamp_card", merchant_account.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// The customer's stamp card PDA
+    #[account(
+        mut,
+        seeds = [b"stamps", merchant_account.key().as_ref(), customer.key().as_ref()],
+        bump = customer_stamps.bump
+    )]
+    pub customer_stamps: Account<'info, CustomerStamps>,
+
+    /// The customer claiming the reward
+    pub customer: Signer<'info>,
+
+    /// The merchant wallet or a delegate with the loyalty permission, confirming the reward
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCustomerStamps<'info> {
+    /// The merchant account PDA
+    #[account(
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The merchant's stamp card PDA
+    #[account(
+        mut,
+        seeds = [b"stamp_card", merchant_account.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// The customer's stamp card PDA to close
+    #[account(
+        mut,
+        close = customer,
+        seeds = [b"stamps", merchant_account.key().as_ref(), customer.key().as_ref()],
+        bump = customer_stamps.bump
+    )]
+    pub customer_stamps: Account<'info, CustomerStamps>,
+
+    /// The customer wallet receiving the reclaimed rent
+    /// CHECK: Only receives SOL; the stamp card PDA is seeded by it
+    #[account(mut)]
+    pub customer: UncheckedAccount<'info>,
+
+    /// The customer, or the merchant wallet or a delegate with the loyalty permission
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseStampCard<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The stamp card PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"stamp_card", merchant_account.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    /// The merchant wallet or a delegate with the loyalty permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+#[instruction(coupon_id: u64)]
+pub struct CreateCoupon<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump,
+        constraint = merchant_account.is_active @ ErrorCode::MerchantInactive
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The coupon PDA
+    #[account(
+        init,
+        payer = authority,
+        space = Coupon::LEN,
+        seeds = [
+            b"coupon",
+            merchant_account.key().as_ref(),
+            coupon_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    /// The merchant wallet or a delegate with the promotions permission (pays rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCoupon<'info> {
+    /// The merchant account PDA
+    #[account(
+        mut,
+        seeds = [
+            b"merchant",
+            merchant_account.registration_key.as_ref(),
+            generation_seed(merchant_account.generation).as_ref()
+        ],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, MerchantAccount>,
+
+    /// The coupon PDA to close
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"coupon",
+            merchant_account.key().as_ref(),
+            coupon.coupon_id.to_le_bytes().as_ref()
+        ],
+        bump = coupon.bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    /// The merchant wallet or a delegate with the promotions permission (receives the reclaimed rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The signer's delegate entry (delegates only)
+    #[account(
+        seeds = [b"delegate", merchant_account.key().as_ref(), authority.key().as_ref()],
+        bump = delegate.bump
+    )]
+    pub delegate: Option<Account<'info, Delegate>>,
+}
+
+#[derive(Accounts)]
+pub struct ClipCoupon<'info> {
+    /// The coupon PDA
+    #[account(
+        seeds = [
+            b"coupon",
+            coupon.merchant_account.as_ref(),
+            coupon.coupon_id.to_le_bytes().as_ref()
+        ],
+        bump = coupon.bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    /// The wallet's usage entry for the coupon
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = 8 + 32 + 32 + 4 + 8 + 1, // discriminator + coupon + wallet + uses + clipped_at + bump
+        seeds = [b"coupon_usage", coupon.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub coupon_usage: Account<'info, CouponUsage>,
+
+    /// The customer clipping the coupon (pays rent for the usage entry)
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Event emitted when the program config is initialized or updated
+#[event]
+pub struct ConfigUpdatedEvent {
+    pub admin: Pubkey,
+    pub treasury: Pubkey,
+    pub registration_fee: u64,
+    pub protocol_fee_bps: u16,
+    pub protocol_fee_cap: u64,
+    pub max_proof_distance_m: u32,
+    pub escrow_dispute_window: i64,
+    pub dispute_ruling_window: i64,
+    pub dispute_default_refund_bps: u16,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a verifier is added to the registry
+#[event]
+pub struct VerifierAddedEvent {
+    pub verifier: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a verifier is removed from the registry
+#[event]
+pub struct VerifierRemovedEvent {
+    pub verifier: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a verifier is suspended or reinstated
+#[event]
+pub struct VerifierSuspendedEvent {
+    pub verifier: Pubkey,
+    pub suspended: bool,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant registers
+#[event]
+pub struct MerchantRegisteredEvent {
+    pub merchant: Pubkey,
+    pub business_name: String,
+    pub lat: i64,
+    pub lng: i64,
+    pub timestamp: i64,
+    pub fee_paid: u64,
+    pub fee_mint: Pubkey,
+    pub fee_waived: bool,
+}
+
+/// Event emitted when a merchant updates their profile
+///
+/// Only changed fields are set; unchanged fields are `None`.
+#[event]
+pub struct MerchantUpdatedEvent {
+    pub merchant: Pubkey,
+    pub old_business_name: Option<String>,
+    pub new_business_name: Option<String>,
+    pub old_lat: Option<i64>,
+    pub new_lat: Option<i64>,
+    pub old_lng: Option<i64>,
+    pub new_lng: Option<i64>,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant closes for business
+#[event]
+pub struct MerchantDeactivatedEvent {
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant reopens for business
+#[event]
+pub struct MerchantReactivatedEvent {
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when the admin suspends a merchant
+#[event]
+pub struct MerchantSuspendedEvent {
+    pub merchant: Pubkey,
+    pub reason_code: u8,
+    pub expires_at: Option<i64>,
+    pub timestamp: i64,
+}
+
+/// Event emitted when the admin lifts a merchant suspension
+#[event]
+pub struct MerchantSuspensionLiftedEvent {
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant closes their account
+#[event]
+pub struct MerchantClosedEvent {
+    pub merchant: Pubkey,
+    pub merchant_id: String,
+    pub business_name: String,
+    pub location_proof: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant proposes an ownership transfer
+#[event]
+pub struct MerchantTransferProposedEvent {
+    pub merchant_account: Pubkey,
+    pub current_merchant: Pubkey,
+    pub proposed_merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when the proposed wallet accepts an ownership transfer
+#[event]
+pub struct MerchantTransferAcceptedEvent {
+    pub merchant_account: Pubkey,
+    pub previous_merchant: Pubkey,
+    pub new_merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a pending ownership transfer is cancelled or declined
+#[event]
+pub struct MerchantTransferCancelledEvent {
+    pub merchant_account: Pubkey,
+    pub proposed_merchant: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a location is verified on-chain
+#[event]
+pub struct LocationVerifiedEvent {
+    pub merchant_account: Pubkey,
+    pub verifier: Pubkey,
+    pub lat: i64,
+    pub lng: i64,
+    pub distance_m: u64,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a location proof is revoked and closed
+#[event]
+pub struct LocationProofRevokedEvent {
+    pub merchant_id: String,
+    pub proof: Pubkey,
+    pub verifier: Pubkey,
+    pub revoked_by: Pubkey,
+    pub reason_code: u8,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a customer pays a merchant
+#[event]
+pub struct PaymentMadeEvent {
+    pub receipt: Pubkey,
+    pub payer: Pubkey,
+    pub merchant_account: Pubkey,
+    pub merchant: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+    pub merchant_amount: u64,
+    pub reference: [u8; 32],
+    pub timestamp: i64,
+}
+
+/// Event emitted when a mint is added to an accepted-mint list
+#[event]
+pub struct AcceptedMintAddedEvent {
+    pub scope: Pubkey,
+    pub mint: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a mint is removed from an accepted-mint list
+#[event]
+pub struct AcceptedMintRemovedEvent {
+    pub scope: Pubkey,
+    pub mint: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant's protocol fee override changes
+#[event]
+pub struct MerchantFeeOverrideSetEvent {
+    pub merchant_account: Pubkey,
+    pub fee_bps: Option<u16>,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment request is created
+#[event]
+pub struct PaymentRequestCreatedEvent {
+    pub payment_request: Pubkey,
+    pub merchant_account: Pubkey,
+    pub request_id: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub expires_at: Option<i64>,
+    pub reusable: bool,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment request is paid
+#[event]
+pub struct PaymentRequestPaidEvent {
+    pub payment_request: Pubkey,
+    pub receipt: Pubkey,
+    pub payer: Pubkey,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment request expires or is cancelled
+#[event]
+pub struct PaymentRequestEndedEvent {
+    pub payment_request: Pubkey,
+    pub merchant_account: Pubkey,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment is fully or partially refunded
+#[event]
+pub struct PaymentRefundedEvent {
+    pub receipt: Pubkey,
+    pub merchant_account: Pubkey,
+    pub payer: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub total_refunded: u64,
+    pub timestamp: i64,
+}
 
-    /// Whether merchant is active
-    pub is_active: bool, // 1 byte
+/// Event emitted when the merchant withdraws from the refund vault
+#[event]
+pub struct RefundVaultWithdrawnEvent {
+    pub merchant_account: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
 
-    /// PDA bump seed
-    pub bump: u8, // 1 byte
+/// Event emitted when a payment is placed in escrow
+#[event]
+pub struct EscrowFundedEvent {
+    pub escrow: Pubkey,
+    pub receipt: Pubkey,
+    pub payer: Pubkey,
+    pub merchant_account: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub release_after: i64,
+    pub timestamp: i64,
 }
 
-// 8 (discriminator) + 32 (merchant) + 68 (business_name) + 8 (lat) + 8 (lng) + 8 (timestamp) + 1 (is_active) + 1 (bump) = 134 bytes
+/// Event emitted when escrowed funds are paid out
+#[event]
+pub struct EscrowSettledEvent {
+    pub escrow: Pubkey,
+    pub receipt: Pubkey,
+    pub settled_by: Pubkey,
+    pub to_merchant: u64,
+    pub to_treasury: u64,
+    pub to_payer: u64,
+    pub timestamp: i64,
+}
 
-/// Account struct for storing location proof (33 bytes total)
-#[account]
-pub struct LocationProof {
-    /// Latitude * 1,000,000 (6 decimal places)
-    pub lat: i64, // 8 bytes
+/// Event emitted when an arbiter is added to the registry
+#[event]
+pub struct ArbiterAddedEvent {
+    pub arbiter: Pubkey,
+    pub timestamp: i64,
+}
 
-    /// Longitude * 1,000,000 (6 decimal places)
-    pub lng: i64, // 8 bytes
+/// Event emitted when an arbiter is removed from the registry
+#[event]
+pub struct ArbiterRemovedEvent {
+    pub arbiter: Pubkey,
+    pub timestamp: i64,
+}
 
-    /// Unix timestamp when location was verified
-    pub verified_at: i64, // 8 bytes
+/// Event emitted when a customer opens a dispute on an escrow
+#[event]
+pub struct DisputeOpenedEvent {
+    pub dispute: Pubkey,
+    pub escrow: Pubkey,
+    pub customer: Pubkey,
+    pub merchant_account: Pubkey,
+    pub opened_by: Pubkey,
+    pub reason_code: u8,
+    pub ruling_deadline: i64,
+    pub timestamp: i64,
+}
 
-    /// PDA bump seed
-    pub bump: u8, // 1 byte
+/// Event emitted when evidence is attached to a dispute
+#[event]
+pub struct DisputeEvidenceSubmittedEvent {
+    pub dispute: Pubkey,
+    pub submitted_by: Pubkey,
+    pub evidence_hash: [u8; 32],
+    pub timestamp: i64,
 }
 
-// 8 (discriminator) + 8 (lat) + 8 (lng) + 8 (timestamp) + 1 (bump) = 33 bytes
+/// Event emitted when a dispute is ruled on, times out, or is withdrawn
+#[event]
+pub struct DisputeResolvedEvent {
+    pub dispute: Pubkey,
+    pub escrow: Pubkey,
+    pub resolved_by: Pubkey,
+    pub payer_share_bps: u16,
+    pub to_merchant: u64,
+    pub to_treasury: u64,
+    pub to_payer: u64,
+    pub by_default: bool,
+    pub timestamp: i64,
+}
 
-#[derive(Accounts)]
-#[instruction(merchant_id: String, business_name: String, lat: i64, lng: i64)]
-pub struct RegisterMerchant<'info> {
-    /// The merchant account PDA
-    #[account(
-        init,
-        payer = merchant,
-        space = 8 + 32 + 68 + 8 + 8 + 8 + 1 + 1, // discriminator + merchant + business_name + lat + lng + timestamp + is_active + bump
-        seeds = [b"merchant", merchant.key().as_ref()],
-        bump
-    )]
-    pub merchant_account: Account<'info, MerchantAccount>,
+/// Event emitted when a staff member is added, re-weighted or removed (`weight` = None)
+#[event]
+pub struct StaffUpdatedEvent {
+    pub merchant_account: Pubkey,
+    pub wallet: Pubkey,
+    pub weight: Option<u16>,
+    pub timestamp: i64,
+}
 
-    /// The merchant wallet (pays rent + registration fee)
-    #[account(mut)]
-    pub merchant: Signer<'info>,
+/// Event emitted when a payment includes a tip for staff
+#[event]
+pub struct TipPaidEvent {
+    pub receipt: Pubkey,
+    pub merchant_account: Pubkey,
+    pub tip: u64,
+    pub legs: Vec<TipLeg>,
+    pub timestamp: i64,
+}
 
-    /// Treasury account that receives registration fees
-    /// CHECK: This is safe because we only transfer SOL to it
-    #[account(mut)]
-    pub treasury: AccountInfo<'info>,
+/// Event emitted when a delegate is added or removed (`permissions` = 0)
+#[event]
+pub struct DelegateUpdatedEvent {
+    pub merchant_account: Pubkey,
+    pub delegate: Pubkey,
+    pub permissions: u8,
+    pub refund_mint: Pubkey,
+    pub refund_limit: u64,
+    pub timestamp: i64,
+}
 
-    pub system_program: Program<'info, System>,
+/// Event emitted when a registration fee waiver is issued
+#[event]
+pub struct FeeWaiverIssuedEvent {
+    pub merchant: Pubkey,
+    pub issued_by: Pubkey,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-#[instruction(lat: i64, lng: i64, merchant_id: String)]
-pub struct CreateLocationProof<'info> {
-    /// The location proof PDA account
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + 8 + 8 + 8 + 1, // discriminator + lat + lng + timestamp + bump
-        seeds = [b"proof", merchant_id.as_bytes()],
-        bump
-    )]
-    pub proof: Account<'info, LocationProof>,
+/// Event emitted when an unused registration fee waiver is revoked
+#[event]
+pub struct FeeWaiverRevokedEvent {
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
 
-    /// The server keypair that pays for the account creation
-    #[account(mut)]
-    pub payer: Signer<'info>,
+/// Event emitted when a customer reviews a merchant
+#[event]
+pub struct ReviewSubmittedEvent {
+    pub review: Pubkey,
+    pub merchant_account: Pubkey,
+    pub reviewer: Pubkey,
+    pub rating: u8,
+    pub content_hash: [u8; 32],
+    pub uri: String,
+    pub timestamp: i64,
+}
 
-    pub system_program: Program<'info, System>,
+/// Event emitted when a review is edited
+#[event]
+pub struct ReviewEditedEvent {
+    pub review: Pubkey,
+    pub merchant_account: Pubkey,
+    pub old_rating: u8,
+    pub new_rating: u8,
+    pub content_hash: [u8; 32],
+    pub uri: String,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CloseLocationProof<'info> {
-    /// The location proof PDA account to close
-    #[account(
-        mut,
-        close = authority,
-        seeds = [b"proof"],
-        bump = proof.bump
-    )]
-    pub proof: Account<'info, LocationProof>,
+/// Event emitted when a review is deleted
+#[event]
+pub struct ReviewDeletedEvent {
+    pub review: Pubkey,
+    pub merchant_account: Pubkey,
+    pub reviewer: Pubkey,
+    pub rating: u8,
+    pub timestamp: i64,
+}
 
-    /// The authority that can close the account (must be the original payer)
-    #[account(mut)]
-    pub authority: Signer<'info>,
+/// Event emitted when a merchant replies to a review
+#[event]
+pub struct ReviewRepliedEvent {
+    pub review: Pubkey,
+    pub merchant_account: Pubkey,
+    pub reply_hash: [u8; 32],
+    pub timestamp: i64,
 }
 
-/// Event emitted when a merchant registers
+/// Event emitted when an admin hides or restores a review
 #[event]
-pub struct MerchantRegisteredEvent {
-    pub merchant: Pubkey,
-    pub business_name: String,
-    pub lat: i64,
-    pub lng: i64,
+pub struct ReviewHiddenEvent {
+    pub review: Pubkey,
+    pub merchant_account: Pubkey,
+    pub hidden: bool,
+    pub reason_code: u8,
     pub timestamp: i64,
-    pub fee_paid: u64,
 }
 
-/// Event emitted when a location is verified on-chain
+/// Event emitted when a loyalty program is created or updated
 #[event]
-pub struct LocationVerifiedEvent {
-    pub lat: i64,
-    pub lng: i64,
+pub struct LoyaltyProgramUpdatedEvent {
+    pub loyalty_program: Pubkey,
+    pub merchant_account: Pubkey,
+    pub mint: Pubkey,
+    pub earn_rate: u64,
+    pub redeem_value: u64,
+    pub points_ttl: i64,
+    pub is_active: bool,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment credits loyalty points
+#[event]
+pub struct LoyaltyPointsEarnedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub points: u64,
+    pub balance: u64,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a customer converts points into a pending discount
+#[event]
+pub struct LoyaltyPointsRedeemedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub points: u64,
+    pub discount: u64,
+    pub pending_discount: u64,
+    pub timestamp: i64,
+}
+
+/// Event emitted when an idle balance's points expire
+#[event]
+pub struct LoyaltyPointsExpiredEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub points: u64,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a customer's loyalty balance is closed
+#[event]
+pub struct LoyaltyBalanceClosedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub points: u64,
+    pub pending_discount: u64,
+    pub closed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant closes its loyalty program
+#[event]
+pub struct LoyaltyProgramClosedEvent {
+    pub loyalty_program: Pubkey,
+    pub merchant_account: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a stamp card is created or updated
+#[event]
+pub struct StampCardUpdatedEvent {
+    pub stamp_card: Pubkey,
+    pub merchant_account: Pubkey,
+    pub mint: Pubkey,
+    pub stamps_required: u8,
+    pub reward_hash: [u8; 32],
+    pub min_purchase: u64,
+    pub is_active: bool,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a receipt earns a stamp
+#[event]
+pub struct StampCollectedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub receipt: Pubkey,
+    pub stamps: u8,
+    pub stamps_required: u8,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a full stamp card is redeemed
+#[event]
+pub struct StampRewardClaimedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub reward_hash: [u8; 32],
+    pub rewards_claimed: u32,
+    pub confirmed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a customer's stamp card is closed
+#[event]
+pub struct CustomerStampsClosedEvent {
+    pub merchant_account: Pubkey,
+    pub customer: Pubkey,
+    pub stamps: u8,
+    pub closed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant closes its stamp card
+#[event]
+pub struct StampCardClosedEvent {
+    pub stamp_card: Pubkey,
+    pub merchant_account: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant creates a coupon
+#[event]
+pub struct CouponCreatedEvent {
+    pub coupon: Pubkey,
+    pub merchant_account: Pubkey,
+    pub coupon_id: u64,
+    pub mint: Pubkey,
+    pub discount: CouponDiscount,
+    pub max_uses: u32,
+    pub per_wallet_limit: u32,
+    pub valid_from: i64,
+    pub valid_until: Option<i64>,
+    pub has_code: bool,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a merchant closes a coupon
+#[event]
+pub struct CouponClosedEvent {
+    pub coupon: Pubkey,
+    pub merchant_account: Pubkey,
+    pub uses: u32,
+    pub timestamp: i64,
+}
+
+/// Event emitted when a payment uses a coupon
+#[event]
+pub struct CouponRedeemedEvent {
+    pub coupon: Pubkey,
+    pub merchant_account: Pubkey,
+    pub wallet: Pubkey,
+    pub discount: u64,
+    pub uses: u32,
     pub timestamp: i64,
 }
 
@@ -315,4 +9067,259 @@ pub enum ErrorCode {
 
     #[msg("Invalid longitude. Must be between -180 and +180 degrees (multiplied by 1,000,000)")]
     InvalidLongitude,
+
+    #[msg("Signer is not authorized to perform this action")]
+    Unauthorized,
+
+    #[msg("Treasury account does not match the configured treasury")]
+    InvalidTreasury,
+
+    #[msg("Fee basis points must not exceed 10,000")]
+    InvalidFeeBps,
+
+    #[msg("Verifier is suspended and cannot create location proofs")]
+    VerifierSuspended,
+
+    #[msg("At least one field must be provided")]
+    NothingToUpdate,
+
+    #[msg("Merchant is suspended")]
+    MerchantSuspended,
+
+    #[msg("Merchant is not suspended")]
+    MerchantNotSuspended,
+
+    #[msg("Reason code must be non-zero")]
+    InvalidReasonCode,
+
+    #[msg("Suspension expiry must be in the future")]
+    InvalidSuspensionExpiry,
+
+    #[msg("Merchant has open obligations and cannot be closed")]
+    MerchantHasOpenObligations,
+
+    #[msg("Transfer target must differ from the current merchant")]
+    InvalidTransferTarget,
+
+    #[msg("No ownership transfer is pending")]
+    NoPendingTransfer,
+
+    #[msg("Merchant ID does not match the registered merchant")]
+    MerchantIdMismatch,
+
+    #[msg("Location proof does not match the merchant's linked proof")]
+    LocationProofMismatch,
+
+    #[msg("Verified location is too far from the merchant's registered location")]
+    LocationTooFar,
+
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+
+    #[msg("Merchant is not active")]
+    MerchantInactive,
+
+    #[msg("Merchants cannot pay themselves")]
+    SelfPayment,
+
+    #[msg("Mint is not accepted by this merchant")]
+    MintNotAccepted,
+
+    #[msg("Payment statistics counter overflow")]
+    StatsOverflow,
+
+    #[msg("Memo exceeds maximum length of 100 bytes")]
+    MemoTooLong,
+
+    #[msg("Payment request expiry must be in the future")]
+    InvalidRequestExpiry,
+
+    #[msg("Payment request is not open")]
+    PaymentRequestNotOpen,
+
+    #[msg("Payment request has expired")]
+    PaymentRequestExpired,
+
+    #[msg("Payment request has not expired yet")]
+    PaymentRequestNotExpired,
+
+    #[msg("Paid mint does not match the payment request")]
+    WrongPaymentMint,
+
+    #[msg("Token payment is missing required token accounts")]
+    MissingTokenAccounts,
+
+    #[msg("Total refunds would exceed the original payment")]
+    RefundExceedsPayment,
+
+    #[msg("Refund recipient does not match the original payer")]
+    InvalidRefundRecipient,
+
+    #[msg("Escrow dispute window must not be negative")]
+    InvalidDisputeWindow,
+
+    #[msg("Payment is still held in escrow")]
+    EscrowPending,
+
+    #[msg("Escrow is under dispute")]
+    EscrowDisputed,
+
+    #[msg("Escrow is not under dispute")]
+    EscrowNotDisputed,
+
+    #[msg("Escrow dispute window has not ended yet")]
+    EscrowWindowOpen,
+
+    #[msg("Escrow dispute window has closed")]
+    DisputeWindowClosed,
+
+    #[msg("Each side can submit at most 4 evidence hashes")]
+    TooMuchEvidence,
+
+    #[msg("Dispute ruling deadline has passed")]
+    RulingWindowClosed,
+
+    #[msg("Dispute ruling deadline has not passed yet")]
+    RulingWindowOpen,
+
+    #[msg("Tip accounts do not match the merchant's staff")]
+    InvalidTipAccounts,
+
+    #[msg("Tip pool split must include every pool member exactly once")]
+    IncompleteTipPool,
+
+    #[msg("Tip pool is limited to 8 members")]
+    TipPoolFull,
+
+    #[msg("Merchant still has registered staff")]
+    MerchantHasStaff,
+
+    #[msg("Delegate permissions must be a non-empty set of known bits")]
+    InvalidPermissions,
+
+    #[msg("Delegate lacks the permission for this action")]
+    MissingPermission,
+
+    #[msg("Refund would take the delegate past its refund limit")]
+    RefundLimitExceeded,
+
+    #[msg("Merchant still has delegates")]
+    MerchantHasDelegates,
+
+    #[msg("Rating must be between 1 and 5")]
+    InvalidRating,
+
+    #[msg("Review URI exceeds maximum length of 200 bytes")]
+    ReviewUriTooLong,
+
+    #[msg("Reviewer has no qualifying payment to this merchant")]
+    ReviewNotEligible,
+
+    #[msg("Review is hidden")]
+    ReviewHidden,
+
+    #[msg("Review is not hidden")]
+    ReviewNotHidden,
+
+    #[msg("Points expiry must not be negative")]
+    InvalidPointsTtl,
+
+    #[msg("Loyalty program is not active")]
+    LoyaltyProgramInactive,
+
+    #[msg("Not enough loyalty points")]
+    InsufficientPoints,
+
+    #[msg("A stamp card needs at least one stamp")]
+    InvalidStampsRequired,
+
+    #[msg("Stamp card is not active")]
+    StampCardInactive,
+
+    #[msg("Receipt does not qualify for a stamp")]
+    ReceiptNotQualifying,
+
+    #[msg("Receipt has already earned a stamp")]
+    ReceiptAlreadyStamped,
+
+    #[msg("Stamp card is full; claim the reward first")]
+    StampCardFull,
+
+    #[msg("Not enough stamps to claim the reward")]
+    StampCardIncomplete,
+
+    #[msg("Coupon discount must be a non-zero fixed amount or 1 to 10,000 bps")]
+    InvalidCouponDiscount,
+
+    #[msg("Coupon must expire after it starts")]
+    InvalidCouponWindow,
+
+    #[msg("Coupon is not valid yet")]
+    CouponNotStarted,
+
+    #[msg("Coupon has expired")]
+    CouponExpired,
+
+    #[msg("Coupon has no uses left")]
+    CouponExhausted,
+
+    #[msg("Wallet has used this coupon the maximum number of times")]
+    CouponWalletLimitReached,
+
+    #[msg("Coupon code is missing or wrong")]
+    InvalidCouponCode,
+
+    #[msg("Coupon has not been clipped by the payer")]
+    CouponNotClipped,
+
+    #[msg("Merchant account already uses the current layout")]
+    MerchantAccountUpToDate,
+
+    #[msg("Merchant ID must not be empty")]
+    EmptyMerchantId,
+
+    #[msg("Delegate refunds require the merchant's refund vault")]
+    MissingRefundVault,
+
+    #[msg("Refund vault balance is too low")]
+    RefundVaultInsufficient,
+
+    #[msg("Refunding a stamped receipt requires the customer's stamp card")]
+    MissingCustomerStamps,
+
+    #[msg("Payment request is still open; cancel it instead")]
+    PaymentRequestStillOpen,
+
+    #[msg("Delegate is not allowed to refund in this mint")]
+    WrongRefundMint,
+
+    #[msg("Merchant still has coupons")]
+    MerchantHasCoupons,
+
+    #[msg("Merchant still has payment requests")]
+    MerchantHasPaymentRequests,
+
+    #[msg("Merchant still has its own accepted mints")]
+    MerchantHasAcceptedMints,
+
+    #[msg("Merchant still has a loyalty program")]
+    MerchantHasLoyaltyProgram,
+
+    #[msg("Merchant still has a stamp card")]
+    MerchantHasStampCard,
+
+    #[msg("Creating a loyalty balance requires the loyalty program")]
+    MissingLoyaltyProgram,
+
+    #[msg("Loyalty program must be paused first")]
+    LoyaltyProgramActive,
+
+    #[msg("Loyalty program still has customer balances")]
+    LoyaltyProgramHasBalances,
+
+    #[msg("Stamp card must be paused first")]
+    StampCardActive,
+
+    #[msg("Stamp card still has customer cards")]
+    StampCardHasHolders,
 }
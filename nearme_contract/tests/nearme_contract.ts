@@ -3,6 +3,17 @@ import { Program } from "@coral-xyz/anchor";
 import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
 import { NearmeContract } from "../target/types/nearme_contract";
 import { assert } from "chai";
+import { createHash } from "crypto";
+import {
+  TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  getAccount,
+} from "@solana/spl-token";
 
 describe("nearme_contract - Location Proof Tests", () => {
   // Configure the client to use the local cluster
@@ -13,15 +24,76 @@ describe("nearme_contract - Location Proof Tests", () => {
   const payer = provider.wallet as anchor.Wallet;
 
   // Test data
-  const merchantId = "test_merchant_" + Date.now();
+  let merchantId: string;
+  let merchantAccountPda: PublicKey;
   const validLat = 37_774_900; // San Francisco: 37.7749° * 1,000,000
   const validLng = -122_419_400; // San Francisco: -122.4194° * 1,000,000
 
   let proofPda: PublicKey;
   let proofBump: number;
 
+  // Global config: registration fee in lamports (0.01 SOL) and fee treasury
+  const REGISTRATION_FEE = 10_000_000;
+  const PROTOCOL_FEE_BPS = 100; // 1%
+  const PROTOCOL_FEE_CAP = 0; // lamports, 0 = no cap
+  const ESCROW_DISPUTE_WINDOW = 5; // seconds
+  const DISPUTE_RULING_WINDOW = 5; // seconds
+  const DISPUTE_DEFAULT_REFUND_BPS = 5_000; // split evenly when nobody rules
+  const MAX_PROOF_DISTANCE_M = 100;
+  const treasury = Keypair.generate();
+  const BPF_LOADER_UPGRADEABLE_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+  let configPda: PublicKey;
+  let verifierPda: PublicKey;
+
   before(async () => {
-    // Derive PDA for this test merchant
+    // Initialize the global config once; the provider wallet is the upgrade authority
+    [configPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const [programDataPda] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      BPF_LOADER_UPGRADEABLE_ID
+    );
+
+    await program.methods
+      .initializeConfig(
+        treasury.publicKey,
+        new anchor.BN(REGISTRATION_FEE),
+        PROTOCOL_FEE_BPS,
+        new anchor.BN(PROTOCOL_FEE_CAP),
+        MAX_PROOF_DISTANCE_M,
+        new anchor.BN(ESCROW_DISPUTE_WINDOW),
+        new anchor.BN(DISPUTE_RULING_WINDOW),
+        DISPUTE_DEFAULT_REFUND_BPS
+      )
+      .accounts({
+        config: configPda,
+        admin: payer.publicKey,
+        program: program.programId,
+        programData: programDataPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    console.log("Config PDA:", configPda.toString());
+
+    // Register the provider wallet as the verification server
+    [verifierPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("verifier"), payer.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .addVerifier(payer.publicKey)
+      .accounts({
+        config: configPda,
+        verifierAccount: verifierPda,
+        admin: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    // Register the main test merchant and derive its proof PDA
+    ({ merchantId, merchantAccountPda } = await registerTestMerchant("Main Test Merchant"));
     [proofPda, proofBump] = await PublicKey.findProgramAddress(
       [Buffer.from("proof"), Buffer.from(merchantId)],
       program.programId
@@ -30,6 +102,342 @@ describe("nearme_contract - Location Proof Tests", () => {
     console.log("Proof PDA:", proofPda.toString());
   });
 
+  // Mint recorded for native SOL payments
+  const NATIVE_SOL_MINT = PublicKey.default;
+
+  /**
+   * Derive the payment statistics PDA for a merchant account
+   */
+  function statsPda(merchantAccountPda: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("stats"), merchantAccountPda.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  function merchantIdPda(merchantId: string): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("merchant_id"), Buffer.from(merchantId)],
+      program.programId
+    )[0];
+  }
+
+  /**
+   * Derive the per-mint payment statistics PDA for a merchant account
+   */
+  function mintStatsPda(merchantAccountPda: PublicKey, mint: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_stats"), merchantAccountPda.toBuffer(), mint.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  /**
+   * Derive the customer record PDA for a merchant account and a payer
+   */
+  function customerRecordPda(merchantAccountPda: PublicKey, payerKey: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("customer"), merchantAccountPda.toBuffer(), payerKey.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  /**
+   * Derive the accepted-mint PDA for a scope (config or merchant account) and a mint
+   */
+  function acceptedMintPda(scope: PublicKey, mint: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("accepted_mint"), scope.toBuffer(), mint.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  /**
+   * Derive the receipt PDA for a merchant and a payment reference
+   */
+  function receiptPda(merchantAccountPda: PublicKey, reference: Buffer): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt"), merchantAccountPda.toBuffer(), reference],
+      program.programId
+    )[0];
+  }
+
+  /**
+   * Fund a fresh merchant wallet and register it
+   */
+  async function registerTestMerchant(
+    businessName: string,
+    lat: number = validLat,
+    lng: number = validLng
+  ): Promise<{ merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey }> {
+    const merchant = Keypair.generate();
+    const merchantId = "m_" + Date.now() + "_" + Math.floor(Math.random() * 1_000_000);
+
+    const airdropSig = await provider.connection.requestAirdrop(
+      merchant.publicKey,
+      2 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdropSig);
+
+    const [merchantAccountPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .registerMerchant(merchantId, businessName, new anchor.BN(lat), new anchor.BN(lng))
+      .accounts({
+        merchantAccount: merchantAccountPda,
+        merchantStats: statsPda(merchantAccountPda),
+        merchant: merchant.publicKey,
+        config: configPda,
+        treasury: treasury.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([merchant])
+      .rpc();
+
+    return { merchant, merchantId, merchantAccountPda };
+  }
+
+  /**
+   * Pay a merchant in SOL with a fresh reference and return the receipt PDA
+   */
+  async function paySol(
+    target: { merchant: Keypair; merchantAccountPda: PublicKey },
+    payer: Keypair,
+    amount: number
+  ): Promise<PublicKey> {
+    const reference = Keypair.generate().publicKey.toBuffer();
+    const receipt = receiptPda(target.merchantAccountPda, reference);
+    await program.methods
+      .payMerchantSol(new anchor.BN(amount), [...reference], new anchor.BN(0), false)
+      .accounts({
+        config: configPda,
+        merchantAccount: target.merchantAccountPda,
+        merchant: target.merchant.publicKey,
+        treasury: treasury.publicKey,
+        receipt,
+        merchantStats: statsPda(target.merchantAccountPda),
+        mintStats: mintStatsPda(target.merchantAccountPda, NATIVE_SOL_MINT),
+        customerRecord: customerRecordPda(target.merchantAccountPda, payer.publicKey),
+        payer: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+    return receipt;
+  }
+
+  describe("config", () => {
+    it("Initializes the config with the provider wallet as admin", async () => {
+      const config = await program.account.config.fetch(configPda);
+
+      assert.equal(config.admin.toString(), payer.publicKey.toString(), "Admin should be the upgrade authority");
+      assert.equal(config.treasury.toString(), treasury.publicKey.toString(), "Treasury should match");
+      assert.equal(config.registrationFee.toNumber(), REGISTRATION_FEE, "Registration fee should match");
+      assert.equal(config.protocolFeeBps, PROTOCOL_FEE_BPS, "Protocol fee should match");
+    });
+
+    it("Rejects a second config initialization", async () => {
+      const [programDataPda] = PublicKey.findProgramAddressSync(
+        [program.programId.toBuffer()],
+        BPF_LOADER_UPGRADEABLE_ID
+      );
+
+      try {
+        await program.methods
+          .initializeConfig(payer.publicKey, new anchor.BN(0), 0, new anchor.BN(0), 0, new anchor.BN(0), new anchor.BN(0), 0)
+          .accounts({
+            config: configPda,
+            admin: payer.publicKey,
+            program: program.programId,
+            programData: programDataPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        assert.fail("Should not allow re-initializing the config");
+      } catch (error: any) {
+        assert.include(error.toString().toLowerCase(), "already in use");
+      }
+    });
+
+    it("Lets the admin update the registration fee", async () => {
+      await program.methods
+        .updateConfig(null, null, new anchor.BN(REGISTRATION_FEE * 2), null, null, null, null, null, null)
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc();
+
+      let config = await program.account.config.fetch(configPda);
+      assert.equal(config.registrationFee.toNumber(), REGISTRATION_FEE * 2);
+
+      // Restore the fee for the registration tests
+      await program.methods
+        .updateConfig(null, null, new anchor.BN(REGISTRATION_FEE), null, null, null, null, null, null)
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc();
+
+      config = await program.account.config.fetch(configPda);
+      assert.equal(config.registrationFee.toNumber(), REGISTRATION_FEE);
+    });
+
+    it("Rejects config updates from non-admin", async () => {
+      const attacker = Keypair.generate();
+
+      try {
+        await program.methods
+          .updateConfig(null, attacker.publicKey, null, null, null, null, null, null, null)
+          .accounts({ config: configPda, admin: attacker.publicKey })
+          .signers([attacker])
+          .rpc();
+
+        assert.fail("Non-admin should not update the config");
+      } catch (error: any) {
+        assert.include(error.toString(), "Unauthorized");
+      }
+    });
+
+    it("Rejects protocol fee above 10,000 bps", async () => {
+      try {
+        await program.methods
+          .updateConfig(null, null, null, 10_001, null, null, null, null, null)
+          .accounts({ config: configPda, admin: payer.publicKey })
+          .rpc();
+
+        assert.fail("Should reject fee above 100%");
+      } catch (error: any) {
+        assert.include(error.toString(), "InvalidFeeBps");
+      }
+    });
+  });
+
+  describe("verifier registry", () => {
+    const rogue = Keypair.generate();
+    let roguePda: PublicKey;
+
+    before(async () => {
+      const airdropSig = await provider.connection.requestAirdrop(
+        rogue.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL
+      );
+      await provider.connection.confirmTransaction(airdropSig);
+
+      [roguePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("verifier"), rogue.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    it("Rejects location proofs from an unregistered signer", async () => {
+      const { merchantId: rogueMerchantId, merchantAccountPda: rogueMerchantPda } =
+        await registerTestMerchant("Rogue Target");
+      const [rogueProofPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof"), Buffer.from(rogueMerchantId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .createLocationProof(new anchor.BN(validLat), new anchor.BN(validLng), rogueMerchantId)
+          .accounts({
+            config: configPda,
+            proof: rogueProofPda,
+            merchantAccount: rogueMerchantPda,
+            verifier: roguePda,
+            payer: rogue.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([rogue])
+          .rpc();
+
+        assert.fail("Unregistered signer should not create proofs");
+      } catch (error: any) {
+        assert.include(error.toString(), "AccountNotInitialized");
+      }
+    });
+
+    it("Rejects verifier registration by non-admin", async () => {
+      try {
+        await program.methods
+          .addVerifier(rogue.publicKey)
+          .accounts({
+            config: configPda,
+            verifierAccount: roguePda,
+            admin: rogue.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([rogue])
+          .rpc();
+
+        assert.fail("Non-admin should not add verifiers");
+      } catch (error: any) {
+        assert.include(error.toString(), "Unauthorized");
+      }
+    });
+
+    it("Blocks suspended verifiers and allows reinstating them", async () => {
+      await program.methods
+        .addVerifier(rogue.publicKey)
+        .accounts({
+          config: configPda,
+          verifierAccount: roguePda,
+          admin: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .suspendVerifier(true)
+        .accounts({ config: configPda, verifierAccount: roguePda, admin: payer.publicKey })
+        .rpc();
+
+      const { merchantId: suspendedMerchantId, merchantAccountPda: suspendedMerchantPda } =
+        await registerTestMerchant("Suspended Target");
+      const [suspendedProofPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof"), Buffer.from(suspendedMerchantId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .createLocationProof(new anchor.BN(validLat), new anchor.BN(validLng), suspendedMerchantId)
+          .accounts({
+            config: configPda,
+            proof: suspendedProofPda,
+            merchantAccount: suspendedMerchantPda,
+            verifier: roguePda,
+            payer: rogue.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([rogue])
+          .rpc();
+
+        assert.fail("Suspended verifier should not create proofs");
+      } catch (error: any) {
+        assert.include(error.toString(), "VerifierSuspended");
+      }
+
+      await program.methods
+        .suspendVerifier(false)
+        .accounts({ config: configPda, verifierAccount: roguePda, admin: payer.publicKey })
+        .rpc();
+
+      const verifierAccount = await program.account.verifier.fetch(roguePda);
+      assert.equal(verifierAccount.isActive, true, "Verifier should be reinstated");
+    });
+
+    it("Removes a verifier and closes its registry entry", async () => {
+      await program.methods
+        .removeVerifier()
+        .accounts({ config: configPda, verifierAccount: roguePda, admin: payer.publicKey })
+        .rpc();
+
+      const info = await provider.connection.getAccountInfo(roguePda);
+      assert.isNull(info, "Verifier account should be closed");
+    });
+  });
+
   describe("create_location_proof", () => {
     it("Creates a location proof with valid coordinates", async () => {
       const tx = await program.methods
@@ -39,7 +447,10 @@ describe("nearme_contract - Location Proof Tests", () => {
           merchantId
         )
         .accounts({
+          config: configPda,
           proof: proofPda,
+          merchantAccount: merchantAccountPda,
+          verifier: verifierPda,
           payer: payer.publicKey,
           systemProgram: SystemProgram.programId,
         })
@@ -65,8 +476,27 @@ describe("nearme_contract - Location Proof Tests", () => {
         proofAccount.verifiedAt.toNumber() > 0,
         "Timestamp should be set"
       );
+      assert.equal(
+        proofAccount.verifier.toString(),
+        payer.publicKey.toString(),
+        "Verifier should be recorded"
+      );
+      assert.equal(
+        proofAccount.merchantAccount.toString(),
+        merchantAccountPda.toString(),
+        "Proof should point to the merchant account"
+      );
       assert.equal(proofAccount.bump, proofBump, "Bump should match");
 
+      const merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+      assert.equal(merchantAccount.merchantId, merchantId, "Merchant ID should be stored");
+      assert.equal(
+        merchantAccount.locationProof.toString(),
+        proofPda.toString(),
+        "Merchant account should link to the proof"
+      );
+      assert.equal(merchantAccount.locationVerified, true, "Merchant should be location verified");
+
       console.log("Location Proof created successfully:");
       console.log("  Latitude:", proofAccount.lat.toNumber() / 1_000_000);
       console.log("  Longitude:", proofAccount.lng.toNumber() / 1_000_000);
@@ -82,7 +512,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             merchantId
           )
           .accounts({
+            config: configPda,
             proof: proofPda,
+            merchantAccount: merchantAccountPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -100,7 +533,8 @@ describe("nearme_contract - Location Proof Tests", () => {
     });
 
     it("Rejects invalid latitude (too high)", async () => {
-      const invalidMerchantId = "invalid_lat_high_" + Date.now();
+      const { merchantId: invalidMerchantId, merchantAccountPda: invalidMerchantPda } =
+        await registerTestMerchant("Invalid Lat High");
       const [invalidProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(invalidMerchantId)],
         program.programId
@@ -114,7 +548,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             invalidMerchantId
           )
           .accounts({
+            config: configPda,
             proof: invalidProofPda,
+            merchantAccount: invalidMerchantPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -131,7 +568,8 @@ describe("nearme_contract - Location Proof Tests", () => {
     });
 
     it("Rejects invalid latitude (too low)", async () => {
-      const invalidMerchantId = "invalid_lat_low_" + Date.now();
+      const { merchantId: invalidMerchantId, merchantAccountPda: invalidMerchantPda } =
+        await registerTestMerchant("Invalid Lat Low");
       const [invalidProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(invalidMerchantId)],
         program.programId
@@ -145,7 +583,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             invalidMerchantId
           )
           .accounts({
+            config: configPda,
             proof: invalidProofPda,
+            merchantAccount: invalidMerchantPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -162,7 +603,8 @@ describe("nearme_contract - Location Proof Tests", () => {
     });
 
     it("Rejects invalid longitude (too high)", async () => {
-      const invalidMerchantId = "invalid_lng_high_" + Date.now();
+      const { merchantId: invalidMerchantId, merchantAccountPda: invalidMerchantPda } =
+        await registerTestMerchant("Invalid Lng High");
       const [invalidProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(invalidMerchantId)],
         program.programId
@@ -176,7 +618,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             invalidMerchantId
           )
           .accounts({
+            config: configPda,
             proof: invalidProofPda,
+            merchantAccount: invalidMerchantPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -193,7 +638,8 @@ describe("nearme_contract - Location Proof Tests", () => {
     });
 
     it("Rejects invalid longitude (too low)", async () => {
-      const invalidMerchantId = "invalid_lng_low_" + Date.now();
+      const { merchantId: invalidMerchantId, merchantAccountPda: invalidMerchantPda } =
+        await registerTestMerchant("Invalid Lng Low");
       const [invalidProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(invalidMerchantId)],
         program.programId
@@ -207,7 +653,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             invalidMerchantId
           )
           .accounts({
+            config: configPda,
             proof: invalidProofPda,
+            merchantAccount: invalidMerchantPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -223,6 +672,86 @@ describe("nearme_contract - Location Proof Tests", () => {
       }
     });
 
+    it("Rejects a proof whose merchant ID does not match the merchant account", async () => {
+      const { merchantAccountPda: otherMerchantPda } = await registerTestMerchant("Other Merchant");
+      const strayMerchantId = "stray_" + Date.now();
+      const [strayProofPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof"), Buffer.from(strayMerchantId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .createLocationProof(new anchor.BN(validLat), new anchor.BN(validLng), strayMerchantId)
+          .accounts({
+            config: configPda,
+            proof: strayProofPda,
+            merchantAccount: otherMerchantPda,
+            verifier: verifierPda,
+            payer: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        assert.fail("Should require a registered merchant with the same ID");
+      } catch (error: any) {
+        assert.include(error.toString(), "MerchantIdMismatch");
+      }
+    });
+
+    it("Rejects a proof outside the configured radius", async () => {
+      const { merchantId: farMerchantId, merchantAccountPda: farMerchantPda } =
+        await registerTestMerchant("Far Away Store");
+      const [farProofPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof"), Buffer.from(farMerchantId)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          // ~1.1 km north of the registered location
+          .createLocationProof(new anchor.BN(validLat + 10_000), new anchor.BN(validLng), farMerchantId)
+          .accounts({
+            config: configPda,
+            proof: farProofPda,
+            merchantAccount: farMerchantPda,
+            verifier: verifierPda,
+            payer: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        assert.fail("Should reject a proof far from the registered location");
+      } catch (error: any) {
+        assert.include(error.toString(), "LocationTooFar");
+      }
+    });
+
+    it("Accepts a proof within the configured radius", async () => {
+      const { merchantId: nearMerchantId, merchantAccountPda: nearMerchantPda } =
+        await registerTestMerchant("Nearby Store");
+      const [nearProofPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("proof"), Buffer.from(nearMerchantId)],
+        program.programId
+      );
+
+      // ~55 m north of the registered location
+      await program.methods
+        .createLocationProof(new anchor.BN(validLat + 500), new anchor.BN(validLng), nearMerchantId)
+        .accounts({
+          config: configPda,
+          proof: nearProofPda,
+          merchantAccount: nearMerchantPda,
+          verifier: verifierPda,
+          payer: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const merchantAccount = await program.account.merchantAccount.fetch(nearMerchantPda);
+      assert.equal(merchantAccount.locationVerified, true);
+    });
+
     it("Rejects merchant ID that's too long", async () => {
       const longMerchantId = "a".repeat(33); // 33 characters - too long
       const [invalidProofPda] = await PublicKey.findProgramAddress(
@@ -238,7 +767,10 @@ describe("nearme_contract - Location Proof Tests", () => {
             longMerchantId
           )
           .accounts({
+            config: configPda,
             proof: invalidProofPda,
+            merchantAccount: merchantAccountPda,
+            verifier: verifierPda,
             payer: payer.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -255,16 +787,17 @@ describe("nearme_contract - Location Proof Tests", () => {
     });
 
     it("Accepts extreme but valid coordinates", async () => {
-      const extremeMerchantId = "extreme_coords_" + Date.now();
+      // North Pole: 90°N, 0°E
+      const extremeLat = 90_000_000;
+      const extremeLng = 0;
+
+      const { merchantId: extremeMerchantId, merchantAccountPda: extremeMerchantPda } =
+        await registerTestMerchant("North Pole Outpost", extremeLat, extremeLng);
       const [extremeProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(extremeMerchantId)],
         program.programId
       );
 
-      // North Pole: 90°N, 0°E
-      const extremeLat = 90_000_000;
-      const extremeLng = 0;
-
       await program.methods
         .createLocationProof(
           new anchor.BN(extremeLat),
@@ -272,7 +805,10 @@ describe("nearme_contract - Location Proof Tests", () => {
           extremeMerchantId
         )
         .accounts({
+          config: configPda,
           proof: extremeProofPda,
+          merchantAccount: extremeMerchantPda,
+          verifier: verifierPda,
           payer: payer.publicKey,
           systemProgram: SystemProgram.programId,
         })
@@ -286,11 +822,13 @@ describe("nearme_contract - Location Proof Tests", () => {
 
   describe("close_location_proof", () => {
     let closeTestMerchantId: string;
+    let closeTestMerchantPda: PublicKey;
     let closeTestProofPda: PublicKey;
 
     before(async () => {
       // Create a proof that we can close
-      closeTestMerchantId = "close_test_" + Date.now();
+      ({ merchantId: closeTestMerchantId, merchantAccountPda: closeTestMerchantPda } =
+        await registerTestMerchant("Close Test Merchant"));
       [closeTestProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(closeTestMerchantId)],
         program.programId
@@ -303,20 +841,51 @@ describe("nearme_contract - Location Proof Tests", () => {
           closeTestMerchantId
         )
         .accounts({
+          config: configPda,
           proof: closeTestProofPda,
+          merchantAccount: closeTestMerchantPda,
+          verifier: verifierPda,
           payer: payer.publicKey,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
     });
 
+    it("Rejects closing by a wallet that is neither verifier nor admin", async () => {
+      const stranger = Keypair.generate();
+      const airdropSig = await provider.connection.requestAirdrop(
+        stranger.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL
+      );
+      await provider.connection.confirmTransaction(airdropSig);
+
+      try {
+        await program.methods
+          .closeLocationProof(closeTestMerchantId, 1)
+          .accounts({
+            config: configPda,
+            proof: closeTestProofPda,
+            merchantAccount: closeTestMerchantPda,
+            authority: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc();
+
+        assert.fail("Stranger should not close the proof");
+      } catch (error: any) {
+        assert.include(error.toString(), "Unauthorized");
+      }
+    });
+
     it("Closes a location proof account", async () => {
       const balanceBefore = await provider.connection.getBalance(payer.publicKey);
 
       await program.methods
-        .closeLocationProof()
+        .closeLocationProof(closeTestMerchantId, 1)
         .accounts({
+          config: configPda,
           proof: closeTestProofPda,
+          merchantAccount: closeTestMerchantPda,
           authority: payer.publicKey,
         })
         .rpc();
@@ -344,7 +913,8 @@ describe("nearme_contract - Location Proof Tests", () => {
 
   describe("Event emission", () => {
     it("Emits LocationVerifiedEvent on creation", async () => {
-      const eventMerchantId = "event_test_" + Date.now();
+      const { merchantId: eventMerchantId, merchantAccountPda: eventMerchantPda } =
+        await registerTestMerchant("Event Test Merchant");
       const [eventProofPda] = await PublicKey.findProgramAddress(
         [Buffer.from("proof"), Buffer.from(eventMerchantId)],
         program.programId
@@ -357,7 +927,10 @@ describe("nearme_contract - Location Proof Tests", () => {
           eventMerchantId
         )
         .accounts({
+          config: configPda,
           proof: eventProofPda,
+          merchantAccount: eventMerchantPda,
+          verifier: verifierPda,
           payer: payer.publicKey,
           systemProgram: SystemProgram.programId,
         })
@@ -406,14 +979,7 @@ describe("nearme_contract - Location Proof Tests", () => {
   });
 
   describe("Merchant Registration Tests", () => {
-    // Registration fee in lamports (0.01 SOL)
-    const REGISTRATION_FEE = 10_000_000;
-
-    // Treasury wallet (use a new keypair for testing)
-    let treasury: Keypair;
-
     before(() => {
-      treasury = Keypair.generate();
       console.log("\n=== Merchant Registration Test Setup ===");
       console.log("Treasury wallet:", treasury.publicKey.toString());
     });
@@ -447,7 +1013,9 @@ describe("nearme_contract - Location Proof Tests", () => {
           .registerMerchant(merchantId, businessName, lat, lng)
           .accounts({
             merchantAccount: merchantAccountPda,
+            merchantStats: statsPda(merchantAccountPda),
             merchant: merchant.publicKey,
+            config: configPda,
             treasury: treasury.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -515,7 +1083,9 @@ describe("nearme_contract - Location Proof Tests", () => {
           .registerMerchant(merchantId, businessName, lat, lng)
           .accounts({
             merchantAccount: merchantAccountPda,
+            merchantStats: statsPda(merchantAccountPda),
             merchant: merchant.publicKey,
+            config: configPda,
             treasury: treasury.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -528,7 +1098,9 @@ describe("nearme_contract - Location Proof Tests", () => {
             .registerMerchant(merchantId + "_2", "New Name", lat, lng)
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: merchant.publicKey,
+              config: configPda,
               treasury: treasury.publicKey,
               systemProgram: SystemProgram.programId,
             })
@@ -546,10 +1118,9 @@ describe("nearme_contract - Location Proof Tests", () => {
         }
       });
 
-      it("Rejects empty business name", async () => {
+      it("Rejects a treasury that does not match the config", async () => {
         const merchant = Keypair.generate();
-        const merchantId = "empty_name_test_" + Date.now();
-        const emptyName = "";
+        const merchantId = "fake_treasury_" + Date.now();
         const lat = new anchor.BN(40_712_800);
         const lng = new anchor.BN(-74_006_000);
 
@@ -566,95 +1137,211 @@ describe("nearme_contract - Location Proof Tests", () => {
 
         try {
           await program.methods
-            .registerMerchant(merchantId, emptyName, lat, lng)
+            .registerMerchant(merchantId, "Self Paying Store", lat, lng)
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: merchant.publicKey,
-              treasury: treasury.publicKey,
+              config: configPda,
+              treasury: merchant.publicKey, // try to pay the fee to themselves
               systemProgram: SystemProgram.programId,
             })
             .signers([merchant])
             .rpc();
 
-          assert.fail("Should reject empty business name");
+          assert.fail("Should reject a treasury other than the configured one");
         } catch (error: any) {
           assert.include(
             error.toString(),
-            "InvalidBusinessName",
-            "Should fail with InvalidBusinessName error"
+            "InvalidTreasury",
+            "Should fail with InvalidTreasury error"
           );
-          console.log("✅ Empty business name correctly rejected");
+          console.log("✅ Fee redirection to a custom treasury correctly rejected");
         }
       });
 
-      it("Rejects business name that's too long", async () => {
+      it("Rejects an empty merchant ID", async () => {
         const merchant = Keypair.generate();
-        const merchantId = "long_name_test_" + Date.now();
-        const longName = "a".repeat(65); // 65 characters - exceeds 64 limit
-        const lat = new anchor.BN(40_712_800);
-        const lng = new anchor.BN(-74_006_000);
-
         const airdropSig = await provider.connection.requestAirdrop(
           merchant.publicKey,
           2 * anchor.web3.LAMPORTS_PER_SOL
         );
         await provider.connection.confirmTransaction(airdropSig);
 
-        const [merchantAccountPda] = await PublicKey.findProgramAddress(
+        const [merchantAccountPda] = PublicKey.findProgramAddressSync(
           [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
           program.programId
         );
 
         try {
           await program.methods
-            .registerMerchant(merchantId, longName, lat, lng)
+            .registerMerchant("", "Nameless Store", new anchor.BN(validLat), new anchor.BN(validLng))
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: merchant.publicKey,
+              config: configPda,
               treasury: treasury.publicKey,
               systemProgram: SystemProgram.programId,
             })
             .signers([merchant])
             .rpc();
 
-          assert.fail("Should reject long business name");
+          assert.fail("Should reject an empty merchant ID");
         } catch (error: any) {
-          assert.include(
-            error.toString(),
-            "InvalidBusinessName",
-            "Should fail with InvalidBusinessName error"
-          );
-          console.log("✅ Long business name correctly rejected");
+          assert.include(error.toString(), "EmptyMerchantId");
         }
       });
 
-      it("Accepts maximum length business name (64 chars)", async () => {
-        const merchant = Keypair.generate();
-        const merchantId = "max_name_test_" + Date.now();
-        const maxName = "a".repeat(64); // Exactly 64 characters
-        const lat = new anchor.BN(40_712_800);
-        const lng = new anchor.BN(-74_006_000);
-
+      it("Rejects a merchant ID already held by another merchant", async () => {
+        const { merchantId: takenId } = await registerTestMerchant("Original Store");
+        const squatter = Keypair.generate();
         const airdropSig = await provider.connection.requestAirdrop(
-          merchant.publicKey,
+          squatter.publicKey,
           2 * anchor.web3.LAMPORTS_PER_SOL
         );
         await provider.connection.confirmTransaction(airdropSig);
 
-        const [merchantAccountPda] = await PublicKey.findProgramAddress(
-          [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
+        const [squatterPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("merchant"), squatter.publicKey.toBuffer()],
           program.programId
         );
 
-        await program.methods
-          .registerMerchant(merchantId, maxName, lat, lng)
-          .accounts({
-            merchantAccount: merchantAccountPda,
-            merchant: merchant.publicKey,
-            treasury: treasury.publicKey,
-            systemProgram: SystemProgram.programId,
-          })
-          .signers([merchant])
+        try {
+          await program.methods
+            .registerMerchant(takenId, "Squatter Store", new anchor.BN(validLat), new anchor.BN(validLng))
+            .accounts({
+              merchantAccount: squatterPda,
+              merchantStats: statsPda(squatterPda),
+              merchant: squatter.publicKey,
+              config: configPda,
+              treasury: treasury.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([squatter])
+            .rpc();
+
+          assert.fail("Merchant IDs must be unique");
+        } catch (error: any) {
+          assert.include(error.toString().toLowerCase(), "already in use");
+        }
+      });
+
+      it("Rejects empty business name", async () => {
+        const merchant = Keypair.generate();
+        const merchantId = "empty_name_test_" + Date.now();
+        const emptyName = "";
+        const lat = new anchor.BN(40_712_800);
+        const lng = new anchor.BN(-74_006_000);
+
+        const airdropSig = await provider.connection.requestAirdrop(
+          merchant.publicKey,
+          2 * anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(airdropSig);
+
+        const [merchantAccountPda] = await PublicKey.findProgramAddress(
+          [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
+          program.programId
+        );
+
+        try {
+          await program.methods
+            .registerMerchant(merchantId, emptyName, lat, lng)
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
+              merchant: merchant.publicKey,
+              config: configPda,
+              treasury: treasury.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Should reject empty business name");
+        } catch (error: any) {
+          assert.include(
+            error.toString(),
+            "InvalidBusinessName",
+            "Should fail with InvalidBusinessName error"
+          );
+          console.log("✅ Empty business name correctly rejected");
+        }
+      });
+
+      it("Rejects business name that's too long", async () => {
+        const merchant = Keypair.generate();
+        const merchantId = "long_name_test_" + Date.now();
+        const longName = "a".repeat(65); // 65 characters - exceeds 64 limit
+        const lat = new anchor.BN(40_712_800);
+        const lng = new anchor.BN(-74_006_000);
+
+        const airdropSig = await provider.connection.requestAirdrop(
+          merchant.publicKey,
+          2 * anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(airdropSig);
+
+        const [merchantAccountPda] = await PublicKey.findProgramAddress(
+          [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
+          program.programId
+        );
+
+        try {
+          await program.methods
+            .registerMerchant(merchantId, longName, lat, lng)
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
+              merchant: merchant.publicKey,
+              config: configPda,
+              treasury: treasury.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Should reject long business name");
+        } catch (error: any) {
+          assert.include(
+            error.toString(),
+            "InvalidBusinessName",
+            "Should fail with InvalidBusinessName error"
+          );
+          console.log("✅ Long business name correctly rejected");
+        }
+      });
+
+      it("Accepts maximum length business name (64 chars)", async () => {
+        const merchant = Keypair.generate();
+        const merchantId = "max_name_test_" + Date.now();
+        const maxName = "a".repeat(64); // Exactly 64 characters
+        const lat = new anchor.BN(40_712_800);
+        const lng = new anchor.BN(-74_006_000);
+
+        const airdropSig = await provider.connection.requestAirdrop(
+          merchant.publicKey,
+          2 * anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(airdropSig);
+
+        const [merchantAccountPda] = await PublicKey.findProgramAddress(
+          [Buffer.from("merchant"), merchant.publicKey.toBuffer()],
+          program.programId
+        );
+
+        await program.methods
+          .registerMerchant(merchantId, maxName, lat, lng)
+          .accounts({
+            merchantAccount: merchantAccountPda,
+            merchantStats: statsPda(merchantAccountPda),
+            merchant: merchant.publicKey,
+            config: configPda,
+            treasury: treasury.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([merchant])
           .rpc();
 
         const merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
@@ -686,7 +1373,9 @@ describe("nearme_contract - Location Proof Tests", () => {
             .registerMerchant(merchantId, businessName, invalidLat, validLng)
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: merchant.publicKey,
+              config: configPda,
               treasury: treasury.publicKey,
               systemProgram: SystemProgram.programId,
             })
@@ -728,7 +1417,9 @@ describe("nearme_contract - Location Proof Tests", () => {
             .registerMerchant(merchantId, businessName, lat, lng)
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: poorMerchant.publicKey,
+              config: configPda,
               treasury: treasury.publicKey,
               systemProgram: SystemProgram.programId,
             })
@@ -779,7 +1470,9 @@ describe("nearme_contract - Location Proof Tests", () => {
             .registerMerchant(merchantId, businessName, lat, lng)
             .accounts({
               merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
               merchant: merchant.publicKey,
+              config: configPda,
               treasury: treasury.publicKey,
               systemProgram: SystemProgram.programId,
             })
@@ -869,7 +1562,9 @@ describe("nearme_contract - Location Proof Tests", () => {
           .registerMerchant(merchantId, businessName, lat, lng)
           .accounts({
             merchantAccount: merchantAccountPda,
+            merchantStats: statsPda(merchantAccountPda),
             merchant: merchant.publicKey,
+            config: configPda,
             treasury: treasury.publicKey,
             systemProgram: SystemProgram.programId,
           })
@@ -881,5 +1576,3268 @@ describe("nearme_contract - Location Proof Tests", () => {
         console.log("✅ MerchantRegisteredEvent emitted on registration");
       });
     });
+
+    describe("update_merchant_profile", () => {
+      it("Updates business name and location", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Old Name");
+
+        await program.methods
+          .updateMerchantProfile("New Name", new anchor.BN(40_712_800), null)
+          .accounts({
+            merchantAccount: merchantAccountPda,
+            authority: merchant.publicKey,
+            delegate: null,
+          })
+          .signers([merchant])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.businessName, "New Name");
+        assert.equal(merchantAccount.lat.toNumber(), 40_712_800);
+        assert.equal(merchantAccount.lng.toNumber(), validLng, "Longitude should be unchanged");
+      });
+
+      it("Clears the verified badge when the merchant relocates", async () => {
+        const { merchant, merchantId: movingId, merchantAccountPda: movingPda } =
+          await registerTestMerchant("Moving Store");
+        const [movingProofPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("proof"), Buffer.from(movingId)],
+          program.programId
+        );
+
+        await program.methods
+          .createLocationProof(new anchor.BN(validLat), new anchor.BN(validLng), movingId)
+          .accounts({
+            config: configPda,
+            proof: movingProofPda,
+            merchantAccount: movingPda,
+            verifier: verifierPda,
+            payer: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        await program.methods
+          .updateMerchantProfile(null, new anchor.BN(40_712_800), new anchor.BN(-74_006_000))
+          .accounts({
+            merchantAccount: movingPda,
+            authority: merchant.publicKey,
+            delegate: null,
+          })
+          .signers([merchant])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(movingPda);
+        assert.equal(merchantAccount.locationVerified, false, "Relocation should clear the badge");
+      });
+
+      it("Re-validates new coordinates", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Bounds Store");
+
+        try {
+          await program.methods
+            .updateMerchantProfile(null, null, new anchor.BN(181_000_000))
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              authority: merchant.publicKey,
+              delegate: null,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Should reject invalid longitude");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidLongitude");
+        }
+      });
+
+      it("Rejects updates from another wallet", async () => {
+        const { merchantAccountPda } = await registerTestMerchant("Victim Store");
+        const { merchant: attacker } = await registerTestMerchant("Attacker Store");
+
+        try {
+          await program.methods
+            .updateMerchantProfile("Hijacked", null, null)
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              authority: attacker.publicKey,
+              delegate: null,
+            })
+            .signers([attacker])
+            .rpc();
+
+          assert.fail("Another wallet should not update the profile");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+
+      it("Refuses to migrate an account that already uses the current layout", async () => {
+        const { merchant, merchantId, merchantAccountPda } = await registerTestMerchant("Current Layout Store");
+
+        try {
+          await program.methods
+            .migrateMerchantAccount(merchantId)
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              merchantIdReservation: merchantIdPda(merchantId),
+              merchantStats: statsPda(merchantAccountPda),
+              merchant: merchant.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Up-to-date accounts should not be migrated");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantAccountUpToDate");
+        }
+      });
+    });
+
+    describe("merchant status", () => {
+      it("Lets the merchant deactivate and reactivate", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Seasonal Store");
+
+        await program.methods
+          .deactivateMerchant()
+          .accounts({ merchantAccount: merchantAccountPda, authority: merchant.publicKey, delegate: null })
+          .signers([merchant])
+          .rpc();
+        let merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.isActive, false, "Merchant should be inactive");
+
+        await program.methods
+          .reactivateMerchant()
+          .accounts({ merchantAccount: merchantAccountPda, authority: merchant.publicKey, delegate: null })
+          .signers([merchant])
+          .rpc();
+        merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.isActive, true, "Merchant should be active again");
+      });
+
+      it("Prevents a suspended merchant from reactivating until the admin lifts it", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Banned Store");
+
+        await program.methods
+          .suspendMerchant(3, null)
+          .accounts({ config: configPda, merchantAccount: merchantAccountPda, admin: payer.publicKey })
+          .rpc();
+
+        let merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.isActive, false);
+        assert.equal(merchantAccount.isSuspended, true);
+        assert.equal(merchantAccount.suspensionReason, 3);
+
+        try {
+          await program.methods
+            .reactivateMerchant()
+            .accounts({ merchantAccount: merchantAccountPda, authority: merchant.publicKey, delegate: null })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Suspended merchant should not reactivate");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantSuspended");
+        }
+
+        await program.methods
+          .liftMerchantSuspension()
+          .accounts({ config: configPda, merchantAccount: merchantAccountPda, admin: payer.publicKey })
+          .rpc();
+
+        await program.methods
+          .reactivateMerchant()
+          .accounts({ merchantAccount: merchantAccountPda, authority: merchant.publicKey, delegate: null })
+          .signers([merchant])
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.isActive, true);
+        assert.equal(merchantAccount.isSuspended, false);
+      });
+
+      it("Rejects suspension by non-admin", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Rival Target");
+
+        try {
+          await program.methods
+            .suspendMerchant(1, null)
+            .accounts({ config: configPda, merchantAccount: merchantAccountPda, admin: merchant.publicKey })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Non-admin should not suspend merchants");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+    });
+
+    describe("close_merchant", () => {
+      it("Closes the merchant account, refunds rent and allows re-registration", async () => {
+        const { merchant, merchantId, merchantAccountPda } = await registerTestMerchant("Closing Store");
+        const balanceBefore = await provider.connection.getBalance(merchant.publicKey);
+
+        await program.methods
+          .closeMerchant()
+          .accounts({
+            merchantAccount: merchantAccountPda,
+            merchantStats: statsPda(merchantAccountPda),
+            locationProof: null,
+            merchantIdReservation: merchantIdPda(merchantId),
+            merchant: merchant.publicKey,
+          })
+          .signers([merchant])
+          .rpc();
+
+        const info = await provider.connection.getAccountInfo(merchantAccountPda);
+        assert.isNull(info, "Merchant account should be closed");
+        assert.isNull(
+          await provider.connection.getAccountInfo(merchantIdPda(merchantId)),
+          "Merchant ID should be released"
+        );
+
+        const balanceAfter = await provider.connection.getBalance(merchant.publicKey);
+        assert.ok(balanceAfter > balanceBefore, "Rent should be returned to the merchant");
+
+        // The second registration lives at a new PDA so nothing keyed by the old one carries over
+        const generation = Buffer.alloc(4);
+        generation.writeUInt32LE(1);
+        const [reopenedPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("merchant"), merchant.publicKey.toBuffer(), generation],
+          program.programId
+        );
+        assert.notOk(reopenedPda.equals(merchantAccountPda));
+
+        await program.methods
+          .registerMerchant("reopened_" + Date.now(), "Reopened Store", new anchor.BN(validLat), new anchor.BN(validLng))
+          .accounts({
+            merchantAccount: reopenedPda,
+            merchantStats: statsPda(reopenedPda),
+            merchant: merchant.publicKey,
+            config: configPda,
+            treasury: treasury.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([merchant])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(reopenedPda);
+        assert.equal(merchantAccount.businessName, "Reopened Store");
+        assert.equal(merchantAccount.generation, 1);
+        assert.equal(merchantAccount.ratingCount, 0);
+      });
+
+      it("Refuses to close while suspended", async () => {
+        const { merchant, merchantId, merchantAccountPda } = await registerTestMerchant("Evading Store");
+
+        await program.methods
+          .suspendMerchant(2, null)
+          .accounts({ config: configPda, merchantAccount: merchantAccountPda, admin: payer.publicKey })
+          .rpc();
+
+        try {
+          await program.methods
+            .closeMerchant()
+            .accounts({
+              merchantAccount: merchantAccountPda,
+              merchantStats: statsPda(merchantAccountPda),
+              locationProof: null,
+              merchantIdReservation: merchantIdPda(merchantId),
+              merchant: merchant.publicKey,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Suspended merchant should not close to evade the ban");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantSuspended");
+        }
+      });
+
+      it("Refuses to close until the merchant's coupons are closed", async () => {
+        const { merchant, merchantId, merchantAccountPda } = await registerTestMerchant("Promo Store");
+        const couponId = new anchor.BN(1);
+        const [coupon] = PublicKey.findProgramAddressSync(
+          [Buffer.from("coupon"), merchantAccountPda.toBuffer(), couponId.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        );
+        const closeAccounts = {
+          merchantAccount: merchantAccountPda,
+          merchantStats: statsPda(merchantAccountPda),
+          locationProof: null,
+          merchantIdReservation: merchantIdPda(merchantId),
+          merchant: merchant.publicKey,
+        };
+
+        await program.methods
+          .createCoupon(couponId, NATIVE_SOL_MINT, { percentage: { bps: 500 } }, 0, 0, new anchor.BN(0), null, null)
+          .accounts({
+            merchantAccount: merchantAccountPda,
+            coupon,
+            authority: merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([merchant])
+          .rpc();
+
+        let merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.couponCount, 1);
+
+        try {
+          await program.methods.closeMerchant().accounts(closeAccounts).signers([merchant]).rpc();
+          assert.fail("Merchant with a live coupon should not close");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantHasCoupons");
+        }
+
+        await program.methods
+          .closeCoupon()
+          .accounts({ merchantAccount: merchantAccountPda, coupon, authority: merchant.publicKey, delegate: null })
+          .signers([merchant])
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.couponCount, 0);
+
+        await program.methods.closeMerchant().accounts(closeAccounts).signers([merchant]).rpc();
+        assert.isNull(await provider.connection.getAccountInfo(merchantAccountPda), "Merchant should be closed");
+      });
+
+      it("Closes the linked location proof with the merchant", async () => {
+        const { merchant, merchantId: linkedId, merchantAccountPda: linkedPda } =
+          await registerTestMerchant("Proven Store");
+        const [linkedProofPda] = PublicKey.findProgramAddressSync(
+          [Buffer.from("proof"), Buffer.from(linkedId)],
+          program.programId
+        );
+
+        await program.methods
+          .createLocationProof(new anchor.BN(validLat), new anchor.BN(validLng), linkedId)
+          .accounts({
+            config: configPda,
+            proof: linkedProofPda,
+            merchantAccount: linkedPda,
+            verifier: verifierPda,
+            payer: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        try {
+          await program.methods
+            .closeMerchant()
+            .accounts({
+              merchantAccount: linkedPda,
+              merchantStats: statsPda(linkedPda),
+              locationProof: null,
+              merchantIdReservation: merchantIdPda(linkedId),
+              merchant: merchant.publicKey,
+            })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Linked proof must be passed");
+        } catch (error: any) {
+          assert.include(error.toString(), "LocationProofMismatch");
+        }
+
+        await program.methods
+          .closeMerchant()
+          .accounts({
+            merchantAccount: linkedPda,
+            merchantStats: statsPda(linkedPda),
+            locationProof: linkedProofPda,
+            merchantIdReservation: merchantIdPda(linkedId),
+            merchant: merchant.publicKey,
+          })
+          .signers([merchant])
+          .rpc();
+
+        assert.isNull(await provider.connection.getAccountInfo(linkedPda), "Merchant should be closed");
+        assert.isNull(await provider.connection.getAccountInfo(linkedProofPda), "Proof should be closed");
+      });
+    });
+
+    describe("merchant ownership transfer", () => {
+      it("Moves the registration to a new wallet in two steps", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Sold Store");
+        const buyer = Keypair.generate();
+
+        await program.methods
+          .proposeMerchantTransfer(buyer.publicKey)
+          .accounts({ merchantAccount: merchantAccountPda, merchant: merchant.publicKey })
+          .signers([merchant])
+          .rpc();
+
+        let merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.pendingMerchant.toString(), buyer.publicKey.toString());
+
+        await program.methods
+          .acceptMerchantTransfer()
+          .accounts({ merchantAccount: merchantAccountPda, newMerchant: buyer.publicKey })
+          .signers([buyer])
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.equal(merchantAccount.merchant.toString(), buyer.publicKey.toString(), "Buyer should own the merchant");
+        assert.equal(
+          merchantAccount.registrationKey.toString(),
+          merchant.publicKey.toString(),
+          "PDA seed key should not change"
+        );
+        assert.isNull(merchantAccount.pendingMerchant);
+
+        // The previous owner can no longer act for the business
+        try {
+          await program.methods
+            .deactivateMerchant()
+            .accounts({ merchantAccount: merchantAccountPda, authority: merchant.publicKey, delegate: null })
+            .signers([merchant])
+            .rpc();
+
+          assert.fail("Previous owner should lose control");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+
+      it("Rejects acceptance by a wallet other than the proposed one", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Contested Store");
+        const buyer = Keypair.generate();
+        const thief = Keypair.generate();
+
+        await program.methods
+          .proposeMerchantTransfer(buyer.publicKey)
+          .accounts({ merchantAccount: merchantAccountPda, merchant: merchant.publicKey })
+          .signers([merchant])
+          .rpc();
+
+        try {
+          await program.methods
+            .acceptMerchantTransfer()
+            .accounts({ merchantAccount: merchantAccountPda, newMerchant: thief.publicKey })
+            .signers([thief])
+            .rpc();
+
+          assert.fail("Only the proposed wallet should accept");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+
+      it("Lets the proposed wallet decline the transfer", async () => {
+        const { merchant, merchantAccountPda } = await registerTestMerchant("Unwanted Store");
+        const buyer = Keypair.generate();
+
+        await program.methods
+          .proposeMerchantTransfer(buyer.publicKey)
+          .accounts({ merchantAccount: merchantAccountPda, merchant: merchant.publicKey })
+          .signers([merchant])
+          .rpc();
+
+        await program.methods
+          .cancelMerchantTransfer()
+          .accounts({ merchantAccount: merchantAccountPda, authority: buyer.publicKey })
+          .signers([buyer])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(merchantAccountPda);
+        assert.isNull(merchantAccount.pendingMerchant);
+        assert.equal(merchantAccount.merchant.toString(), merchant.publicKey.toString());
+      });
+    });
+  });
+
+  describe("Payment Tests", () => {
+    let shop: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+    const customer = Keypair.generate();
+
+    before(async () => {
+      shop = await registerTestMerchant("Payment Test Cafe");
+
+      const airdropSig = await provider.connection.requestAirdrop(
+        customer.publicKey,
+        5 * anchor.web3.LAMPORTS_PER_SOL
+      );
+      await provider.connection.confirmTransaction(airdropSig);
+    });
+
+    describe("pay_merchant_sol", () => {
+      it("Transfers lamports to the merchant and creates a receipt", async () => {
+        const amount = 250_000_000; // 0.25 SOL
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(shop.merchantAccountPda, reference);
+        const merchantBalanceBefore = await provider.connection.getBalance(shop.merchant.publicKey);
+
+        await program.methods
+          .payMerchantSol(new anchor.BN(amount), [...reference], new anchor.BN(0), false)
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            merchant: shop.merchant.publicKey,
+            treasury: treasury.publicKey,
+            receipt,
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        const protocolFee = (amount * PROTOCOL_FEE_BPS) / 10_000;
+        const merchantBalanceAfter = await provider.connection.getBalance(shop.merchant.publicKey);
+        assert.equal(
+          merchantBalanceAfter - merchantBalanceBefore,
+          amount - protocolFee,
+          "Merchant should receive the amount minus the protocol fee"
+        );
+
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.payer.toString(), customer.publicKey.toString());
+        assert.equal(receiptAccount.merchantAccount.toString(), shop.merchantAccountPda.toString());
+        assert.equal(receiptAccount.mint.toString(), PublicKey.default.toString(), "SOL receipts use the default mint");
+        assert.equal(receiptAccount.amount.toNumber(), amount);
+        assert.equal(receiptAccount.protocolFee.toNumber(), protocolFee);
+        assert.equal(receiptAccount.merchantAmount.toNumber(), amount - protocolFee);
+        assert.deepEqual([...receiptAccount.reference], [...reference]);
+      });
+
+      it("Rejects paying the same reference twice", async () => {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(shop.merchantAccountPda, reference);
+        const accounts = {
+          config: configPda,
+          merchantAccount: shop.merchantAccountPda,
+          merchantStats: statsPda(shop.merchantAccountPda),
+          mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+          customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+          merchant: shop.merchant.publicKey,
+          treasury: treasury.publicKey,
+          receipt,
+          payer: customer.publicKey,
+          systemProgram: SystemProgram.programId,
+        };
+
+        await program.methods
+          .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+          .accounts(accounts)
+          .signers([customer])
+          .rpc();
+
+        try {
+          await program.methods
+            .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+            .accounts(accounts)
+            .signers([customer])
+            .rpc();
+
+          assert.fail("Duplicate reference should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString().toLowerCase(), "already in use");
+        }
+      });
+
+      it("Rejects payments to an inactive merchant", async () => {
+        const closedShop = await registerTestMerchant("Closed Cafe");
+        await program.methods
+          .deactivateMerchant()
+          .accounts({ merchantAccount: closedShop.merchantAccountPda, authority: closedShop.merchant.publicKey, delegate: null })
+          .signers([closedShop.merchant])
+          .rpc();
+
+        const reference = Keypair.generate().publicKey.toBuffer();
+
+        try {
+          await program.methods
+            .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+            .accounts({
+              config: configPda,
+              merchantAccount: closedShop.merchantAccountPda,
+              merchantStats: statsPda(closedShop.merchantAccountPda),
+              mintStats: mintStatsPda(closedShop.merchantAccountPda, NATIVE_SOL_MINT),
+              customerRecord: customerRecordPda(closedShop.merchantAccountPda, customer.publicKey),
+              merchant: closedShop.merchant.publicKey,
+              treasury: treasury.publicKey,
+              receipt: receiptPda(closedShop.merchantAccountPda, reference),
+              payer: customer.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([customer])
+            .rpc();
+
+          assert.fail("Inactive merchant should not accept payments");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantInactive");
+        }
+      });
+
+      it("Rejects a zero amount", async () => {
+        const reference = Keypair.generate().publicKey.toBuffer();
+
+        try {
+          await program.methods
+            .payMerchantSol(new anchor.BN(0), [...reference], new anchor.BN(0), false)
+            .accounts({
+              config: configPda,
+              merchantAccount: shop.merchantAccountPda,
+              merchantStats: statsPda(shop.merchantAccountPda),
+              mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+              customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+              merchant: shop.merchant.publicKey,
+              treasury: treasury.publicKey,
+              receipt: receiptPda(shop.merchantAccountPda, reference),
+              payer: customer.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([customer])
+            .rpc();
+
+          assert.fail("Zero amount should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidAmount");
+        }
+      });
+    });
+
+    describe("pay_merchant_token", () => {
+      const DECIMALS = 6;
+
+      /**
+       * Create a mint under the given token program and fund the customer with it
+       */
+      async function createFundedMint(tokenProgram: PublicKey): Promise<{ mint: PublicKey; customerAta: PublicKey }> {
+        const mint = await createMint(
+          provider.connection,
+          payer.payer,
+          payer.publicKey,
+          null,
+          DECIMALS,
+          undefined,
+          undefined,
+          tokenProgram
+        );
+        const customerAta = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer.payer,
+          mint,
+          customer.publicKey,
+          false,
+          undefined,
+          undefined,
+          tokenProgram
+        );
+        await mintTo(
+          provider.connection,
+          payer.payer,
+          mint,
+          customerAta.address,
+          payer.publicKey,
+          1_000_000_000,
+          [],
+          undefined,
+          tokenProgram
+        );
+        return { mint, customerAta: customerAta.address };
+      }
+
+      async function payToken(mint: PublicKey, acceptedMint: PublicKey, customerAta: PublicKey, tokenProgram: PublicKey, amount: number, reference: Buffer) {
+        return program.methods
+          .payMerchantToken(new anchor.BN(amount), Array.from(reference))
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchant: shop.merchant.publicKey,
+            mint,
+            acceptedMint,
+            globalAcceptedMint: acceptedMintPda(configPda, mint),
+            payerTokenAccount: customerAta,
+            merchantTokenAccount: getAssociatedTokenAddressSync(mint, shop.merchant.publicKey, false, tokenProgram),
+            treasury: treasury.publicKey,
+            treasuryTokenAccount: getAssociatedTokenAddressSync(mint, treasury.publicKey, false, tokenProgram),
+            receipt: receiptPda(shop.merchantAccountPda, reference),
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, mint),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            tokenProgram,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+      }
+
+      it("Pays with a globally accepted Token mint and creates the merchant ATA", async () => {
+        const { mint, customerAta } = await createFundedMint(TOKEN_PROGRAM_ID);
+        const acceptedMint = acceptedMintPda(configPda, mint);
+
+        await program.methods
+          .addAcceptedMint(new anchor.BN(0), new anchor.BN(0))
+          .accounts({
+            config: configPda,
+            mint,
+            acceptedMint,
+            admin: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        const amount = 2_500_000;
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await payToken(mint, acceptedMint, customerAta, TOKEN_PROGRAM_ID, amount, reference);
+
+        const merchantAta = await getAccount(
+          provider.connection,
+          getAssociatedTokenAddressSync(mint, shop.merchant.publicKey),
+          undefined,
+          TOKEN_PROGRAM_ID
+        );
+        const protocolFee = (amount * PROTOCOL_FEE_BPS) / 10_000;
+        assert.equal(Number(merchantAta.amount), amount - protocolFee);
+
+        const treasuryAta = await getAccount(
+          provider.connection,
+          getAssociatedTokenAddressSync(mint, treasury.publicKey),
+          undefined,
+          TOKEN_PROGRAM_ID
+        );
+        assert.equal(Number(treasuryAta.amount), protocolFee);
+
+        const receipt = await program.account.paymentReceipt.fetch(receiptPda(shop.merchantAccountPda, reference));
+        assert.equal(receipt.mint.toString(), mint.toString());
+        assert.equal(receipt.amount.toNumber(), amount);
+        assert.equal(receipt.protocolFee.toNumber(), protocolFee);
+        assert.equal(receipt.payer.toString(), customer.publicKey.toString());
+      });
+
+      it("Pays with a merchant-accepted Token-2022 mint", async () => {
+        const { mint, customerAta } = await createFundedMint(TOKEN_2022_PROGRAM_ID);
+        const acceptedMint = acceptedMintPda(shop.merchantAccountPda, mint);
+
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        const amount = 1_000_000;
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await payToken(mint, acceptedMint, customerAta, TOKEN_2022_PROGRAM_ID, amount, reference);
+
+        // Paying again with an existing merchant ATA must also succeed
+        await payToken(mint, acceptedMint, customerAta, TOKEN_2022_PROGRAM_ID, amount, Keypair.generate().publicKey.toBuffer());
+
+        const merchantAta = await getAccount(
+          provider.connection,
+          getAssociatedTokenAddressSync(mint, shop.merchant.publicKey, false, TOKEN_2022_PROGRAM_ID),
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        );
+        assert.equal(Number(merchantAta.amount), 2 * (amount - (amount * PROTOCOL_FEE_BPS) / 10_000));
+      });
+
+      it("Caps fees on a merchant-listed mint with the mint's global cap", async () => {
+        const { mint, customerAta } = await createFundedMint(TOKEN_PROGRAM_ID);
+        const feeCap = 100;
+
+        await program.methods
+          .addAcceptedMint(new anchor.BN(feeCap), new anchor.BN(0))
+          .accounts({
+            config: configPda,
+            mint,
+            acceptedMint: acceptedMintPda(configPda, mint),
+            admin: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        const acceptedMint = acceptedMintPda(shop.merchantAccountPda, mint);
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await payToken(mint, acceptedMint, customerAta, TOKEN_PROGRAM_ID, 10_000_000, reference);
+
+        const receipt = await program.account.paymentReceipt.fetch(receiptPda(shop.merchantAccountPda, reference));
+        assert.equal(receipt.protocolFee.toNumber(), feeCap, "Merchant entries use the global cap");
+      });
+
+      it("Rejects a mint accepted only by another merchant", async () => {
+        const other = await registerTestMerchant("Other Token Shop");
+        const { mint, customerAta } = await createFundedMint(TOKEN_PROGRAM_ID);
+        const acceptedMint = acceptedMintPda(other.merchantAccountPda, mint);
+
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: other.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: other.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([other.merchant])
+          .rpc();
+
+        try {
+          await payToken(mint, acceptedMint, customerAta, TOKEN_PROGRAM_ID, 1_000, Keypair.generate().publicKey.toBuffer());
+          assert.fail("Should have failed with MintNotAccepted");
+        } catch (error) {
+          assert.include(error.toString(), "MintNotAccepted");
+        }
+      });
+
+      it("Stops accepting a mint after the admin removes it", async () => {
+        const { mint, customerAta } = await createFundedMint(TOKEN_PROGRAM_ID);
+        const acceptedMint = acceptedMintPda(configPda, mint);
+
+        await program.methods
+          .addAcceptedMint(new anchor.BN(0), new anchor.BN(0))
+          .accounts({
+            config: configPda,
+            mint,
+            acceptedMint,
+            admin: payer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        await program.methods
+          .removeAcceptedMint()
+          .accounts({ config: configPda, acceptedMint, admin: payer.publicKey })
+          .rpc();
+
+        try {
+          await payToken(mint, acceptedMint, customerAta, TOKEN_PROGRAM_ID, 1_000, Keypair.generate().publicKey.toBuffer());
+          assert.fail("Should have failed because the accepted-mint entry is closed");
+        } catch (error) {
+          assert.include(error.toString(), "AccountNotInitialized");
+        }
+      });
+    });
+
+    describe("payment statistics", () => {
+      it("Starts with zeroed stats at registration", async () => {
+        const fresh = await registerTestMerchant("Fresh Stats Cafe");
+        const stats = await program.account.merchantStats.fetch(statsPda(fresh.merchantAccountPda));
+
+        assert.equal(stats.merchantAccount.toString(), fresh.merchantAccountPda.toString());
+        assert.equal(stats.paymentCount.toNumber(), 0);
+        assert.equal(stats.uniquePayers.toNumber(), 0);
+        assert.equal(stats.lastPaymentAt.toNumber(), 0);
+      });
+
+      it("Counts payments, per-mint volume and unique payers", async () => {
+        const statsShop = await registerTestMerchant("Stats Cafe");
+        const secondCustomer = Keypair.generate();
+        const airdropSig = await provider.connection.requestAirdrop(
+          secondCustomer.publicKey,
+          anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(airdropSig);
+
+        await paySol(statsShop, customer, 1_000_000);
+        await paySol(statsShop, customer, 2_000_000);
+        await paySol(statsShop, secondCustomer, 3_000_000);
+
+        const stats = await program.account.merchantStats.fetch(statsPda(statsShop.merchantAccountPda));
+        assert.equal(stats.paymentCount.toNumber(), 3);
+        assert.equal(stats.uniquePayers.toNumber(), 2);
+        assert.isAbove(stats.lastPaymentAt.toNumber(), 0);
+
+        const mintStats = await program.account.merchantMintStats.fetch(
+          mintStatsPda(statsShop.merchantAccountPda, NATIVE_SOL_MINT)
+        );
+        assert.equal(mintStats.mint.toString(), NATIVE_SOL_MINT.toString());
+        assert.equal(mintStats.paymentCount.toNumber(), 3);
+        assert.equal(mintStats.volume.toNumber(), 6_000_000);
+
+        const record = await program.account.customerRecord.fetch(
+          customerRecordPda(statsShop.merchantAccountPda, customer.publicKey)
+        );
+        assert.equal(record.paymentCount.toNumber(), 2);
+        assert.equal(record.payer.toString(), customer.publicKey.toString());
+      });
+
+      it("Rejects a stats account belonging to another merchant", async () => {
+        const statsShop = await registerTestMerchant("Honest Stats Cafe");
+        const otherShop = await registerTestMerchant("Other Stats Cafe");
+        const reference = Keypair.generate().publicKey.toBuffer();
+
+        try {
+          await program.methods
+            .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+            .accounts({
+              config: configPda,
+              merchantAccount: statsShop.merchantAccountPda,
+              merchant: statsShop.merchant.publicKey,
+              treasury: treasury.publicKey,
+              receipt: receiptPda(statsShop.merchantAccountPda, reference),
+              merchantStats: statsPda(otherShop.merchantAccountPda),
+              mintStats: mintStatsPda(statsShop.merchantAccountPda, NATIVE_SOL_MINT),
+              customerRecord: customerRecordPda(statsShop.merchantAccountPda, customer.publicKey),
+              payer: customer.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([customer])
+            .rpc();
+
+          assert.fail("Stats of another merchant should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "ConstraintSeeds");
+        }
+      });
+    });
+
+    describe("protocol fee", () => {
+      it("Sends the protocol fee to the treasury", async () => {
+        const amount = 100_000_000;
+        const treasuryBefore = await provider.connection.getBalance(treasury.publicKey);
+
+        const receipt = await paySol(shop, customer, amount);
+
+        const treasuryAfter = await provider.connection.getBalance(treasury.publicKey);
+        const expectedFee = (amount * PROTOCOL_FEE_BPS) / 10_000;
+        assert.equal(treasuryAfter - treasuryBefore, expectedFee);
+
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.protocolFee.toNumber() + receiptAccount.merchantAmount.toNumber(), amount);
+      });
+
+      it("Applies a zero-bps override for promotional partners", async () => {
+        const partner = await registerTestMerchant("Promo Partner Cafe");
+
+        await program.methods
+          .setMerchantFeeOverride(0)
+          .accounts({ config: configPda, merchantAccount: partner.merchantAccountPda, admin: payer.publicKey })
+          .rpc();
+
+        const amount = 50_000_000;
+        const merchantBefore = await provider.connection.getBalance(partner.merchant.publicKey);
+        const receipt = await paySol(partner, customer, amount);
+        const merchantAfter = await provider.connection.getBalance(partner.merchant.publicKey);
+
+        assert.equal(merchantAfter - merchantBefore, amount, "Partner should receive the full amount");
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.protocolFee.toNumber(), 0);
+
+        // Clearing the override falls back to the config fee
+        await program.methods
+          .setMerchantFeeOverride(null)
+          .accounts({ config: configPda, merchantAccount: partner.merchantAccountPda, admin: payer.publicKey })
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(partner.merchantAccountPda);
+        assert.isNull(merchantAccount.feeBpsOverride);
+      });
+
+      it("Caps the protocol fee per payment", async () => {
+        const cap = 10_000;
+        await program.methods
+          .updateConfig(null, null, null, null, new anchor.BN(cap), null, null, null, null)
+          .accounts({ config: configPda, admin: payer.publicKey })
+          .rpc();
+
+        try {
+          const receipt = await paySol(shop, customer, 100_000_000);
+          const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+          assert.equal(receiptAccount.protocolFee.toNumber(), cap);
+          assert.equal(receiptAccount.merchantAmount.toNumber(), 100_000_000 - cap);
+        } finally {
+          await program.methods
+            .updateConfig(null, null, null, null, new anchor.BN(PROTOCOL_FEE_CAP), null, null, null, null)
+            .accounts({ config: configPda, admin: payer.publicKey })
+            .rpc();
+        }
+      });
+
+      it("Rejects an override above 100%", async () => {
+        try {
+          await program.methods
+            .setMerchantFeeOverride(10_001)
+            .accounts({ config: configPda, merchantAccount: shop.merchantAccountPda, admin: payer.publicKey })
+            .rpc();
+
+          assert.fail("Should reject fee above 100%");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidFeeBps");
+        }
+      });
+
+      it("Rejects fee overrides from non-admin", async () => {
+        try {
+          await program.methods
+            .setMerchantFeeOverride(0)
+            .accounts({ config: configPda, merchantAccount: shop.merchantAccountPda, admin: shop.merchant.publicKey })
+            .signers([shop.merchant])
+            .rpc();
+
+          assert.fail("Merchant should not set their own fee");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+    });
+
+    describe("payment requests", () => {
+      /**
+       * Derive the payment request PDA for a merchant account and a request ID
+       */
+      function requestPda(merchantAccountPda: PublicKey, requestId: anchor.BN): PublicKey {
+        return PublicKey.findProgramAddressSync(
+          [Buffer.from("payment_request"), merchantAccountPda.toBuffer(), requestId.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+      }
+
+      let nextRequestId = 1;
+
+      async function createRequest(
+        mint: PublicKey,
+        amount: number,
+        expiresAt: number | null,
+        reusable: boolean
+      ): Promise<PublicKey> {
+        const requestId = new anchor.BN(nextRequestId++);
+        const paymentRequest = requestPda(shop.merchantAccountPda, requestId);
+        await program.methods
+          .createPaymentRequest(
+            requestId,
+            mint,
+            new anchor.BN(amount),
+            "Table 4",
+            expiresAt === null ? null : new anchor.BN(expiresAt),
+            reusable
+          )
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            paymentRequest,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+        return paymentRequest;
+      }
+
+      function solRequestAccounts(paymentRequest: PublicKey, mint: PublicKey, reference: Buffer) {
+        return {
+          config: configPda,
+          merchantAccount: shop.merchantAccountPda,
+          merchant: shop.merchant.publicKey,
+          treasury: treasury.publicKey,
+          paymentRequest,
+          receipt: receiptPda(shop.merchantAccountPda, reference),
+          merchantStats: statsPda(shop.merchantAccountPda),
+          mintStats: mintStatsPda(shop.merchantAccountPda, mint),
+          customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+          mint: null,
+          acceptedMint: null,
+          globalAcceptedMint: null,
+          payerTokenAccount: null,
+          merchantTokenAccount: null,
+          treasuryTokenAccount: null,
+          payer: customer.publicKey,
+          tokenProgram: null,
+          associatedTokenProgram: null,
+          systemProgram: SystemProgram.programId,
+        };
+      }
+
+      async function payRequest(paymentRequest: PublicKey, mint: PublicKey = NATIVE_SOL_MINT): Promise<PublicKey> {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await program.methods
+          .payPaymentRequest([...reference])
+          .accounts(solRequestAccounts(paymentRequest, mint, reference))
+          .signers([customer])
+          .rpc();
+        return receiptPda(shop.merchantAccountPda, reference);
+      }
+
+      it("Settles a single-use SOL request exactly once", async () => {
+        const amount = 42_000_000;
+        const paymentRequest = await createRequest(NATIVE_SOL_MINT, amount, null, false);
+
+        let request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.deepEqual(request.status, { open: {} });
+        assert.equal(request.memo, "Table 4");
+
+        const receipt = await payRequest(paymentRequest);
+
+        request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.deepEqual(request.status, { paid: {} });
+        assert.equal(request.paymentCount, 1);
+        assert.equal(request.lastReceipt.toString(), receipt.toString());
+
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.amount.toNumber(), amount);
+
+        try {
+          await payRequest(paymentRequest);
+          assert.fail("A paid request should not be paid again");
+        } catch (error: any) {
+          assert.include(error.toString(), "PaymentRequestNotOpen");
+        }
+      });
+
+      it("Keeps a reusable request open", async () => {
+        const paymentRequest = await createRequest(NATIVE_SOL_MINT, 1_000_000, null, true);
+
+        await payRequest(paymentRequest);
+        await payRequest(paymentRequest);
+
+        const request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.deepEqual(request.status, { open: {} });
+        assert.equal(request.paymentCount, 2);
+      });
+
+      it("Rejects payment after expiry and lets anyone mark it expired", async () => {
+        const now = Math.floor(Date.now() / 1000);
+        const paymentRequest = await createRequest(NATIVE_SOL_MINT, 1_000_000, now + 2, false);
+
+        try {
+          await program.methods.expirePaymentRequest().accounts({ paymentRequest }).rpc();
+          assert.fail("Should not expire before the deadline");
+        } catch (error: any) {
+          assert.include(error.toString(), "PaymentRequestNotExpired");
+        }
+
+        await new Promise((resolve) => setTimeout(resolve, 4000));
+
+        try {
+          await payRequest(paymentRequest);
+          assert.fail("Expired request should not be payable");
+        } catch (error: any) {
+          assert.include(error.toString(), "PaymentRequestExpired");
+        }
+
+        await program.methods.expirePaymentRequest().accounts({ paymentRequest }).rpc();
+
+        const request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.deepEqual(request.status, { expired: {} });
+      });
+
+      it("Rejects paying a token request with SOL", async () => {
+        const mint = await createMint(provider.connection, payer.payer, payer.publicKey, null, 6);
+        const paymentRequest = await createRequest(mint, 1_000_000, null, false);
+
+        try {
+          await payRequest(paymentRequest, mint);
+          assert.fail("Token request should not accept SOL");
+        } catch (error: any) {
+          assert.include(error.toString(), "WrongPaymentMint");
+        }
+      });
+
+      it("Settles a token request", async () => {
+        const mint = await createMint(provider.connection, payer.payer, payer.publicKey, null, 6);
+        const customerAta = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer.payer,
+          mint,
+          customer.publicKey
+        );
+        await mintTo(provider.connection, payer.payer, mint, customerAta.address, payer.publicKey, 10_000_000);
+
+        const acceptedMint = PublicKey.findProgramAddressSync(
+          [Buffer.from("accepted_mint"), shop.merchantAccountPda.toBuffer(), mint.toBuffer()],
+          program.programId
+        )[0];
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        const amount = 5_000_000;
+        const paymentRequest = await createRequest(mint, amount, null, false);
+        const reference = Keypair.generate().publicKey.toBuffer();
+
+        await program.methods
+          .payPaymentRequest([...reference])
+          .accounts({
+            ...solRequestAccounts(paymentRequest, mint, reference),
+            mint,
+            acceptedMint,
+            globalAcceptedMint: acceptedMintPda(configPda, mint),
+            payerTokenAccount: customerAta.address,
+            merchantTokenAccount: getAssociatedTokenAddressSync(mint, shop.merchant.publicKey),
+            treasuryTokenAccount: getAssociatedTokenAddressSync(mint, treasury.publicKey),
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          })
+          .signers([customer])
+          .rpc();
+
+        const merchantAta = await getAccount(
+          provider.connection,
+          getAssociatedTokenAddressSync(mint, shop.merchant.publicKey)
+        );
+        assert.equal(Number(merchantAta.amount), amount - (amount * PROTOCOL_FEE_BPS) / 10_000);
+
+        const request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.deepEqual(request.status, { paid: {} });
+      });
+
+      it("Lets only the merchant cancel a request and refunds rent", async () => {
+        const paymentRequest = await createRequest(NATIVE_SOL_MINT, 1_000_000, null, false);
+
+        try {
+          await program.methods
+            .cancelPaymentRequest()
+            .accounts({ merchantAccount: shop.merchantAccountPda, paymentRequest, authority: customer.publicKey, delegate: null })
+            .signers([customer])
+            .rpc();
+          assert.fail("Customer should not cancel the merchant's request");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        const balanceBefore = await provider.connection.getBalance(shop.merchant.publicKey);
+        await program.methods
+          .cancelPaymentRequest()
+          .accounts({ merchantAccount: shop.merchantAccountPda, paymentRequest, authority: shop.merchant.publicKey, delegate: null })
+          .signers([shop.merchant])
+          .rpc();
+
+        const info = await provider.connection.getAccountInfo(paymentRequest);
+        assert.isNull(info, "Payment request should be closed");
+        const balanceAfter = await provider.connection.getBalance(shop.merchant.publicKey);
+        assert.isAbove(balanceAfter, balanceBefore, "Rent should be refunded");
+      });
+
+      it("Closes settled requests instead of cancelling them", async () => {
+        const paymentRequest = await createRequest(NATIVE_SOL_MINT, 1_000_000, null, false);
+        const endAccounts = { merchantAccount: shop.merchantAccountPda, paymentRequest, authority: shop.merchant.publicKey, delegate: null };
+
+        try {
+          await program.methods.closePaymentRequest().accounts(endAccounts).signers([shop.merchant]).rpc();
+          assert.fail("Open requests should be cancelled, not closed");
+        } catch (error: any) {
+          assert.include(error.toString(), "PaymentRequestStillOpen");
+        }
+
+        await payRequest(paymentRequest);
+
+        try {
+          await program.methods.cancelPaymentRequest().accounts(endAccounts).signers([shop.merchant]).rpc();
+          assert.fail("A paid request should not be reported as cancelled");
+        } catch (error: any) {
+          assert.include(error.toString(), "PaymentRequestNotOpen");
+        }
+
+        await program.methods.closePaymentRequest().accounts(endAccounts).signers([shop.merchant]).rpc();
+        assert.isNull(await provider.connection.getAccountInfo(paymentRequest), "Payment request should be closed");
+      });
+    });
+
+    describe("refund_payment", () => {
+      function refundAccounts(receipt: PublicKey, signer: PublicKey, payerKey: PublicKey = customer.publicKey) {
+        return {
+          merchantAccount: shop.merchantAccountPda,
+          receipt,
+          merchantStats: statsPda(shop.merchantAccountPda),
+          mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+          payer: payerKey,
+          mint: null,
+          merchantTokenAccount: null,
+          refundVault: null,
+          vaultTokenAccount: null,
+          payerTokenAccount: null,
+          authority: signer,
+          delegate: null,
+          customerStamps: null,
+          tokenProgram: null,
+          associatedTokenProgram: null,
+          systemProgram: SystemProgram.programId,
+        };
+      }
+
+      it("Refunds partially and then fully, up to the original amount", async () => {
+        const amount = 20_000_000;
+        const receipt = await paySol(shop, customer, amount);
+        const statsBefore = await program.account.merchantStats.fetch(statsPda(shop.merchantAccountPda));
+        const customerBefore = await provider.connection.getBalance(customer.publicKey);
+
+        await program.methods
+          .refundPayment(new anchor.BN(5_000_000))
+          .accounts(refundAccounts(receipt, shop.merchant.publicKey))
+          .signers([shop.merchant])
+          .rpc();
+
+        let receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.refundedAmount.toNumber(), 5_000_000);
+        const customerAfter = await provider.connection.getBalance(customer.publicKey);
+        assert.equal(customerAfter - customerBefore, 5_000_000);
+
+        await program.methods
+          .refundPayment(new anchor.BN(15_000_000))
+          .accounts(refundAccounts(receipt, shop.merchant.publicKey))
+          .signers([shop.merchant])
+          .rpc();
+
+        receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.refundedAmount.toNumber(), amount);
+
+        const statsAfter = await program.account.merchantStats.fetch(statsPda(shop.merchantAccountPda));
+        assert.equal(statsAfter.refundCount.toNumber(), statsBefore.refundCount.toNumber() + 2);
+
+        try {
+          await program.methods
+            .refundPayment(new anchor.BN(1))
+            .accounts(refundAccounts(receipt, shop.merchant.publicKey))
+            .signers([shop.merchant])
+            .rpc();
+          assert.fail("Refunds beyond the payment should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "RefundExceedsPayment");
+        }
+      });
+
+      it("Rejects refunds to anyone but the original payer", async () => {
+        const receipt = await paySol(shop, customer, 1_000_000);
+        const stranger = Keypair.generate();
+
+        try {
+          await program.methods
+            .refundPayment(new anchor.BN(1_000_000))
+            .accounts(refundAccounts(receipt, shop.merchant.publicKey, stranger.publicKey))
+            .signers([shop.merchant])
+            .rpc();
+          assert.fail("Refund should only go to the original payer");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidRefundRecipient");
+        }
+      });
+
+      it("Rejects refunds signed by someone other than the merchant", async () => {
+        const receipt = await paySol(shop, customer, 1_000_000);
+
+        try {
+          await program.methods
+            .refundPayment(new anchor.BN(1_000_000))
+            .accounts(refundAccounts(receipt, customer.publicKey))
+            .signers([customer])
+            .rpc();
+          assert.fail("Only the merchant should refund");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+
+      it("Refunds a token payment to the payer's ATA", async () => {
+        const mint = await createMint(provider.connection, payer.payer, payer.publicKey, null, 6);
+        const customerAta = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer.payer,
+          mint,
+          customer.publicKey
+        );
+        await mintTo(provider.connection, payer.payer, mint, customerAta.address, payer.publicKey, 10_000_000);
+
+        const acceptedMint = PublicKey.findProgramAddressSync(
+          [Buffer.from("accepted_mint"), shop.merchantAccountPda.toBuffer(), mint.toBuffer()],
+          program.programId
+        )[0];
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(shop.merchantAccountPda, reference);
+        const merchantAta = getAssociatedTokenAddressSync(mint, shop.merchant.publicKey);
+        await program.methods
+          .payMerchantToken(new anchor.BN(4_000_000), [...reference])
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchant: shop.merchant.publicKey,
+            mint,
+            acceptedMint,
+            globalAcceptedMint: acceptedMintPda(configPda, mint),
+            payerTokenAccount: customerAta.address,
+            merchantTokenAccount: merchantAta,
+            treasury: treasury.publicKey,
+            treasuryTokenAccount: getAssociatedTokenAddressSync(mint, treasury.publicKey),
+            receipt,
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, mint),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        await program.methods
+          .refundPayment(new anchor.BN(1_000_000))
+          .accounts({
+            ...refundAccounts(receipt, shop.merchant.publicKey),
+            mintStats: mintStatsPda(shop.merchantAccountPda, mint),
+            mint,
+            merchantTokenAccount: merchantAta,
+            payerTokenAccount: customerAta.address,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        const customerAccount = await getAccount(provider.connection, customerAta.address);
+        assert.equal(Number(customerAccount.amount), 10_000_000 - 4_000_000 + 1_000_000);
+
+        const mintStats = await program.account.merchantMintStats.fetch(mintStatsPda(shop.merchantAccountPda, mint));
+        assert.equal(mintStats.refundedVolume.toNumber(), 1_000_000);
+      });
+    });
+
+    describe("escrow payments", () => {
+      const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+      function escrowPda(receipt: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync([Buffer.from("escrow"), receipt.toBuffer()], program.programId)[0];
+      }
+
+      function vaultPda(escrow: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync([Buffer.from("escrow_vault"), escrow.toBuffer()], program.programId)[0];
+      }
+
+      async function escrowSol(
+        target: { merchant: Keypair; merchantAccountPda: PublicKey },
+        amount: number
+      ): Promise<{ receipt: PublicKey; escrow: PublicKey }> {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(target.merchantAccountPda, reference);
+        const escrow = escrowPda(receipt);
+        await program.methods
+          .escrowPaymentSol(new anchor.BN(amount), [...reference])
+          .accounts({
+            config: configPda,
+            merchantAccount: target.merchantAccountPda,
+            merchant: target.merchant.publicKey,
+            receipt,
+            escrow,
+            merchantStats: statsPda(target.merchantAccountPda),
+            mintStats: mintStatsPda(target.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(target.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+        return { receipt, escrow };
+      }
+
+      function disputePda(escrow: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync([Buffer.from("dispute"), escrow.toBuffer()], program.programId)[0];
+      }
+
+      function arbiterPda(arbiter: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync([Buffer.from("arbiter"), arbiter.toBuffer()], program.programId)[0];
+      }
+
+      async function openDispute(
+        target: { merchantAccountPda: PublicKey },
+        held: { escrow: PublicKey },
+        opener: Keypair = customer
+      ): Promise<PublicKey> {
+        const dispute = disputePda(held.escrow);
+        await program.methods
+          .openDispute(1, [...Buffer.alloc(32, 7)])
+          .accounts({
+            config: configPda,
+            merchantAccount: target.merchantAccountPda,
+            escrow: held.escrow,
+            dispute,
+            authority: opener.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([opener])
+          .rpc();
+        return dispute;
+      }
+
+      function settleAccounts(
+        target: { merchant: Keypair; merchantAccountPda: PublicKey },
+        escrow: { receipt: PublicKey; escrow: PublicKey },
+        authority: PublicKey,
+        mint: PublicKey = NATIVE_SOL_MINT
+      ) {
+        return {
+          config: configPda,
+          merchantAccount: target.merchantAccountPda,
+          merchant: target.merchant.publicKey,
+          treasury: treasury.publicKey,
+          payer: customer.publicKey,
+          escrow: escrow.escrow,
+          receipt: escrow.receipt,
+          dispute: null,
+          arbiter: null,
+          delegate: null,
+          merchantStats: statsPda(target.merchantAccountPda),
+          mintStats: mintStatsPda(target.merchantAccountPda, mint),
+          mint: null,
+          vault: null,
+          merchantTokenAccount: null,
+          treasuryTokenAccount: null,
+          payerTokenAccount: null,
+          authority,
+          tokenProgram: null,
+          associatedTokenProgram: null,
+          systemProgram: SystemProgram.programId,
+        };
+      }
+
+      it("Holds SOL until the customer confirms receipt", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Electronics");
+        const amount = 300_000_000;
+        const held = await escrowSol(escrowShop, amount);
+
+        let merchantAccount = await program.account.merchantAccount.fetch(escrowShop.merchantAccountPda);
+        assert.equal(merchantAccount.openObligations, 1, "Held escrow is an open obligation");
+        let receipt = await program.account.paymentReceipt.fetch(held.receipt);
+        assert.isTrue(receipt.escrowed);
+
+        try {
+          await program.methods
+            .closeMerchant()
+            .accounts({
+              merchantAccount: escrowShop.merchantAccountPda,
+              merchantStats: statsPda(escrowShop.merchantAccountPda),
+              locationProof: null,
+              merchantIdReservation: merchantIdPda(escrowShop.merchantId),
+              merchant: escrowShop.merchant.publicKey,
+            })
+            .signers([escrowShop.merchant])
+            .rpc();
+          assert.fail("Merchant with a held escrow should not close");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantHasOpenObligations");
+        }
+
+        const merchantBefore = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+        await program.methods
+          .confirmReceipt()
+          .accounts(settleAccounts(escrowShop, held, customer.publicKey))
+          .signers([customer])
+          .rpc();
+
+        const merchantAfter = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+        const protocolFee = (amount * PROTOCOL_FEE_BPS) / 10_000;
+        assert.equal(merchantAfter - merchantBefore, amount - protocolFee);
+
+        assert.isNull(await provider.connection.getAccountInfo(held.escrow), "Escrow should be closed");
+        receipt = await program.account.paymentReceipt.fetch(held.receipt);
+        assert.isFalse(receipt.escrowed);
+        merchantAccount = await program.account.merchantAccount.fetch(escrowShop.merchantAccountPda);
+        assert.equal(merchantAccount.openObligations, 0);
+      });
+
+      it("Lets the merchant claim only after the dispute window", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Furniture");
+        const held = await escrowSol(escrowShop, 10_000_000);
+
+        try {
+          await program.methods
+            .claimEscrow()
+            .accounts(settleAccounts(escrowShop, held, escrowShop.merchant.publicKey))
+            .signers([escrowShop.merchant])
+            .rpc();
+          assert.fail("Claim should wait for the dispute window");
+        } catch (error: any) {
+          assert.include(error.toString(), "EscrowWindowOpen");
+        }
+
+        await sleep((ESCROW_DISPUTE_WINDOW + 1) * 1000);
+
+        await program.methods
+          .claimEscrow()
+          .accounts(settleAccounts(escrowShop, held, escrowShop.merchant.publicKey))
+          .signers([escrowShop.merchant])
+          .rpc();
+
+        assert.isNull(await provider.connection.getAccountInfo(held.escrow), "Escrow should be closed");
+      });
+
+      it("Freezes a disputed escrow until an arbiter splits it", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Jewellery");
+        const amount = 20_000_000;
+        const held = await escrowSol(escrowShop, amount);
+        const dispute = disputePda(held.escrow);
+
+        try {
+          await program.methods
+            .openDispute(1, null)
+            .accounts({
+              config: configPda,
+              merchantAccount: escrowShop.merchantAccountPda,
+              escrow: held.escrow,
+              dispute,
+              authority: payer.publicKey,
+              delegate: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .rpc();
+          assert.fail("Only the customer or the merchant should open a dispute");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        await openDispute(escrowShop, held);
+
+        const escrowAccount = await program.account.escrow.fetch(held.escrow);
+        assert.deepEqual(escrowAccount.status, { disputed: {} });
+
+        await program.methods
+          .submitDisputeEvidence([...Buffer.alloc(32, 9)])
+          .accounts({ merchantAccount: escrowShop.merchantAccountPda, dispute, authority: escrowShop.merchant.publicKey, delegate: null })
+          .signers([escrowShop.merchant])
+          .rpc();
+
+        const disputeAccount = await program.account.dispute.fetch(dispute);
+        assert.equal(disputeAccount.reasonCode, 1);
+        assert.equal(disputeAccount.customerEvidence.length, 1);
+        assert.equal(disputeAccount.merchantEvidence.length, 1);
+
+        try {
+          await program.methods
+            .claimEscrow()
+            .accounts(settleAccounts(escrowShop, held, escrowShop.merchant.publicKey))
+            .signers([escrowShop.merchant])
+            .rpc();
+          assert.fail("Disputed escrow should not be claimable");
+        } catch (error: any) {
+          assert.include(error.toString(), "EscrowDisputed");
+        }
+
+        const arbiter = Keypair.generate();
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(arbiter.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+        );
+
+        try {
+          await program.methods
+            .resolveDispute(10_000)
+            .accounts({ ...settleAccounts(escrowShop, held, arbiter.publicKey), dispute })
+            .signers([arbiter])
+            .rpc();
+          assert.fail("Unregistered wallets should not rule");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        await program.methods
+          .addArbiter(arbiter.publicKey)
+          .accounts({ config: configPda, arbiterAccount: arbiterPda(arbiter.publicKey), admin: payer.publicKey, systemProgram: SystemProgram.programId })
+          .rpc();
+
+        const merchantBefore = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+        const customerBefore = await provider.connection.getBalance(customer.publicKey);
+        await program.methods
+          .resolveDispute(2_500)
+          .accounts({ ...settleAccounts(escrowShop, held, arbiter.publicKey), dispute, arbiter: arbiterPda(arbiter.publicKey) })
+          .signers([arbiter])
+          .rpc();
+        const merchantAfter = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+        const customerAfter = await provider.connection.getBalance(customer.publicKey);
+
+        const refunded = amount / 4;
+        const treasuryShare = ((amount * PROTOCOL_FEE_BPS) / 10_000) * 3 / 4;
+        assert.equal(merchantAfter - merchantBefore, amount - refunded - treasuryShare);
+        assert.isAtLeast(customerAfter - customerBefore, refunded, "Customer gets its share plus reclaimed rent");
+        assert.isNull(await provider.connection.getAccountInfo(dispute), "Dispute should be closed");
+        const receipt = await program.account.paymentReceipt.fetch(held.receipt);
+        assert.equal(receipt.refundedAmount.toNumber(), refunded);
+
+        await program.methods
+          .removeArbiter()
+          .accounts({ config: configPda, arbiterAccount: arbiterPda(arbiter.publicKey), admin: payer.publicKey })
+          .rpc();
+      });
+
+      it("Applies the default outcome when nobody rules in time", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Watches");
+        const amount = 10_000_000;
+        const held = await escrowSol(escrowShop, amount);
+        const dispute = await openDispute(escrowShop, held);
+
+        try {
+          await program.methods
+            .applyDisputeDefault()
+            .accounts({ ...settleAccounts(escrowShop, held, payer.publicKey), dispute })
+            .rpc();
+          assert.fail("Default should wait for the ruling deadline");
+        } catch (error: any) {
+          assert.include(error.toString(), "RulingWindowOpen");
+        }
+
+        await sleep((DISPUTE_RULING_WINDOW + 1) * 1000);
+
+        try {
+          await program.methods
+            .resolveDispute(0)
+            .accounts({ ...settleAccounts(escrowShop, held, payer.publicKey), dispute })
+            .rpc();
+          assert.fail("Late rulings should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "RulingWindowClosed");
+        }
+
+        await program.methods
+          .applyDisputeDefault()
+          .accounts({ ...settleAccounts(escrowShop, held, payer.publicKey), dispute })
+          .rpc();
+
+        const receipt = await program.account.paymentReceipt.fetch(held.receipt);
+        assert.equal(receipt.refundedAmount.toNumber(), (amount * DISPUTE_DEFAULT_REFUND_BPS) / 10_000);
+        assert.isNull(await provider.connection.getAccountInfo(held.escrow), "Escrow should be closed");
+      });
+
+      it("Lets the merchant open a dispute with its own evidence", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Bikes");
+        const held = await escrowSol(escrowShop, 10_000_000);
+        const dispute = await openDispute(escrowShop, held, escrowShop.merchant);
+
+        const disputeAccount = await program.account.dispute.fetch(dispute);
+        assert.equal(disputeAccount.openedBy.toString(), escrowShop.merchant.publicKey.toString());
+        assert.equal(disputeAccount.customer.toString(), customer.publicKey.toString());
+        assert.equal(disputeAccount.customerEvidence.length, 0);
+        assert.equal(disputeAccount.merchantEvidence.length, 1);
+      });
+
+      it("Lets the customer withdraw a dispute by confirming receipt", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Shoes");
+        const amount = 10_000_000;
+        const held = await escrowSol(escrowShop, amount);
+        const dispute = await openDispute(escrowShop, held);
+
+        const merchantBefore = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+        await program.methods
+          .confirmReceipt()
+          .accounts({ ...settleAccounts(escrowShop, held, customer.publicKey), dispute })
+          .signers([customer])
+          .rpc();
+        const merchantAfter = await provider.connection.getBalance(escrowShop.merchant.publicKey);
+
+        assert.equal(merchantAfter - merchantBefore, amount - (amount * PROTOCOL_FEE_BPS) / 10_000);
+        assert.isNull(await provider.connection.getAccountInfo(dispute), "Dispute should be closed");
+      });
+
+      it("Rejects a direct refund while funds are escrowed", async () => {
+        const held = await escrowSol(shop, 1_000_000);
+
+        try {
+          await program.methods
+            .refundPayment(new anchor.BN(1_000_000))
+            .accounts({
+              merchantAccount: shop.merchantAccountPda,
+              receipt: held.receipt,
+              merchantStats: statsPda(shop.merchantAccountPda),
+              mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+              payer: customer.publicKey,
+              mint: null,
+              merchantTokenAccount: null,
+              refundVault: null,
+              vaultTokenAccount: null,
+              payerTokenAccount: null,
+              authority: shop.merchant.publicKey,
+              delegate: null,
+              customerStamps: null,
+              tokenProgram: null,
+              associatedTokenProgram: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([shop.merchant])
+            .rpc();
+          assert.fail("Escrowed receipts should not be refunded directly");
+        } catch (error: any) {
+          assert.include(error.toString(), "EscrowPending");
+        }
+      });
+
+      it("Holds tokens in a vault and releases them on confirmation", async () => {
+        const escrowShop = await registerTestMerchant("Escrow Token Store");
+        const mint = await createMint(provider.connection, payer.payer, payer.publicKey, null, 6);
+        const customerAta = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer.payer,
+          mint,
+          customer.publicKey
+        );
+        await mintTo(provider.connection, payer.payer, mint, customerAta.address, payer.publicKey, 10_000_000);
+
+        const acceptedMint = PublicKey.findProgramAddressSync(
+          [Buffer.from("accepted_mint"), escrowShop.merchantAccountPda.toBuffer(), mint.toBuffer()],
+          program.programId
+        )[0];
+        await program.methods
+          .addMerchantAcceptedMint()
+          .accounts({
+            merchantAccount: escrowShop.merchantAccountPda,
+            mint,
+            acceptedMint,
+            authority: escrowShop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([escrowShop.merchant])
+          .rpc();
+
+        const amount = 2_000_000;
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(escrowShop.merchantAccountPda, reference);
+        const escrow = escrowPda(receipt);
+        const vault = vaultPda(escrow);
+
+        await program.methods
+          .escrowPaymentToken(new anchor.BN(amount), [...reference])
+          .accounts({
+            config: configPda,
+            merchantAccount: escrowShop.merchantAccountPda,
+            merchant: escrowShop.merchant.publicKey,
+            mint,
+            acceptedMint,
+            globalAcceptedMint: acceptedMintPda(configPda, mint),
+            payerTokenAccount: customerAta.address,
+            receipt,
+            escrow,
+            vault,
+            merchantStats: statsPda(escrowShop.merchantAccountPda),
+            mintStats: mintStatsPda(escrowShop.merchantAccountPda, mint),
+            customerRecord: customerRecordPda(escrowShop.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        const vaultAccount = await getAccount(provider.connection, vault);
+        assert.equal(Number(vaultAccount.amount), amount);
+
+        const merchantAta = getAssociatedTokenAddressSync(mint, escrowShop.merchant.publicKey);
+        await program.methods
+          .confirmReceipt()
+          .accounts({
+            ...settleAccounts(escrowShop, { receipt, escrow }, customer.publicKey, mint),
+            mint,
+            vault,
+            merchantTokenAccount: merchantAta,
+            treasuryTokenAccount: getAssociatedTokenAddressSync(mint, treasury.publicKey),
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          })
+          .signers([customer])
+          .rpc();
+
+        const merchantAccount = await getAccount(provider.connection, merchantAta);
+        assert.equal(Number(merchantAccount.amount), amount - (amount * PROTOCOL_FEE_BPS) / 10_000);
+        assert.isNull(await provider.connection.getAccountInfo(vault), "Vault should be closed");
+      });
+    });
+
+    describe("staff tips", () => {
+      function staffPda(merchantAccountPda: PublicKey, wallet: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync(
+          [Buffer.from("staff"), merchantAccountPda.toBuffer(), wallet.toBuffer()],
+          program.programId
+        )[0];
+      }
+
+      let restaurant: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      const waiter = Keypair.generate();
+      const chef = Keypair.generate();
+      const host = Keypair.generate();
+
+      async function payWithTip(amount: number, tip: number, splitTip: boolean, staff: PublicKey[]) {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(restaurant.merchantAccountPda, reference);
+        await program.methods
+          .payMerchantSol(new anchor.BN(amount), [...reference], new anchor.BN(tip), splitTip)
+          .accounts({
+            config: configPda,
+            merchantAccount: restaurant.merchantAccountPda,
+            merchantStats: statsPda(restaurant.merchantAccountPda),
+            mintStats: mintStatsPda(restaurant.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(restaurant.merchantAccountPda, customer.publicKey),
+            merchant: restaurant.merchant.publicKey,
+            treasury: treasury.publicKey,
+            receipt,
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(
+            staff.flatMap((wallet) => [
+              { pubkey: staffPda(restaurant.merchantAccountPda, wallet), isWritable: false, isSigner: false },
+              { pubkey: wallet, isWritable: true, isSigner: false },
+            ])
+          )
+          .signers([customer])
+          .rpc();
+        return receipt;
+      }
+
+      before(async () => {
+        restaurant = await registerTestMerchant("Tip Top Bistro");
+
+        // Waiter and chef share the pool 1:3; the host only takes direct tips
+        for (const [wallet, weight] of [[waiter, 1], [chef, 3], [host, 0]] as [Keypair, number][]) {
+          await program.methods
+            .addStaff(wallet.publicKey, weight)
+            .accounts({
+              merchantAccount: restaurant.merchantAccountPda,
+              staff: staffPda(restaurant.merchantAccountPda, wallet.publicKey),
+              authority: restaurant.merchant.publicKey,
+              delegate: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([restaurant.merchant])
+            .rpc();
+        }
+
+        const merchantAccount = await program.account.merchantAccount.fetch(restaurant.merchantAccountPda);
+        assert.equal(merchantAccount.staffCount, 3);
+        assert.equal(merchantAccount.tipPoolSize, 2);
+      });
+
+      it("Routes a tip to a single staff member", async () => {
+        const tip = 5_000_000;
+        const hostBefore = await provider.connection.getBalance(host.publicKey);
+        const receipt = await payWithTip(50_000_000, tip, false, [host.publicKey]);
+        const hostAfter = await provider.connection.getBalance(host.publicKey);
+
+        assert.equal(hostAfter - hostBefore, tip);
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.tipAmount.toNumber(), tip);
+        assert.equal(receiptAccount.tips.length, 1);
+        assert.equal(receiptAccount.tips[0].staff.toString(), host.publicKey.toString());
+      });
+
+      it("Splits a tip across the pool by weight", async () => {
+        const tip = 8_000_000;
+        const receipt = await payWithTip(50_000_000, tip, true, [waiter.publicKey, chef.publicKey]);
+
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.deepEqual(
+          receiptAccount.tips.map((leg: any) => [leg.staff.toString(), leg.amount.toNumber()]),
+          [
+            [waiter.publicKey.toString(), 2_000_000],
+            [chef.publicKey.toString(), 6_000_000],
+          ]
+        );
+      });
+
+      it("Sizes receipts by the number of tip legs", async () => {
+        const TIP_LEG_LEN = 40;
+        const plain = await payWithTip(50_000_000, 0, false, []);
+        const direct = await payWithTip(50_000_000, 1_000_000, false, [host.publicKey]);
+        const split = await payWithTip(50_000_000, 1_000_000, true, [waiter.publicKey, chef.publicKey]);
+
+        const [plainInfo, directInfo, splitInfo] = await Promise.all(
+          [plain, direct, split].map((receipt) => provider.connection.getAccountInfo(receipt))
+        );
+        assert.equal(directInfo!.data.length - plainInfo!.data.length, TIP_LEG_LEN);
+        assert.equal(splitInfo!.data.length - plainInfo!.data.length, 2 * TIP_LEG_LEN);
+      });
+
+      it("Rejects a pool split that leaves out a member", async () => {
+        try {
+          await payWithTip(50_000_000, 1_000_000, true, [chef.publicKey]);
+          assert.fail("Pool split should include every member");
+        } catch (error: any) {
+          assert.include(error.toString(), "IncompleteTipPool");
+        }
+      });
+
+      it("Rejects tips to wallets that are not staff", async () => {
+        try {
+          await payWithTip(50_000_000, 1_000_000, false, [customer.publicKey]);
+          assert.fail("Tip should only go to registered staff");
+        } catch (error: any) {
+          assert.ok(error);
+        }
+      });
+
+      it("Removes staff from the pool", async () => {
+        await program.methods
+          .setStaffWeight(0)
+          .accounts({
+            merchantAccount: restaurant.merchantAccountPda,
+            staff: staffPda(restaurant.merchantAccountPda, waiter.publicKey),
+            authority: restaurant.merchant.publicKey,
+            delegate: null,
+          })
+          .signers([restaurant.merchant])
+          .rpc();
+
+        await program.methods
+          .removeStaff()
+          .accounts({
+            merchantAccount: restaurant.merchantAccountPda,
+            staff: staffPda(restaurant.merchantAccountPda, host.publicKey),
+            authority: restaurant.merchant.publicKey,
+            delegate: null,
+          })
+          .signers([restaurant.merchant])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(restaurant.merchantAccountPda);
+        assert.equal(merchantAccount.staffCount, 2);
+        assert.equal(merchantAccount.tipPoolSize, 1);
+      });
+    });
+
+    describe("delegates", () => {
+      const PERM_ISSUE_INVOICES = 1 << 0;
+      const PERM_REFUND = 1 << 1;
+      const REFUND_LIMIT = 2_000_000;
+      const cashier = Keypair.generate();
+      let cafe: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      let cashierPda: PublicKey;
+      let refundVaultPda: PublicKey;
+
+      function withdrawRefundVault(amount: number) {
+        return program.methods
+          .withdrawRefundVault(new anchor.BN(amount))
+          .accounts({
+            merchantAccount: cafe.merchantAccountPda,
+            refundVault: refundVaultPda,
+            mint: null,
+            vaultTokenAccount: null,
+            merchantTokenAccount: null,
+            merchant: cafe.merchant.publicKey,
+            tokenProgram: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cafe.merchant])
+          .rpc();
+      }
+
+      function cashierRefund(receipt: PublicKey, amount: number) {
+        return program.methods
+          .refundPayment(new anchor.BN(amount))
+          .accounts({
+            merchantAccount: cafe.merchantAccountPda,
+            receipt,
+            merchantStats: statsPda(cafe.merchantAccountPda),
+            mintStats: mintStatsPda(cafe.merchantAccountPda, NATIVE_SOL_MINT),
+            payer: customer.publicKey,
+            mint: null,
+            merchantTokenAccount: null,
+            refundVault: refundVaultPda,
+            vaultTokenAccount: null,
+            payerTokenAccount: null,
+            authority: cashier.publicKey,
+            delegate: cashierPda,
+            customerStamps: null,
+            tokenProgram: null,
+            associatedTokenProgram: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cashier])
+          .rpc();
+      }
+
+      before(async () => {
+        cafe = await registerTestMerchant("Delegate Diner");
+        cashierPda = PublicKey.findProgramAddressSync(
+          [Buffer.from("delegate"), cafe.merchantAccountPda.toBuffer(), cashier.publicKey.toBuffer()],
+          program.programId
+        )[0];
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(cashier.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+        );
+
+        await program.methods
+          .addDelegate(cashier.publicKey, PERM_ISSUE_INVOICES | PERM_REFUND, NATIVE_SOL_MINT, new anchor.BN(REFUND_LIMIT))
+          .accounts({
+            merchantAccount: cafe.merchantAccountPda,
+            delegateAccount: cashierPda,
+            merchant: cafe.merchant.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cafe.merchant])
+          .rpc();
+
+        // The owner funds the refund vault that cashier refunds are paid from
+        refundVaultPda = PublicKey.findProgramAddressSync(
+          [Buffer.from("refund_vault"), cafe.merchantAccountPda.toBuffer()],
+          program.programId
+        )[0];
+        await provider.sendAndConfirm(
+          new anchor.web3.Transaction().add(
+            SystemProgram.transfer({
+              fromPubkey: cafe.merchant.publicKey,
+              toPubkey: refundVaultPda,
+              lamports: 4 * REFUND_LIMIT,
+            })
+          ),
+          [cafe.merchant]
+        );
+      });
+
+      it("Lets a cashier issue invoices without the owner's wallet", async () => {
+        const requestId = new anchor.BN(1);
+        const paymentRequest = PublicKey.findProgramAddressSync(
+          [Buffer.from("payment_request"), cafe.merchantAccountPda.toBuffer(), requestId.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+
+        await program.methods
+          .createPaymentRequest(requestId, NATIVE_SOL_MINT, new anchor.BN(1_000_000), "Counter 2", null, false)
+          .accounts({
+            merchantAccount: cafe.merchantAccountPda,
+            paymentRequest,
+            authority: cashier.publicKey,
+            delegate: cashierPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cashier])
+          .rpc();
+
+        const request = await program.account.paymentRequest.fetch(paymentRequest);
+        assert.equal(request.amount.toNumber(), 1_000_000);
+      });
+
+      it("Enforces the cashier's refund limit across calls", async () => {
+        const receipt = await paySol(cafe, customer, 10_000_000);
+
+        await cashierRefund(receipt, REFUND_LIMIT / 2);
+
+        try {
+          await cashierRefund(await paySol(cafe, customer, 10_000_000), REFUND_LIMIT / 2 + 1);
+          assert.fail("Refunds adding up past the limit should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "RefundLimitExceeded");
+        }
+
+        const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+        assert.equal(receiptAccount.refundedAmount.toNumber(), REFUND_LIMIT / 2);
+        const cashierAccount = await program.account.delegate.fetch(cashierPda);
+        assert.equal(cashierAccount.refundedAmount.toNumber(), REFUND_LIMIT / 2);
+      });
+
+      it("Pays cashier refunds from the refund vault, not the cashier's wallet", async () => {
+        const receipt = await paySol(cafe, customer, 10_000_000);
+        const cashierBefore = await provider.connection.getBalance(cashier.publicKey);
+        const vaultBefore = await provider.connection.getBalance(refundVaultPda);
+
+        await cashierRefund(receipt, REFUND_LIMIT / 4);
+
+        assert.equal(await provider.connection.getBalance(cashier.publicKey), cashierBefore);
+        assert.equal(vaultBefore - (await provider.connection.getBalance(refundVaultPda)), REFUND_LIMIT / 4);
+      });
+
+      it("Rejects cashier refunds the vault cannot cover and lets the owner withdraw", async () => {
+        const rentMinimum = await provider.connection.getMinimumBalanceForRentExemption(0);
+        const vaultBalance = await provider.connection.getBalance(refundVaultPda);
+        await withdrawRefundVault(vaultBalance - rentMinimum);
+
+        const receipt = await paySol(cafe, customer, 10_000_000);
+        try {
+          await cashierRefund(receipt, REFUND_LIMIT / 8);
+          assert.fail("Refund should not dip below the vault's rent-exempt balance");
+        } catch (error: any) {
+          assert.include(error.toString(), "RefundVaultInsufficient");
+        }
+
+        await withdrawRefundVault(rentMinimum);
+        assert.equal(await provider.connection.getBalance(refundVaultPda), 0);
+      });
+
+      it("Only lets the owner withdraw from the refund vault", async () => {
+        try {
+          await program.methods
+            .withdrawRefundVault(new anchor.BN(1))
+            .accounts({
+              merchantAccount: cafe.merchantAccountPda,
+              refundVault: refundVaultPda,
+              mint: null,
+              vaultTokenAccount: null,
+              merchantTokenAccount: null,
+              merchant: cashier.publicKey,
+              tokenProgram: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([cashier])
+            .rpc();
+          assert.fail("Cashier should not withdraw from the vault");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+      });
+
+      it("Rejects actions outside the cashier's permissions", async () => {
+        try {
+          await program.methods
+            .updateMerchantProfile("Cashier's Diner", null, null)
+            .accounts({
+              merchantAccount: cafe.merchantAccountPda,
+              authority: cashier.publicKey,
+              delegate: cashierPda,
+            })
+            .signers([cashier])
+            .rpc();
+          assert.fail("Cashier should not edit the profile");
+        } catch (error: any) {
+          assert.include(error.toString(), "MissingPermission");
+        }
+
+        try {
+          await program.methods
+            .deactivateMerchant()
+            .accounts({ merchantAccount: cafe.merchantAccountPda, authority: cashier.publicKey, delegate: cashierPda })
+            .signers([cashier])
+            .rpc();
+          assert.fail("Cashier should not close the business");
+        } catch (error: any) {
+          assert.include(error.toString(), "MissingPermission");
+        }
+      });
+
+      it("Blocks ownership transfer while delegates remain", async () => {
+        try {
+          await program.methods
+            .proposeMerchantTransfer(Keypair.generate().publicKey)
+            .accounts({ merchantAccount: cafe.merchantAccountPda, merchant: cafe.merchant.publicKey })
+            .signers([cafe.merchant])
+            .rpc();
+          assert.fail("Delegates should be removed before a transfer");
+        } catch (error: any) {
+          assert.include(error.toString(), "MerchantHasDelegates");
+        }
+      });
+
+      it("Revokes a delegate", async () => {
+        await program.methods
+          .removeDelegate()
+          .accounts({ merchantAccount: cafe.merchantAccountPda, delegateAccount: cashierPda, merchant: cafe.merchant.publicKey })
+          .signers([cafe.merchant])
+          .rpc();
+
+        const merchantAccount = await program.account.merchantAccount.fetch(cafe.merchantAccountPda);
+        assert.equal(merchantAccount.delegateCount, 0);
+
+        const receipt = await paySol(cafe, customer, 1_000_000);
+        try {
+          await cashierRefund(receipt, 1_000_000);
+          assert.fail("Revoked cashier should not refund");
+        } catch (error: any) {
+          assert.ok(error);
+        }
+      });
+    });
+
+    describe("reviews", () => {
+      let diner: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      let receipt: PublicKey;
+
+      function reviewPda(merchantAccountPda: PublicKey, reviewer: PublicKey): PublicKey {
+        return PublicKey.findProgramAddressSync(
+          [Buffer.from("review"), merchantAccountPda.toBuffer(), reviewer.toBuffer()],
+          program.programId
+        )[0];
+      }
+
+      before(async () => {
+        diner = await registerTestMerchant("Review Ramen");
+        receipt = await paySol(diner, customer, 5_000_000);
+      });
+
+      it("Rejects reviews from wallets without a receipt", async () => {
+        const stranger = Keypair.generate();
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(stranger.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+        );
+
+        try {
+          await program.methods
+            .submitReview(5, [...Buffer.alloc(32, 1)], "ipfs://fake")
+            .accounts({
+              merchantAccount: diner.merchantAccountPda,
+              receipt,
+              review: reviewPda(diner.merchantAccountPda, stranger.publicKey),
+              reviewer: stranger.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([stranger])
+            .rpc();
+          assert.fail("Only paying customers should review");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReviewNotEligible");
+        }
+      });
+
+      it("Rejects reviews backed by a payment still held in escrow", async () => {
+        const buyer = Keypair.generate();
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(buyer.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+        );
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const heldReceipt = receiptPda(diner.merchantAccountPda, reference);
+        const escrow = PublicKey.findProgramAddressSync(
+          [Buffer.from("escrow"), heldReceipt.toBuffer()],
+          program.programId
+        )[0];
+        await program.methods
+          .escrowPaymentSol(new anchor.BN(5_000_000), [...reference])
+          .accounts({
+            config: configPda,
+            merchantAccount: diner.merchantAccountPda,
+            merchant: diner.merchant.publicKey,
+            receipt: heldReceipt,
+            escrow,
+            merchantStats: statsPda(diner.merchantAccountPda),
+            mintStats: mintStatsPda(diner.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(diner.merchantAccountPda, buyer.publicKey),
+            payer: buyer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([buyer])
+          .rpc();
+
+        try {
+          await program.methods
+            .submitReview(1, [...Buffer.alloc(32, 1)], "ipfs://held")
+            .accounts({
+              merchantAccount: diner.merchantAccountPda,
+              receipt: heldReceipt,
+              review: reviewPda(diner.merchantAccountPda, buyer.publicKey),
+              reviewer: buyer.publicKey,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([buyer])
+            .rpc();
+          assert.fail("Escrowed payments should not back a review");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReviewNotEligible");
+        }
+      });
+
+      it("Keeps the rating aggregate consistent across submit, edit and delete", async () => {
+        const review = reviewPda(diner.merchantAccountPda, customer.publicKey);
+
+        try {
+          await program.methods
+            .submitReview(6, [...Buffer.alloc(32, 1)], "ipfs://review")
+            .accounts({ merchantAccount: diner.merchantAccountPda, receipt, review, reviewer: customer.publicKey, systemProgram: SystemProgram.programId })
+            .signers([customer])
+            .rpc();
+          assert.fail("Ratings above 5 should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidRating");
+        }
+
+        await program.methods
+          .submitReview(4, [...Buffer.alloc(32, 1)], "ipfs://review")
+          .accounts({ merchantAccount: diner.merchantAccountPda, receipt, review, reviewer: customer.publicKey, systemProgram: SystemProgram.programId })
+          .signers([customer])
+          .rpc();
+
+        let merchantAccount = await program.account.merchantAccount.fetch(diner.merchantAccountPda);
+        assert.equal(merchantAccount.ratingSum.toNumber(), 4);
+        assert.equal(merchantAccount.ratingCount, 1);
+
+        await program.methods
+          .editReview(2, [...Buffer.alloc(32, 2)], "ipfs://review-v2")
+          .accounts({ merchantAccount: diner.merchantAccountPda, review, reviewer: customer.publicKey })
+          .signers([customer])
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(diner.merchantAccountPda);
+        assert.equal(merchantAccount.ratingSum.toNumber(), 2);
+        assert.equal(merchantAccount.ratingCount, 1);
+        const reviewAccount = await program.account.review.fetch(review);
+        assert.equal(reviewAccount.uri, "ipfs://review-v2");
+
+        await program.methods
+          .deleteReview()
+          .accounts({ merchantAccount: diner.merchantAccountPda, review, reviewer: customer.publicKey })
+          .signers([customer])
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(diner.merchantAccountPda);
+        assert.equal(merchantAccount.ratingSum.toNumber(), 0);
+        assert.equal(merchantAccount.ratingCount, 0);
+        assert.isNull(await provider.connection.getAccountInfo(review), "Review should be closed");
+      });
+
+      it("Stores merchant replies and lets the admin hide a review from the aggregate", async () => {
+        const review = reviewPda(diner.merchantAccountPda, customer.publicKey);
+        await program.methods
+          .submitReview(1, [...Buffer.alloc(32, 3)], "ipfs://rant")
+          .accounts({ merchantAccount: diner.merchantAccountPda, receipt, review, reviewer: customer.publicKey, systemProgram: SystemProgram.programId })
+          .signers([customer])
+          .rpc();
+
+        await program.methods
+          .replyToReview([...Buffer.alloc(32, 4)])
+          .accounts({ merchantAccount: diner.merchantAccountPda, review, authority: diner.merchant.publicKey, delegate: null })
+          .signers([diner.merchant])
+          .rpc();
+
+        let reviewAccount = await program.account.review.fetch(review);
+        assert.deepEqual([...reviewAccount.replyHash], [...Buffer.alloc(32, 4)]);
+
+        try {
+          await program.methods
+            .hideReview(1)
+            .accounts({ config: configPda, merchantAccount: diner.merchantAccountPda, review, admin: diner.merchant.publicKey })
+            .signers([diner.merchant])
+            .rpc();
+          assert.fail("Merchants should not moderate their own reviews");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        await program.methods
+          .hideReview(2)
+          .accounts({ config: configPda, merchantAccount: diner.merchantAccountPda, review, admin: payer.publicKey })
+          .rpc();
+
+        let merchantAccount = await program.account.merchantAccount.fetch(diner.merchantAccountPda);
+        assert.equal(merchantAccount.ratingCount, 0, "Hidden reviews leave the aggregate");
+        reviewAccount = await program.account.review.fetch(review);
+        assert.isTrue(reviewAccount.isHidden);
+        assert.equal(reviewAccount.hideReason, 2);
+
+        try {
+          await program.methods
+            .deleteReview()
+            .accounts({ merchantAccount: diner.merchantAccountPda, review, reviewer: customer.publicKey })
+            .signers([customer])
+            .rpc();
+          assert.fail("Hidden reviews should not be deleted and resubmitted");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReviewHidden");
+        }
+
+        await program.methods
+          .unhideReview()
+          .accounts({ config: configPda, merchantAccount: diner.merchantAccountPda, review, admin: payer.publicKey })
+          .rpc();
+
+        merchantAccount = await program.account.merchantAccount.fetch(diner.merchantAccountPda);
+        assert.equal(merchantAccount.ratingCount, 1);
+        assert.equal(merchantAccount.ratingSum.toNumber(), 1);
+      });
+    });
+
+    describe("loyalty points", () => {
+      const EARN_RATE = 10; // points per 1,000,000 lamports
+      const REDEEM_VALUE = 10_000; // lamports of discount per point
+      let bistro: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      let loyaltyProgram: PublicKey;
+      let loyaltyBalance: PublicKey;
+
+      function payBistro(amount: number, reference: Buffer) {
+        return program.methods
+          .payMerchantSol(new anchor.BN(amount), [...reference], new anchor.BN(0), false)
+          .accounts({
+            config: configPda,
+            merchantAccount: bistro.merchantAccountPda,
+            merchant: bistro.merchant.publicKey,
+            treasury: treasury.publicKey,
+            receipt: receiptPda(bistro.merchantAccountPda, reference),
+            merchantStats: statsPda(bistro.merchantAccountPda),
+            mintStats: mintStatsPda(bistro.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(bistro.merchantAccountPda, customer.publicKey),
+            loyaltyProgram,
+            loyaltyBalance,
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer]);
+      }
+
+      function redeem(points: number) {
+        return program.methods
+          .redeemPoints(new anchor.BN(points))
+          .accounts({ loyaltyProgram, loyaltyBalance, customer: customer.publicKey })
+          .signers([customer]);
+      }
+
+      before(async () => {
+        bistro = await registerTestMerchant("Loyalty Bistro");
+        loyaltyProgram = PublicKey.findProgramAddressSync(
+          [Buffer.from("loyalty_program"), bistro.merchantAccountPda.toBuffer()],
+          program.programId
+        )[0];
+        loyaltyBalance = PublicKey.findProgramAddressSync(
+          [Buffer.from("loyalty"), bistro.merchantAccountPda.toBuffer(), customer.publicKey.toBuffer()],
+          program.programId
+        )[0];
+      });
+
+      it("Lets only the merchant start a program", async () => {
+        try {
+          await program.methods
+            .createLoyaltyProgram(NATIVE_SOL_MINT, new anchor.BN(EARN_RATE), new anchor.BN(REDEEM_VALUE), new anchor.BN(0))
+            .accounts({
+              merchantAccount: bistro.merchantAccountPda,
+              loyaltyProgram,
+              authority: customer.publicKey,
+              delegate: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([customer])
+            .rpc();
+          assert.fail("Customers should not create loyalty programs");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        await program.methods
+          .createLoyaltyProgram(NATIVE_SOL_MINT, new anchor.BN(EARN_RATE), new anchor.BN(REDEEM_VALUE), new anchor.BN(0))
+          .accounts({
+            merchantAccount: bistro.merchantAccountPda,
+            loyaltyProgram,
+            authority: bistro.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bistro.merchant])
+          .rpc();
+
+        const programAccount = await program.account.loyaltyProgram.fetch(loyaltyProgram);
+        assert.equal(programAccount.earnRate.toNumber(), EARN_RATE);
+        assert.isTrue(programAccount.isActive);
+      });
+
+      it("Credits points on payments", async () => {
+        await payBistro(5_000_000, Keypair.generate().publicKey.toBuffer()).rpc();
+
+        const balance = await program.account.loyaltyBalance.fetch(loyaltyBalance);
+        assert.equal(balance.points.toNumber(), 50);
+        assert.equal(balance.lifetimePoints.toNumber(), 50);
+        assert.ok(balance.customer.equals(customer.publicKey));
+      });
+
+      it("Rejects redeeming more points than the balance holds", async () => {
+        try {
+          await redeem(51).rpc();
+          assert.fail("Should not redeem more than the balance");
+        } catch (error: any) {
+          assert.include(error.toString(), "InsufficientPoints");
+        }
+      });
+
+      it("Redeems points as a discount on a payment in the same transaction", async () => {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const merchantBefore = await provider.connection.getBalance(bistro.merchant.publicKey);
+
+        await payBistro(1_000_000, reference)
+          .preInstructions([await redeem(20).instruction()])
+          .rpc();
+
+        const receipt = await program.account.paymentReceipt.fetch(
+          receiptPda(bistro.merchantAccountPda, reference)
+        );
+        assert.equal(receipt.discount.toNumber(), 200_000);
+        assert.equal(receipt.amount.toNumber(), 800_000, "Receipt records what was actually paid");
+
+        const merchantAfter = await provider.connection.getBalance(bistro.merchant.publicKey);
+        assert.equal(merchantAfter - merchantBefore, receipt.merchantAmount.toNumber());
+
+        const balance = await program.account.loyaltyBalance.fetch(loyaltyBalance);
+        assert.equal(balance.points.toNumber(), 50 - 20 + 8, "Points earn on the discounted amount");
+        assert.equal(balance.pendingDiscount.toNumber(), 0);
+      });
+
+      it("Earns no points on escrow payments", async () => {
+        const reference = Keypair.generate().publicKey.toBuffer();
+        const receipt = receiptPda(bistro.merchantAccountPda, reference);
+        await program.methods
+          .escrowPaymentSol(new anchor.BN(1_000_000), [...reference])
+          .accounts({
+            config: configPda,
+            merchantAccount: bistro.merchantAccountPda,
+            merchant: bistro.merchant.publicKey,
+            receipt,
+            escrow: PublicKey.findProgramAddressSync([Buffer.from("escrow"), receipt.toBuffer()], program.programId)[0],
+            merchantStats: statsPda(bistro.merchantAccountPda),
+            mintStats: mintStatsPda(bistro.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(bistro.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        const balance = await program.account.loyaltyBalance.fetch(loyaltyBalance);
+        assert.equal(balance.points.toNumber(), 38, "Held funds may still be refunded, so they earn nothing");
+      });
+
+      it("Stops earning and redemption but honours pending discounts while paused", async () => {
+        // Redeem on its own so the discount is still pending when the program pauses
+        await redeem(10).rpc();
+
+        await program.methods
+          .updateLoyaltyProgram(null, null, null, false)
+          .accounts({
+            merchantAccount: bistro.merchantAccountPda,
+            loyaltyProgram,
+            authority: bistro.merchant.publicKey,
+            delegate: null,
+          })
+          .signers([bistro.merchant])
+          .rpc();
+
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await payBistro(1_000_000, reference).rpc();
+        const receipt = await program.account.paymentReceipt.fetch(receiptPda(bistro.merchantAccountPda, reference));
+        assert.equal(receipt.discount.toNumber(), 10 * REDEEM_VALUE, "Discounts redeemed before the pause still apply");
+        const balance = await program.account.loyaltyBalance.fetch(loyaltyBalance);
+        assert.equal(balance.points.toNumber(), 28, "Paused programs should not credit points");
+        assert.equal(balance.pendingDiscount.toNumber(), 0);
+
+        try {
+          await redeem(1).rpc();
+          assert.fail("Paused programs should not redeem");
+        } catch (error: any) {
+          assert.include(error.toString(), "LoyaltyProgramInactive");
+        }
+      });
+
+      it("Initializes a balance created by a payment that earns nothing", async () => {
+        const newcomer = Keypair.generate();
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(newcomer.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+        );
+        const [newcomerBalance, bump] = PublicKey.findProgramAddressSync(
+          [Buffer.from("loyalty"), bistro.merchantAccountPda.toBuffer(), newcomer.publicKey.toBuffer()],
+          program.programId
+        );
+        const reference = Keypair.generate().publicKey.toBuffer();
+
+        // The program is still paused, so this payment earns no points
+        await program.methods
+          .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+          .accounts({
+            config: configPda,
+            merchantAccount: bistro.merchantAccountPda,
+            merchant: bistro.merchant.publicKey,
+            treasury: treasury.publicKey,
+            receipt: receiptPda(bistro.merchantAccountPda, reference),
+            merchantStats: statsPda(bistro.merchantAccountPda),
+            mintStats: mintStatsPda(bistro.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(bistro.merchantAccountPda, newcomer.publicKey),
+            loyaltyProgram,
+            loyaltyBalance: newcomerBalance,
+            payer: newcomer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([newcomer])
+          .rpc();
+
+        const balance = await program.account.loyaltyBalance.fetch(newcomerBalance);
+        assert.equal(balance.points.toNumber(), 0);
+        assert.equal(balance.bump, bump);
+        assert.ok(balance.customer.equals(newcomer.publicKey));
+        assert.ok(balance.merchantAccount.equals(bistro.merchantAccountPda));
+
+        const programAccount = await program.account.loyaltyProgram.fetch(loyaltyProgram);
+        assert.equal(programAccount.balanceCount, 2);
+      });
+
+      it("Closes the program only once every balance is closed", async () => {
+        const closeProgram = () =>
+          program.methods
+            .closeLoyaltyProgram()
+            .accounts({
+              merchantAccount: bistro.merchantAccountPda,
+              loyaltyProgram,
+              authority: bistro.merchant.publicKey,
+              delegate: null,
+            })
+            .signers([bistro.merchant])
+            .rpc();
+
+        try {
+          await closeProgram();
+          assert.fail("A program with customer balances should not close");
+        } catch (error: any) {
+          assert.include(error.toString(), "LoyaltyProgramHasBalances");
+        }
+
+        // The customer closes their own balance; the merchant may close the rest while paused
+        await program.methods
+          .closeLoyaltyBalance()
+          .accounts({
+            merchantAccount: bistro.merchantAccountPda,
+            loyaltyProgram,
+            loyaltyBalance,
+            customer: customer.publicKey,
+            authority: customer.publicKey,
+            delegate: null,
+          })
+          .signers([customer])
+          .rpc();
+
+        const programAccount = await program.account.loyaltyProgram.fetch(loyaltyProgram);
+        assert.equal(programAccount.balanceCount, 1);
+        const [remaining] = (
+          await program.account.loyaltyBalance.all([
+            { memcmp: { offset: 8, bytes: bistro.merchantAccountPda.toBase58() } },
+          ])
+        ).map((entry) => entry.account.customer);
+
+        await program.methods
+          .closeLoyaltyBalance()
+          .accounts({
+            merchantAccount: bistro.merchantAccountPda,
+            loyaltyProgram,
+            loyaltyBalance: PublicKey.findProgramAddressSync(
+              [Buffer.from("loyalty"), bistro.merchantAccountPda.toBuffer(), remaining.toBuffer()],
+              program.programId
+            )[0],
+            customer: remaining,
+            authority: bistro.merchant.publicKey,
+            delegate: null,
+          })
+          .signers([bistro.merchant])
+          .rpc();
+
+        await closeProgram();
+        assert.isNull(await provider.connection.getAccountInfo(loyaltyProgram), "Program should be closed");
+        const merchantAccount = await program.account.merchantAccount.fetch(bistro.merchantAccountPda);
+        assert.isFalse(merchantAccount.hasLoyaltyProgram);
+      });
+    });
+
+    describe("stamp cards", () => {
+      const STAMPS_REQUIRED = 2;
+      const MIN_PURCHASE = 1_000_000;
+      let cafe: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      let stampCard: PublicKey;
+      let customerStamps: PublicKey;
+
+      function collectStamp(receipt: PublicKey) {
+        return program.methods
+          .collectStamp()
+          .accounts({ stampCard, receipt, customerStamps, customer: customer.publicKey, systemProgram: SystemProgram.programId })
+          .signers([customer])
+          .rpc();
+      }
+
+      before(async () => {
+        cafe = await registerTestMerchant("Stamp Card Cafe");
+        stampCard = PublicKey.findProgramAddressSync(
+          [Buffer.from("stamp_card"), cafe.merchantAccountPda.toBuffer()],
+          program.programId
+        )[0];
+        customerStamps = PublicKey.findProgramAddressSync(
+          [Buffer.from("stamps"), cafe.merchantAccountPda.toBuffer(), customer.publicKey.toBuffer()],
+          program.programId
+        )[0];
+
+        await program.methods
+          .createStampCard(NATIVE_SOL_MINT, STAMPS_REQUIRED, [...Buffer.alloc(32, 9)], new anchor.BN(MIN_PURCHASE))
+          .accounts({
+            merchantAccount: cafe.merchantAccountPda,
+            stampCard,
+            authority: cafe.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([cafe.merchant])
+          .rpc();
+      });
+
+      it("Stamps each qualifying receipt once", async () => {
+        const small = await paySol(cafe, customer, MIN_PURCHASE - 1);
+        try {
+          await collectStamp(small);
+          assert.fail("Purchases below the minimum should not earn a stamp");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReceiptNotQualifying");
+        }
+
+        const receipt = await paySol(cafe, customer, MIN_PURCHASE);
+        await collectStamp(receipt);
+
+        try {
+          await collectStamp(receipt);
+          assert.fail("A receipt should only earn one stamp");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReceiptAlreadyStamped");
+        }
+
+        const card = await program.account.customerStamps.fetch(customerStamps);
+        assert.equal(card.stamps, 1);
+      });
+
+      it("Needs the merchant to confirm the reward and resets the card", async () => {
+        const claim = (authority: Keypair) =>
+          program.methods
+            .claimStampReward()
+            .accounts({
+              merchantAccount: cafe.merchantAccountPda,
+              stampCard,
+              customerStamps,
+              customer: customer.publicKey,
+              authority: authority.publicKey,
+              delegate: null,
+            })
+            .signers(authority === customer ? [customer] : [customer, authority])
+            .rpc();
+
+        try {
+          await claim(cafe.merchant);
+          assert.fail("An incomplete card should not be claimable");
+        } catch (error: any) {
+          assert.include(error.toString(), "StampCardIncomplete");
+        }
+
+        await collectStamp(await paySol(cafe, customer, MIN_PURCHASE));
+
+        try {
+          await claim(customer);
+          assert.fail("Customers should not confirm their own rewards");
+        } catch (error: any) {
+          assert.include(error.toString(), "Unauthorized");
+        }
+
+        await claim(cafe.merchant);
+
+        const card = await program.account.customerStamps.fetch(customerStamps);
+        assert.equal(card.stamps, 0);
+        assert.equal(card.rewardsClaimed, 1);
+      });
+
+      it("Never stamps a refunded receipt and claws back the stamp of a refunded one", async () => {
+        const refund = (receipt: PublicKey, withCard = true) =>
+          program.methods
+            .refundPayment(new anchor.BN(1))
+            .accounts({
+              merchantAccount: cafe.merchantAccountPda,
+              receipt,
+              merchantStats: statsPda(cafe.merchantAccountPda),
+              mintStats: mintStatsPda(cafe.merchantAccountPda, NATIVE_SOL_MINT),
+              payer: customer.publicKey,
+              mint: null,
+              merchantTokenAccount: null,
+              refundVault: null,
+              vaultTokenAccount: null,
+              payerTokenAccount: null,
+              authority: cafe.merchant.publicKey,
+              delegate: null,
+              customerStamps: withCard ? customerStamps : null,
+              tokenProgram: null,
+              associatedTokenProgram: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([cafe.merchant])
+            .rpc();
+
+        const refunded = await paySol(cafe, customer, 2 * MIN_PURCHASE);
+        await refund(refunded);
+        try {
+          await collectStamp(refunded);
+          assert.fail("A refunded receipt should not earn a stamp");
+        } catch (error: any) {
+          assert.include(error.toString(), "ReceiptNotQualifying");
+        }
+
+        const stamped = await paySol(cafe, customer, MIN_PURCHASE);
+        await collectStamp(stamped);
+        try {
+          await refund(stamped, false);
+          assert.fail("Refunding a stamped receipt needs the customer's card");
+        } catch (error: any) {
+          assert.include(error.toString(), "MissingCustomerStamps");
+        }
+
+        await refund(stamped);
+        const card = await program.account.customerStamps.fetch(customerStamps);
+        assert.equal(card.stamps, 0, "The refunded receipt's stamp is taken back");
+        const receipt = await program.account.paymentReceipt.fetch(stamped);
+        assert.isFalse(receipt.stamped);
+      });
+    });
+
+    describe("coupons", () => {
+      const CODE = "SUMMER10";
+      const couponId = new anchor.BN(1);
+      let shop: { merchant: Keypair; merchantId: string; merchantAccountPda: PublicKey };
+      let coupon: PublicKey;
+      let couponUsage: PublicKey;
+
+      function payShop(reference: Buffer, withCoupon: { coupon: PublicKey; couponUsage: PublicKey | null } | null) {
+        return program.methods
+          .payMerchantSol(new anchor.BN(1_000_000), [...reference], new anchor.BN(0), false)
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchant: shop.merchant.publicKey,
+            treasury: treasury.publicKey,
+            receipt: receiptPda(shop.merchantAccountPda, reference),
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            coupon: withCoupon ? withCoupon.coupon : null,
+            couponUsage: withCoupon ? withCoupon.couponUsage : null,
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer]);
+      }
+
+      function clip(code: string | null) {
+        return program.methods
+          .clipCoupon(code)
+          .accounts({ coupon, couponUsage, wallet: customer.publicKey, systemProgram: SystemProgram.programId })
+          .signers([customer]);
+      }
+
+      before(async () => {
+        shop = await registerTestMerchant("Coupon Corner");
+        coupon = PublicKey.findProgramAddressSync(
+          [Buffer.from("coupon"), shop.merchantAccountPda.toBuffer(), couponId.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+        couponUsage = PublicKey.findProgramAddressSync(
+          [Buffer.from("coupon_usage"), coupon.toBuffer(), customer.publicKey.toBuffer()],
+          program.programId
+        )[0];
+      });
+
+      it("Rejects coupons that expire before they start", async () => {
+        const now = Math.floor(Date.now() / 1000);
+        try {
+          await program.methods
+            .createCoupon(
+              couponId,
+              NATIVE_SOL_MINT,
+              { percentage: { bps: 1_000 } },
+              2,
+              1,
+              new anchor.BN(now),
+              new anchor.BN(now - 1),
+              null
+            )
+            .accounts({
+              merchantAccount: shop.merchantAccountPda,
+              coupon,
+              authority: shop.merchant.publicKey,
+              delegate: null,
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([shop.merchant])
+            .rpc();
+          assert.fail("Invalid validity windows should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidCouponWindow");
+        }
+      });
+
+      it("Applies a code-protected percentage coupon once per wallet", async () => {
+        const codeHash = createHash("sha256").update(CODE).digest();
+        await program.methods
+          .createCoupon(couponId, NATIVE_SOL_MINT, { percentage: { bps: 1_000 } }, 2, 1, new anchor.BN(0), null, [...codeHash])
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            coupon,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        try {
+          await clip("WINTER10").rpc();
+          assert.fail("A wrong code should not clip the coupon");
+        } catch (error: any) {
+          assert.include(error.toString(), "InvalidCouponCode");
+        }
+
+        try {
+          await payShop(Keypair.generate().publicKey.toBuffer(), { coupon, couponUsage: null }).rpc();
+          assert.fail("Unclipped coupons should be rejected");
+        } catch (error: any) {
+          assert.include(error.toString(), "CouponNotClipped");
+        }
+
+        const reference = Keypair.generate().publicKey.toBuffer();
+        await payShop(reference, { coupon, couponUsage })
+          .preInstructions([await clip(CODE).instruction()])
+          .rpc();
+
+        const receipt = await program.account.paymentReceipt.fetch(receiptPda(shop.merchantAccountPda, reference));
+        assert.equal(receipt.discount.toNumber(), 100_000);
+        assert.equal(receipt.amount.toNumber(), 900_000);
+
+        const couponAccount = await program.account.coupon.fetch(coupon);
+        assert.equal(couponAccount.uses, 1);
+
+        try {
+          await payShop(Keypair.generate().publicKey.toBuffer(), { coupon, couponUsage }).rpc();
+          assert.fail("The per-wallet limit should apply");
+        } catch (error: any) {
+          assert.include(error.toString(), "CouponWalletLimitReached");
+        }
+      });
+
+      it("Does not apply coupons to escrow payments or payment requests", async () => {
+        const escrowReference = Keypair.generate().publicKey.toBuffer();
+        const escrowReceipt = receiptPda(shop.merchantAccountPda, escrowReference);
+        await program.methods
+          .escrowPaymentSol(new anchor.BN(1_000_000), [...escrowReference])
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchant: shop.merchant.publicKey,
+            receipt: escrowReceipt,
+            escrow: PublicKey.findProgramAddressSync([Buffer.from("escrow"), escrowReceipt.toBuffer()], program.programId)[0],
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            payer: customer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        const requestId = new anchor.BN(1);
+        const paymentRequest = PublicKey.findProgramAddressSync(
+          [Buffer.from("payment_request"), shop.merchantAccountPda.toBuffer(), requestId.toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+        await program.methods
+          .createPaymentRequest(requestId, NATIVE_SOL_MINT, new anchor.BN(1_000_000), "Coupon-free", null, false)
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            paymentRequest,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([shop.merchant])
+          .rpc();
+        const requestReference = Keypair.generate().publicKey.toBuffer();
+        await program.methods
+          .payPaymentRequest([...requestReference])
+          .accounts({
+            config: configPda,
+            merchantAccount: shop.merchantAccountPda,
+            merchant: shop.merchant.publicKey,
+            treasury: treasury.publicKey,
+            paymentRequest,
+            receipt: receiptPda(shop.merchantAccountPda, requestReference),
+            merchantStats: statsPda(shop.merchantAccountPda),
+            mintStats: mintStatsPda(shop.merchantAccountPda, NATIVE_SOL_MINT),
+            customerRecord: customerRecordPda(shop.merchantAccountPda, customer.publicKey),
+            mint: null,
+            acceptedMint: null,
+            globalAcceptedMint: null,
+            payerTokenAccount: null,
+            merchantTokenAccount: null,
+            treasuryTokenAccount: null,
+            payer: customer.publicKey,
+            tokenProgram: null,
+            associatedTokenProgram: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([customer])
+          .rpc();
+
+        for (const receipt of [escrowReceipt, receiptPda(shop.merchantAccountPda, requestReference)]) {
+          const receiptAccount = await program.account.paymentReceipt.fetch(receipt);
+          assert.equal(receiptAccount.discount.toNumber(), 0);
+          assert.equal(receiptAccount.amount.toNumber(), 1_000_000);
+        }
+        const couponAccount = await program.account.coupon.fetch(coupon);
+        assert.equal(couponAccount.uses, 1, "Neither payment should count a coupon use");
+      });
+
+      it("Lets the merchant end the promotion", async () => {
+        await program.methods
+          .closeCoupon()
+          .accounts({
+            merchantAccount: shop.merchantAccountPda,
+            coupon,
+            authority: shop.merchant.publicKey,
+            delegate: null,
+          })
+          .signers([shop.merchant])
+          .rpc();
+
+        assert.isNull(await provider.connection.getAccountInfo(coupon), "Coupon should be closed");
+      });
+    });
+  });
+
+  describe("Registration Fee Options", () => {
+    async function fundedWallet(): Promise<Keypair> {
+      const wallet = Keypair.generate();
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(wallet.publicKey, 2 * anchor.web3.LAMPORTS_PER_SOL)
+      );
+      return wallet;
+    }
+
+    function merchantPdaFor(wallet: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync([Buffer.from("merchant"), wallet.toBuffer()], program.programId)[0];
+    }
+
+    function feeWaiverPda(wallet: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync([Buffer.from("fee_waiver"), wallet.toBuffer()], program.programId)[0];
+    }
+
+    it("Registers for free with a single-use fee waiver", async () => {
+      const partner = await fundedWallet();
+      const merchantAccountPda = merchantPdaFor(partner.publicKey);
+      const feeWaiver = feeWaiverPda(partner.publicKey);
+
+      await program.methods
+        .issueFeeWaiver(partner.publicKey)
+        .accounts({ config: configPda, feeWaiver, admin: payer.publicKey, systemProgram: SystemProgram.programId })
+        .rpc();
+
+      const treasuryBefore = await provider.connection.getBalance(treasury.publicKey);
+      await program.methods
+        .registerMerchant("partner_" + Date.now(), "Partner Bakery", new anchor.BN(validLat), new anchor.BN(validLng))
+        .accounts({
+          merchantAccount: merchantAccountPda,
+          merchantStats: statsPda(merchantAccountPda),
+          merchant: partner.publicKey,
+          config: configPda,
+          treasury: treasury.publicKey,
+          feeWaiver,
+          feeMint: null,
+          feeAcceptedMint: null,
+          merchantFeeTokenAccount: null,
+          treasuryFeeTokenAccount: null,
+          tokenProgram: null,
+          associatedTokenProgram: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([partner])
+        .rpc();
+      const treasuryAfter = await provider.connection.getBalance(treasury.publicKey);
+
+      assert.equal(treasuryAfter, treasuryBefore, "No fee should be charged");
+      assert.isNull(await provider.connection.getAccountInfo(feeWaiver), "Waiver should be consumed");
+    });
+
+    it("Lets only the admin issue waivers", async () => {
+      const attacker = await fundedWallet();
+
+      try {
+        await program.methods
+          .issueFeeWaiver(attacker.publicKey)
+          .accounts({
+            config: configPda,
+            feeWaiver: feeWaiverPda(attacker.publicKey),
+            admin: attacker.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([attacker])
+          .rpc();
+        assert.fail("Non-admin should not issue waivers");
+      } catch (error: any) {
+        assert.include(error.toString(), "Unauthorized");
+      }
+    });
+
+    it("Charges the registration fee in an accepted SPL mint", async () => {
+      const TOKEN_REGISTRATION_FEE = 25_000_000; // 25 units of a 6-decimal stablecoin
+      const merchant = await fundedWallet();
+      const merchantAccountPda = merchantPdaFor(merchant.publicKey);
+
+      const feeMint = await createMint(provider.connection, payer.payer, payer.publicKey, null, 6);
+      const feeAcceptedMint = PublicKey.findProgramAddressSync(
+        [Buffer.from("accepted_mint"), configPda.toBuffer(), feeMint.toBuffer()],
+        program.programId
+      )[0];
+      await program.methods
+        .addAcceptedMint(new anchor.BN(0), new anchor.BN(TOKEN_REGISTRATION_FEE))
+        .accounts({
+          config: configPda,
+          mint: feeMint,
+          acceptedMint: feeAcceptedMint,
+          admin: payer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const merchantAta = await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        payer.payer,
+        feeMint,
+        merchant.publicKey
+      );
+      await mintTo(provider.connection, payer.payer, feeMint, merchantAta.address, payer.publicKey, 30_000_000);
+
+      const treasuryAta = getAssociatedTokenAddressSync(feeMint, treasury.publicKey);
+      await program.methods
+        .registerMerchant("usdc_" + Date.now(), "Stablecoin Store", new anchor.BN(validLat), new anchor.BN(validLng))
+        .accounts({
+          merchantAccount: merchantAccountPda,
+          merchantStats: statsPda(merchantAccountPda),
+          merchant: merchant.publicKey,
+          config: configPda,
+          treasury: treasury.publicKey,
+          feeWaiver: null,
+          feeMint,
+          feeAcceptedMint,
+          merchantFeeTokenAccount: merchantAta.address,
+          treasuryFeeTokenAccount: treasuryAta,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchant])
+        .rpc();
+
+      const treasuryAccount = await getAccount(provider.connection, treasuryAta);
+      assert.equal(Number(treasuryAccount.amount), TOKEN_REGISTRATION_FEE);
+      const merchantAccount = await getAccount(provider.connection, merchantAta.address);
+      assert.equal(Number(merchantAccount.amount), 30_000_000 - TOKEN_REGISTRATION_FEE);
+    });
   });
 });